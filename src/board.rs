@@ -1,9 +1,13 @@
+use std::collections::HashMap;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::thread;
 
 use crate::{
   movegen,
   moves::{self, Move, MoveList},
   nnue,
+  tt::PerftTable,
   types::{Accumulator, Bitboard, Color, PieceType, Square},
   zobrist,
 };
@@ -17,9 +21,73 @@ pub struct UndoInfo {
   pub old_halfmove_clock: u8,
   pub captured_piece: u8,
   pub old_zobrist_hash: ZHash,
+  pub old_pawn_hash: ZHash,
+}
+
+/// Why `Board::make_move_checked` refused a move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveError {
+  /// The from-square holds no piece of the side to move.
+  NoPiece,
+  /// The move is not among the position's pseudo-legal moves.
+  NotPseudoLegal,
+  /// The move would leave the mover's king in check.
+  LeavesKingInCheck,
+}
+
+impl fmt::Display for MoveError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let msg = match self {
+      MoveError::NoPiece => "no piece of the side to move on the from-square",
+      MoveError::NotPseudoLegal => "move is not pseudo-legal",
+      MoveError::LeavesKingInCheck => "move leaves the king in check",
+    };
+    f.write_str(msg)
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DrawReason {
+  Stalemate,
+  InsufficientMaterial,
+  ThreefoldRepetition,
+  FiftyMoveRule,
+  /// Drawn without a claim: the position occurred a fifth time.
+  FivefoldRepetition,
+  /// Drawn without a claim: 75 moves by each side without a capture or pawn move.
+  SeventyFiveMoveRule,
+}
+
+/// Outcome of a position, see `Board::game_result`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameResult {
+  /// The game is not over (or, in `game::play_game`, was stopped early).
+  Ongoing,
+  WhiteWins,
+  BlackWins,
+  Draw(DrawReason),
+}
+
+/// Leaf-level breakdown of a perft run, in the categories published for
+/// the standard perft positions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PerftStats {
+  pub nodes: u64,
+  /// Includes en passant captures.
+  pub captures: u64,
+  pub en_passant: u64,
+  pub castles: u64,
+  pub promotions: u64,
+  pub checks: u64,
+  pub checkmates: u64,
 }
 
 #[derive(Clone)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(into = "BoardRepr", try_from = "BoardRepr")
+)]
 pub struct Board {
   pub pieces: [[Bitboard; 2]; 6],
   pub occupancy: [Bitboard; 3],
@@ -29,82 +97,35 @@ pub struct Board {
   pub halfmove_clock: u8,
   pub fullmove_number: u32,
   pub zobrist_hash: ZHash,
+  /// Zobrist key over the pawns alone, for the evaluator's pawn hash table.
+  pub pawn_hash: ZHash,
   pub history: Vec<UndoInfo>,
   pub accumulator: [Accumulator; 2],
+  /// Accumulators before each move in `history`, restored by `unmake_move`.
+  pub accumulator_stack: Vec<[Accumulator; 2]>,
   pub king_sq: [Square; 2],
+  /// Set when the position's castling rooks or king start off the standard
+  /// squares, see `from_fen`.
+  pub chess960: bool,
+  /// Start square of the castling rook for each right, indexed like the bits
+  /// of `castling_rights`: white king side, white queen side, then black.
+  pub castling_rooks: [Square; 4],
+  castle_mask: [u8; 64],
 }
 
-const WK_CASTLE: u8 = 0b0001;
-const WQ_CASTLE: u8 = 0b0010;
-const BK_CASTLE: u8 = 0b0100;
-const BQ_CASTLE: u8 = 0b1000;
-
-static CASTLE_MASK: [u8; 64] = [
-  !WQ_CASTLE,
-  0xFF,
-  0xFF,
-  0xFF,
-  !(WK_CASTLE | WQ_CASTLE),
-  0xFF,
-  0xFF,
-  !WK_CASTLE,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  0xFF,
-  !BQ_CASTLE,
-  0xFF,
-  0xFF,
-  0xFF,
-  !(BK_CASTLE | BQ_CASTLE),
-  0xFF,
-  0xFF,
-  !BK_CASTLE,
-];
+const STANDARD_CASTLING_ROOKS: [Square; 4] = [7, 0, 63, 56];
+
+/// Rights lost when a piece moves from or to each square: those of the king
+/// on its start square and of each castling rook.
+fn castle_mask(king_sq: [Square; 2], castling_rooks: [Square; 4]) -> [u8; 64] {
+  let mut mask = [0xFF; 64];
+  for (right, &rook_sq) in castling_rooks.iter().enumerate() {
+    let bit = 1u8 << right;
+    mask[rook_sq as usize] &= !bit;
+    mask[king_sq[right / 2] as usize] &= !bit;
+  }
+  mask
+}
 
 impl Board {
 
@@ -118,32 +139,46 @@ impl Board {
         halfmove_clock: self.halfmove_clock,
         fullmove_number: self.fullmove_number,
         zobrist_hash: self.zobrist_hash,
+        pawn_hash: self.pawn_hash,
         history: Vec::with_capacity(128),
         accumulator: self.accumulator,
+        accumulator_stack: Vec::new(),
         king_sq: self.king_sq,
+        chess960: self.chess960,
+        castling_rooks: self.castling_rooks,
+        castle_mask: self.castle_mask,
       }
     }
     pub fn from_fen(fen: &str) -> Result<Board, &'static str> {
         let mut board = Board::default();
         let parts: Vec<&str> = fen.split_whitespace().collect();
-        if parts.len() != 6 {
-          return Err("Invalid FEN: must have 6 fields");
-        }
-
-        let piece_placement = parts[0];
-        let mut rank = 7;
-        let mut file = 0;
-        for ch in piece_placement.chars() {
-            if ch == '/' {
-                rank -= 1;
-                file = 0;
-            } else if let Some(digit) = ch.to_digit(10) {
-                file += digit as u8;
-            } else {
+        // GUIs sometimes omit the move counters; default them like an EPD.
+        if parts.len() < 4 || parts.len() > 6 {
+          return Err("Invalid FEN: must have 4 to 6 fields");
+        }
+
+        let ranks: Vec<&str> = parts[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err("Invalid FEN: piece placement must have 8 ranks");
+        }
+        for (i, rank_str) in ranks.iter().enumerate() {
+            let rank = 7 - i as u8;
+            let mut file = 0u8;
+            for ch in rank_str.chars() {
+                if let Some(digit) = ch.to_digit(10) {
+                    if digit == 0 || digit > 8 {
+                        return Err("Invalid FEN: empty-square count must be 1 to 8");
+                    }
+                    file += digit as u8;
+                    if file > 8 {
+                        return Err("Invalid FEN: a rank has more than 8 squares");
+                    }
+                    continue;
+                }
+
                 if file > 7 {
-                    return Err("Invalid FEN: piece placement");
+                    return Err("Invalid FEN: a rank has more than 8 squares");
                 }
-                let square = rank * 8 + file;
                 let color = if ch.is_uppercase() {
                     Color::White
                 } else {
@@ -156,49 +191,83 @@ impl Board {
                     'r' => PieceType::Rook,
                     'q' => PieceType::Queen,
                     'k' => PieceType::King,
-                    _ => return Err("Invalid FEN: Unknown piece"),
+                    _ => return Err("Invalid FEN: unknown piece letter"),
                 };
-                board.add_piece(piece_type, color, square);
+                board.add_piece(piece_type, color, rank * 8 + file);
                 file += 1;
             }
+            if file < 8 {
+                return Err("Invalid FEN: a rank has fewer than 8 squares");
+            }
         }
 
+        board.validate_material()?;
+
         board.side_to_move = match parts[1] {
             "w" => Color::White,
             "b" => Color::Black,
-            _ => return Err("Invalid side"),
+            _ => return Err("Invalid FEN: side to move must be w or b"),
         };
 
+        board.king_sq[Color::White as usize] = 
+            board.pieces[PieceType::King as usize][Color::White as usize].trailing_zeros() as Square;
+        board.king_sq[Color::Black as usize] = 
+            board.pieces[PieceType::King as usize][Color::Black as usize].trailing_zeros() as Square;
+
+        // As in X-FEN, KQkq refer to the outermost rook on that side of the
+        // king; Shredder-FEN file letters (HAha) name the rook's file and
+        // always mark a Chess960 game. Rights with no such rook, or no king
+        // on the back rank, are dropped.
+        let castling_ok = parts[2] == "-"
+            || parts[2].chars().all(|c| "KQkqABCDEFGHabcdefgh".contains(c));
+        if !castling_ok {
+            return Err("Invalid FEN: castling field");
+        }
+
         board.castling_rights = 0;
+        board.castling_rooks = STANDARD_CASTLING_ROOKS;
+        let mut file_letters = false;
         for ch in parts[2].chars() {
-            match ch {
-                'K' => board.castling_rights |= 0b0001,
-                'Q' => board.castling_rights |= 0b0010,
-                'k' => board.castling_rights |= 0b0100,
-                'q' => board.castling_rights |= 0b1000,
-                _ => {}
+            let (color, rook_sq) = match ch {
+                'K' => (Color::White, board.find_castling_rook(Color::White, true)),
+                'Q' => (Color::White, board.find_castling_rook(Color::White, false)),
+                'k' => (Color::Black, board.find_castling_rook(Color::Black, true)),
+                'q' => (Color::Black, board.find_castling_rook(Color::Black, false)),
+                'A'..='H' => (Color::White, board.castling_rook_on_file(Color::White, ch as u8 - b'A')),
+                'a'..='h' => (Color::Black, board.castling_rook_on_file(Color::Black, ch as u8 - b'a')),
+                _ => continue,
+            };
+            file_letters |= !"KQkq".contains(ch);
+            if let Some(rook_sq) = rook_sq {
+                let kingside = rook_sq > board.king_sq[color as usize];
+                let right = color as usize * 2 + !kingside as usize;
+                board.castling_rights |= 1 << right;
+                board.castling_rooks[right] = rook_sq;
             }
         }
-
-        board.en_passant = if parts[3] == "-" {
-            None
-        } else {
-            let chars: Vec<char> = parts[3].chars().collect();
-            let f = (chars[0] as u8) - b'a';
-            let r = (chars[1] as u8) - b'1';
-            Some(r * 8 + f)
+        board.chess960 = file_letters || (0..4).any(|right| {
+            board.castling_rights & (1 << right) != 0
+                && (board.king_sq[right / 2] % 8 != 4
+                    || board.castling_rooks[right] != STANDARD_CASTLING_ROOKS[right])
+        });
+        board.castle_mask = castle_mask(board.king_sq, board.castling_rooks);
+
+        // The square behind a pawn that just double-pushed: rank 6 with
+        // white to move, rank 3 with black
+        let ep_rank = if board.side_to_move == Color::White { b'6' } else { b'3' };
+        board.en_passant = match parts[3].as_bytes() {
+            [b'-'] => None,
+            &[f @ b'a'..=b'h', r] if r == ep_rank => Some((r - b'1') * 8 + (f - b'a')),
+            _ => return Err("Invalid FEN: en passant square"),
         };
 
-        board.halfmove_clock = parts[4].parse().unwrap_or(0);
-        board.fullmove_number = parts[5].parse().unwrap_or(1);
-
-        board.king_sq[Color::White as usize] = 
-            board.pieces[PieceType::King as usize][Color::White as usize].trailing_zeros() as Square;
-        board.king_sq[Color::Black as usize] = 
-            board.pieces[PieceType::King as usize][Color::Black as usize].trailing_zeros() as Square;
+        // Counters are optional, and ones that do not parse take the same
+        // defaults as missing ones
+        board.halfmove_clock = parts.get(4).and_then(|p| p.parse().ok()).unwrap_or(0);
+        board.fullmove_number = parts.get(5).and_then(|p| p.parse().ok()).unwrap_or(1);
 
-        
         board.zobrist_hash = board.calculate_zobrist_hash();
+        board.pawn_hash = board.calculate_pawn_hash();
 
         // Initialize NNUE
         if nnue::is_enabled() {
@@ -208,6 +277,111 @@ impl Board {
         Ok(board)
     }
 
+    /// Outermost rook of `color` on its back rank on the given side of the
+    /// king, or `None` if there is none or the king is off the back rank.
+    fn find_castling_rook(&self, color: Color, kingside: bool) -> Option<Square> {
+        let king_sq = self.king_sq[color as usize];
+        let back_rank = if color == Color::White { 0 } else { 7 };
+        if king_sq / 8 != back_rank {
+            return None;
+        }
+
+        let rooks = self.pieces[PieceType::Rook as usize][color as usize] & (0xFF << (back_rank * 8));
+        let beyond_king = if kingside {
+            rooks & !((2u64 << king_sq) - 1)
+        } else {
+            rooks & ((1u64 << king_sq) - 1)
+        };
+        match (beyond_king, kingside) {
+            (0, _) => None,
+            (rooks, true) => Some(63 - rooks.leading_zeros() as Square),
+            (rooks, false) => Some(rooks.trailing_zeros() as Square),
+        }
+    }
+
+    /// The rook of `color` on `file` of its back rank, if it can castle
+    /// with a king on the same rank.
+    fn castling_rook_on_file(&self, color: Color, file: u8) -> Option<Square> {
+        let king_sq = self.king_sq[color as usize];
+        let rook_sq = if color == Color::White { file } else { 56 + file };
+        let rooks = self.pieces[PieceType::Rook as usize][color as usize];
+        (king_sq / 8 == rook_sq / 8 && rooks & (1u64 << rook_sq) != 0).then_some(rook_sq)
+    }
+
+    /// Rook source and destination squares for a castling move by `us`.
+    pub fn castle_rook_squares(&self, us: Color, flag: u16) -> (Square, Square) {
+        let base = if us == Color::White { 0 } else { 56 };
+        if flag == moves::KING_CASTLE_FLAG {
+            (self.castling_rooks[us as usize * 2], base + 5)
+        } else {
+            (self.castling_rooks[us as usize * 2 + 1], base + 3)
+        }
+    }
+
+    /// Parses an EPD record: the first four FEN fields followed by `;`
+    /// terminated operations such as `bm Nf3; id "WAC.001";`. Operands are
+    /// returned with surrounding quotes removed; `hmvc` and `fmvn` also set
+    /// the move counters, which otherwise default to 0 and 1.
+    pub fn from_epd(epd: &str) -> Result<(Board, HashMap<String, String>), &'static str> {
+        let mut fields = Vec::with_capacity(4);
+        let mut rest = epd.trim();
+        for _ in 0..4 {
+            let (field, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if field.is_empty() {
+                return Err("Invalid EPD: must have 4 position fields");
+            }
+            fields.push(field);
+            rest = tail.trim_start();
+        }
+
+        let mut board = Board::from_fen(&fields.join(" "))?;
+        let mut ops = HashMap::new();
+
+        let mut in_quotes = false;
+        let mut start = 0;
+        for (i, ch) in rest.char_indices() {
+            match ch {
+                '"' => in_quotes = !in_quotes,
+                ';' if !in_quotes => {
+                    Self::push_epd_op(&rest[start..i], &mut ops)?;
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        if in_quotes {
+            return Err("Invalid EPD: unterminated string operand");
+        }
+        Self::push_epd_op(&rest[start..], &mut ops)?;
+
+        if let Some(hmvc) = ops.get("hmvc") {
+            board.halfmove_clock = hmvc.parse().map_err(|_| "Invalid EPD: hmvc")?;
+        }
+        if let Some(fmvn) = ops.get("fmvn") {
+            board.fullmove_number = fmvn.parse().map_err(|_| "Invalid EPD: fmvn")?;
+        }
+
+        Ok((board, ops))
+    }
+
+    fn push_epd_op(op: &str, ops: &mut HashMap<String, String>) -> Result<(), &'static str> {
+        let op = op.trim();
+        if op.is_empty() {
+            return Ok(());
+        }
+        let (opcode, operands) = op.split_once(char::is_whitespace).unwrap_or((op, ""));
+        if !opcode.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return Err("Invalid EPD: bad opcode");
+        }
+        let operands = operands.trim();
+        let operands = operands
+            .strip_prefix('"')
+            .and_then(|o| o.strip_suffix('"'))
+            .unwrap_or(operands);
+        ops.insert(opcode.to_string(), operands.to_string());
+        Ok(())
+    }
+
     pub fn to_fen(&self) -> String {
         let mut fen = String::with_capacity(90);
         for rank in (0..8).rev() {
@@ -218,7 +392,7 @@ impl Board {
                 let mut piece_char = None;
                 for pt_idx in 0..6 {
                     if (self.pieces[pt_idx][Color::White as usize] & bit) != 0 {
-                        piece_char = Some(match pt_idx.into() {
+                        piece_char = Some(match PieceType::from_index(pt_idx) {
                             PieceType::Pawn => 'P',
                             PieceType::Knight => 'N',
                             PieceType::Bishop => 'B',
@@ -229,7 +403,7 @@ impl Board {
                         break;
                     }
                     if (self.pieces[pt_idx][Color::Black as usize] & bit) != 0 {
-                        piece_char = Some(match pt_idx.into() {
+                        piece_char = Some(match PieceType::from_index(pt_idx) {
                             PieceType::Pawn => 'p',
                             PieceType::Knight => 'n',
                             PieceType::Bishop => 'b',
@@ -263,18 +437,18 @@ impl Board {
             Color::Black => 'b',
         });
         fen.push(' ');
+        // Chess960 games are written Shredder-style, naming each rook's file
         let mut castling_str = String::new();
-        if self.castling_rights & 0b0001 != 0 {
-            castling_str.push('K');
-        }
-        if self.castling_rights & 0b0010 != 0 {
-            castling_str.push('Q');
-        }
-        if self.castling_rights & 0b0100 != 0 {
-            castling_str.push('k');
-        }
-        if self.castling_rights & 0b1000 != 0 {
-            castling_str.push('q');
+        for (right, symbol) in "KQkq".chars().enumerate() {
+            if self.castling_rights & (1 << right) == 0 {
+                continue;
+            }
+            if self.chess960 {
+                let file = (b'a' + self.castling_rooks[right] % 8) as char;
+                castling_str.push(if right < 2 { file.to_ascii_uppercase() } else { file });
+            } else {
+                castling_str.push(symbol);
+            }
         }
         if castling_str.is_empty() {
             fen.push('-');
@@ -301,7 +475,7 @@ impl Board {
         let bit = 1 << sq;
         for pt_idx in 0..6 {
             if (self.pieces[pt_idx][0] | self.pieces[pt_idx][1]) & bit != 0 {
-                return Some(PieceType::from(pt_idx));
+                return Some(PieceType::from_index(pt_idx));
             }
         }
         None
@@ -314,6 +488,41 @@ impl Board {
         self.occupancy[2] ^= from_to_bb;
     }
 
+    /// Rejects placements no legal game can reach: anything but one king per
+    /// side, pawns on the back ranks, or more pieces than promotions allow.
+    fn validate_material(&self) -> Result<(), &'static str> {
+        const BACK_RANKS: Bitboard = 0xFF00_0000_0000_00FF;
+
+        for c in 0..2 {
+            match (c, self.pieces[PieceType::King as usize][c].count_ones()) {
+                (_, 1) => {}
+                (0, 0) => return Err("Invalid FEN: white has no king"),
+                (1, 0) => return Err("Invalid FEN: black has no king"),
+                (0, _) => return Err("Invalid FEN: white has more than one king"),
+                _ => return Err("Invalid FEN: black has more than one king"),
+            }
+
+            let pawns = self.pieces[PieceType::Pawn as usize][c];
+            if pawns & BACK_RANKS != 0 {
+                return Err("Invalid FEN: pawn on the first or eighth rank");
+            }
+            if pawns.count_ones() > 8 {
+                return Err("Invalid FEN: more than 8 pawns");
+            }
+
+            let count = |pt: PieceType| self.pieces[pt as usize][c].count_ones();
+            let promoted = count(PieceType::Knight).saturating_sub(2)
+                + count(PieceType::Bishop).saturating_sub(2)
+                + count(PieceType::Rook).saturating_sub(2)
+                + count(PieceType::Queen).saturating_sub(1);
+            if promoted > 8 - pawns.count_ones() {
+                return Err("Invalid FEN: too many pieces for the remaining pawns");
+            }
+        }
+
+        Ok(())
+    }
+
     fn add_piece(&mut self, pt: PieceType, c: Color, sq: Square) {
         let bit = 1 << sq;
         self.pieces[pt as usize][c as usize] |= bit;
@@ -328,6 +537,15 @@ impl Board {
         self.occupancy[2] &= !bit;
     }
 
+    /// The en passant square if a pawn of the side to move can actually
+    /// capture there. Only then is it part of the hash and of `==`.
+    fn capturable_en_passant(&self) -> Option<Square> {
+        let us = self.side_to_move;
+        let them = if us == Color::White { Color::Black } else { Color::White };
+        let our_pawns = self.pieces[PieceType::Pawn as usize][us as usize];
+        self.en_passant.filter(|&sq| movegen::pawn_attacks(them, sq) & our_pawns != 0)
+    }
+
     fn calculate_zobrist_hash(&self) -> ZHash {
         let keys = zobrist::keys();
         let mut hash: ZHash = 0;
@@ -342,19 +560,8 @@ impl Board {
             }
         }
         hash ^= keys.castling[self.castling_rights as usize];
-        if let Some(sq) = self.en_passant {
-            let us = self.side_to_move;
-            let our_pawns = self.pieces[PieceType::Pawn as usize][us as usize];
-            let them = if us == Color::White {
-                Color::Black
-            } else {
-                Color::White
-            };
-            let potential_attackers = movegen::pawn_attacks(them, sq);
-            if (potential_attackers & our_pawns) != 0 {
-                let file = (sq % 8) as usize;
-                hash ^= keys.en_passant_file[file];
-            }
+        if let Some(sq) = self.capturable_en_passant() {
+            hash ^= keys.en_passant_file[(sq % 8) as usize];
         }
         if self.side_to_move == Color::White {
             hash ^= keys.side_to_move;
@@ -362,9 +569,24 @@ impl Board {
         hash
     }
 
+    fn calculate_pawn_hash(&self) -> ZHash {
+        let keys = zobrist::keys();
+        let mut hash: ZHash = 0;
+        for c_idx in 0..2 {
+            let mut bb = self.pieces[PieceType::Pawn as usize][c_idx];
+            while bb != 0 {
+                let sq = bb.trailing_zeros() as usize;
+                hash ^= keys.pieces[PieceType::Pawn as usize][c_idx][sq];
+                bb &= bb - 1;
+            }
+        }
+        hash
+    }
+
     pub fn make_move(&mut self, m: Move) -> UndoInfo {
         let keys = zobrist::keys();
         let mut hash = self.zobrist_hash;
+        let mut pawn_hash = self.pawn_hash;
         let from = moves::from_sq(m);
         let to = moves::to_sq(m);
         let flag = moves::flag(m);
@@ -392,15 +614,12 @@ impl Board {
         old_halfmove_clock: self.halfmove_clock,
         captured_piece: captured_byte,
         old_zobrist_hash: self.zobrist_hash,
+        old_pawn_hash: self.pawn_hash,
         };
         self.history.push(undo.clone());
-
-        // NNUE Incremental Updates
         let has_nnue = nnue::is_enabled();
         if has_nnue {
-            if moving_piece != PieceType::King {
-                self.apply_nnue_updates(m, moving_piece, captured, us, them, true);
-            }
+            self.accumulator_stack.push(self.accumulator);
         }
 
         // Update Board State
@@ -418,19 +637,39 @@ impl Board {
                 let captured_sq = if us == Color::White { to - 8 } else { to + 8 };
                 self.remove_piece(PieceType::Pawn, them, captured_sq);
                 hash ^= keys.pieces[PieceType::Pawn as usize][them as usize][captured_sq as usize];
+                pawn_hash ^= keys.pieces[PieceType::Pawn as usize][them as usize][captured_sq as usize];
             } else {
                 self.remove_piece(cap_pt, them, to);
                 hash ^= keys.pieces[cap_pt as usize][them as usize][to as usize];
+                if cap_pt == PieceType::Pawn {
+                    pawn_hash ^= keys.pieces[PieceType::Pawn as usize][them as usize][to as usize];
+                }
             }
         }
 
-        self.move_piece(moving_piece, us, from, to);
+        if flag == moves::KING_CASTLE_FLAG || flag == moves::QUEEN_CASTLE_FLAG {
+            // In Chess960 the king or rook may end on the other's start
+            // square (or its own), so lift both before placing them
+            let (rf, rt) = self.castle_rook_squares(us, flag);
+            self.remove_piece(PieceType::King, us, from);
+            self.remove_piece(PieceType::Rook, us, rf);
+            self.add_piece(PieceType::King, us, to);
+            self.add_piece(PieceType::Rook, us, rt);
+            hash ^= keys.pieces[PieceType::Rook as usize][us as usize][rf as usize];
+            hash ^= keys.pieces[PieceType::Rook as usize][us as usize][rt as usize];
+        } else {
+            self.move_piece(moving_piece, us, from, to);
+        }
         if moving_piece == PieceType::King {
             self.king_sq[us as usize] = to;
         }
 
         hash ^= keys.pieces[moving_piece as usize][us as usize][from as usize];
         hash ^= keys.pieces[moving_piece as usize][us as usize][to as usize];
+        if moving_piece == PieceType::Pawn {
+            pawn_hash ^= keys.pieces[PieceType::Pawn as usize][us as usize][from as usize];
+            pawn_hash ^= keys.pieces[PieceType::Pawn as usize][us as usize][to as usize];
+        }
 
         if moves::is_promotion(m) {
             let promo = moves::promotion_piece(m);
@@ -438,16 +677,7 @@ impl Board {
             self.add_piece(promo, us, to);
             hash ^= keys.pieces[PieceType::Pawn as usize][us as usize][to as usize];
             hash ^= keys.pieces[promo as usize][us as usize][to as usize];
-        } else if flag == moves::KING_CASTLE_FLAG {
-            let (rf, rt) = if us == Color::White { (7, 5) } else { (63, 61) };
-            self.move_piece(PieceType::Rook, us, rf, rt);
-            hash ^= keys.pieces[PieceType::Rook as usize][us as usize][rf as usize];
-            hash ^= keys.pieces[PieceType::Rook as usize][us as usize][rt as usize];
-        } else if flag == moves::QUEEN_CASTLE_FLAG {
-            let (rf, rt) = if us == Color::White { (0, 3) } else { (56, 59) };
-            self.move_piece(PieceType::Rook, us, rf, rt);
-            hash ^= keys.pieces[PieceType::Rook as usize][us as usize][rf as usize];
-            hash ^= keys.pieces[PieceType::Rook as usize][us as usize][rt as usize];
+            pawn_hash ^= keys.pieces[PieceType::Pawn as usize][us as usize][to as usize];
         }
 
         self.en_passant = if flag == moves::DOUBLE_PAWN_PUSH_FLAG {
@@ -456,7 +686,8 @@ impl Board {
             } else {
                 from - 8
             };
-            if (movegen::pawn_attacks(them, ep_sq)
+            // Their pawns that could capture sit where our pawn on ep_sq would attack
+            if (movegen::pawn_attacks(us, ep_sq)
                 & self.pieces[PieceType::Pawn as usize][them as usize])
                 != 0
             {
@@ -476,24 +707,84 @@ impl Board {
             self.fullmove_number += 1;
         }
 
-        self.castling_rights &= CASTLE_MASK[from as usize];
-        self.castling_rights &= CASTLE_MASK[to as usize];
+        self.castling_rights &= self.castle_mask[from as usize];
+        self.castling_rights &= self.castle_mask[to as usize];
         hash ^= keys.castling[self.castling_rights as usize];
 
         self.side_to_move = them;
         self.zobrist_hash = hash;
+        self.pawn_hash = pawn_hash;
 
-        //  King Move Refresh 
-        if has_nnue && moving_piece == PieceType::King {
-            self.accumulator = nnue::refresh_accumulator(self);
+        if has_nnue {
+            self.apply_nnue_updates(m, moving_piece, captured, us, them);
         }
 
         undo
     }
 
+    /// Applies `m` only if it is legal here, leaving the board untouched on
+    /// error. For untrusted input; the search uses the unchecked `make_move`.
+    pub fn make_move_checked(&mut self, m: Move) -> Result<UndoInfo, MoveError> {
+        let from = moves::from_sq(m);
+        if self.occupancy[self.side_to_move as usize] & (1u64 << from) == 0 {
+            return Err(MoveError::NoPiece);
+        }
+
+        if !self.is_pseudo_legal(m) {
+            return Err(MoveError::NotPseudoLegal);
+        }
+
+        let us = self.side_to_move;
+        let undo = self.make_move(m);
+        if self.is_square_attacked(self.king_sq[us as usize], self.side_to_move) {
+            self.unmake_move(m, undo);
+            return Err(MoveError::LeavesKingInCheck);
+        }
+        Ok(undo)
+    }
+
+    /// True if `m` is legal here. Observable state is unchanged afterwards:
+    /// moves that cannot expose the king (not in check, not a king move, en
+    /// passant or pinned piece) are decided from pin info alone, the rest by
+    /// a make/unmake.
+    pub fn is_legal(&mut self, m: Move) -> bool {
+        let us = self.side_to_move;
+        let from = moves::from_sq(m);
+        if self.occupancy[us as usize] & (1u64 << from) == 0 || !self.is_pseudo_legal(m) {
+            return false;
+        }
+
+        let them = if us == Color::White { Color::Black } else { Color::White };
+        let in_check = self.is_square_attacked(self.king_sq[us as usize], them);
+        let pinned = movegen::pinned_pieces(self, us);
+        if !movegen::needs_legality_check(self, m, pinned, in_check) {
+            return true;
+        }
+
+        let undo = self.make_move(m);
+        let legal = !self.is_square_attacked(self.king_sq[us as usize], self.side_to_move);
+        self.unmake_move(m, undo);
+        legal
+    }
+
+    /// True if `m` is one of this position's pseudo-legal moves.
+    pub fn is_pseudo_legal(&self, m: Move) -> bool {
+        movegen::is_pseudo_legal(self, m)
+    }
+
     pub fn unmake_move(&mut self, m: Move, undo: UndoInfo) {
         let _ = self.history.pop();
         self.zobrist_hash = undo.old_zobrist_hash;
+        self.pawn_hash = undo.old_pawn_hash;
+        // The stack is empty only if NNUE was enabled after the move was
+        // made; the accumulator is then rebuilt once the pieces are back
+        let mut stale = false;
+        if nnue::is_enabled() {
+            match self.accumulator_stack.pop() {
+                Some(accumulator) => self.accumulator = accumulator,
+                None => stale = true,
+            }
+        }
 
         let from = moves::from_sq(m);
         let to = moves::to_sq(m);
@@ -513,45 +804,48 @@ impl Board {
         }
         self.side_to_move = us;
 
-        let mut moving_piece = self.piece_type_on(to).unwrap();
-        if moves::is_promotion(m) {
-            self.remove_piece(moving_piece, us, to);
-            self.add_piece(PieceType::Pawn, us, to);
-            moving_piece = PieceType::Pawn;
-        } else if flag == moves::KING_CASTLE_FLAG {
-            let (rf, rt) = if us == Color::White { (7, 5) } else { (63, 61) };
-            self.move_piece(PieceType::Rook, us, rt, rf);
-        } else if flag == moves::QUEEN_CASTLE_FLAG {
-            let (rf, rt) = if us == Color::White { (0, 3) } else { (56, 59) };
-            self.move_piece(PieceType::Rook, us, rt, rf);
-        }
+        if flag == moves::KING_CASTLE_FLAG || flag == moves::QUEEN_CASTLE_FLAG {
+            let (rf, rt) = self.castle_rook_squares(us, flag);
+            self.remove_piece(PieceType::King, us, to);
+            self.remove_piece(PieceType::Rook, us, rt);
+            self.add_piece(PieceType::King, us, from);
+            self.add_piece(PieceType::Rook, us, rf);
+            self.king_sq[us as usize] = from;
+        } else {
+            let mut moving_piece = self.piece_type_on(to).unwrap();
+            if moves::is_promotion(m) {
+                self.remove_piece(moving_piece, us, to);
+                self.add_piece(PieceType::Pawn, us, to);
+                moving_piece = PieceType::Pawn;
+            }
 
-        self.move_piece(moving_piece, us, to, from);
+            self.move_piece(moving_piece, us, to, from);
 
-        if moving_piece == PieceType::King {
-            self.king_sq[us as usize] = from;
-        }
+            if moving_piece == PieceType::King {
+                self.king_sq[us as usize] = from;
+            }
 
-        let captured_piece_type = if undo.captured_piece == 6 { None } else { Some(PieceType::from(undo.captured_piece as usize)) };
+            let captured_piece_type = PieceType::try_from(undo.captured_piece as usize).ok();
 
-        if let Some(cap_pt) = captured_piece_type {
-            if flag == moves::EN_PASSANT_CAPTURE_FLAG {
-                let cap_sq = if us == Color::White { to - 8 } else { to + 8 };
-                self.add_piece(PieceType::Pawn, them, cap_sq);
-            } else {
-                self.add_piece(cap_pt, them, to);
+            if let Some(cap_pt) = captured_piece_type {
+                if flag == moves::EN_PASSANT_CAPTURE_FLAG {
+                    let cap_sq = if us == Color::White { to - 8 } else { to + 8 };
+                    self.add_piece(PieceType::Pawn, them, cap_sq);
+                } else {
+                    self.add_piece(cap_pt, them, to);
+                }
             }
         }
 
-        if nnue::is_enabled() {
-            if moving_piece == PieceType::King {
-                self.accumulator = nnue::refresh_accumulator(self);
-            } else {
-                self.apply_nnue_updates(m, moving_piece, captured_piece_type, us, them, false);
-            }
+        if stale {
+            self.accumulator = nnue::refresh_accumulator(self);
         }
     }
 
+    /// Updates both accumulators for `m` once the board shows the position
+    /// after it. Kings are not features, so a king move refreshes only the
+    /// mover's perspective and the other side just sees the captured piece
+    /// and a castling rook.
     #[inline(always)]
     fn apply_nnue_updates(
         &mut self,
@@ -560,93 +854,55 @@ impl Board {
         captured: Option<PieceType>,
         us: Color,
         them: Color,
-        forward: bool,
     ) {
-
-        let wk_sq = self.king_sq[Color::White as usize];
-        let bk_sq = self.king_sq[Color::Black as usize];
-
         let from = moves::from_sq(m);
         let to = moves::to_sq(m);
         let flag = moves::flag(m);
 
-        let mut batch_w = [(0usize, false); 8];
-        let mut batch_b = [(0usize, false); 8];
+        // (square, piece, color, added by the move)
+        let mut changes = [(0 as Square, PieceType::Pawn, us, false); 4];
         let mut count = 0;
 
-        // Moving piece (remove from source)
-        let idx_w = nnue::halfkp_index(wk_sq, from, moving_piece, us, Color::White);
-        let idx_b = nnue::halfkp_index(bk_sq, from, moving_piece, us, Color::Black);
-        batch_w[count] = (idx_w, if forward { false } else { true });
-        batch_b[count] = (idx_b, if forward { false } else { true });
-        count += 1;
-
-        // Capture handling
-        if let Some(cap_pt) = captured {
-            let (cap_sq, cap_pt) = if flag == moves::EN_PASSANT_CAPTURE_FLAG {
-                (if us == Color::White { to - 8 } else { to + 8 }, PieceType::Pawn)
-            } else {
-                (to, cap_pt)
-            };
-            
-            let idx_w = nnue::halfkp_index(wk_sq, cap_sq, cap_pt, them, Color::White);
-            let idx_b = nnue::halfkp_index(bk_sq, cap_sq, cap_pt, them, Color::Black);
-            batch_w[count] = (idx_w, if forward { false } else { true });
-            batch_b[count] = (idx_b, if forward { false } else { true });
-            count += 1;
-        }
-
-        // Moving piece or Promotion
-        let (dest_pt, dest_sq) = if moves::is_promotion(m) {
-            (moves::promotion_piece(m), to)
+        if flag == moves::KING_CASTLE_FLAG || flag == moves::QUEEN_CASTLE_FLAG {
+            let (rf, rt) = self.castle_rook_squares(us, flag);
+            changes[count] = (rf, PieceType::Rook, us, false);
+            changes[count + 1] = (rt, PieceType::Rook, us, true);
+            count += 2;
         } else {
-            (moving_piece, to)
-        };
-        
-        let idx_w = nnue::halfkp_index(wk_sq, dest_sq, dest_pt, us, Color::White);
-        let idx_b = nnue::halfkp_index(bk_sq, dest_sq, dest_pt, us, Color::Black);
-        batch_w[count] = (idx_w, if forward { true } else { false });
-        batch_b[count] = (idx_b, if forward { true } else { false });
-        count += 1;
-
-        // 4. Castle handling (Rook move)
-        if flag == moves::KING_CASTLE_FLAG {
-            let (r_from, r_to) = if us == Color::White { (7, 5) } else { (63, 61) };
-            
-            // Remove rook from old square
-            let idx_w_rem = nnue::halfkp_index(wk_sq, r_from, PieceType::Rook, us, Color::White);
-            let idx_b_rem = nnue::halfkp_index(bk_sq, r_from, PieceType::Rook, us, Color::Black);
-            batch_w[count] = (idx_w_rem, if forward { false } else { true });
-            batch_b[count] = (idx_b_rem, if forward { false } else { true });
-            count += 1;
-            
-            // Add rook to new square
-            let idx_w_add = nnue::halfkp_index(wk_sq, r_to, PieceType::Rook, us, Color::White);
-            let idx_b_add = nnue::halfkp_index(bk_sq, r_to, PieceType::Rook, us, Color::Black);
-            batch_w[count] = (idx_w_add, if forward { true } else { false });
-            batch_b[count] = (idx_b_add, if forward { true } else { false });
-            count += 1;
-        } else if flag == moves::QUEEN_CASTLE_FLAG {
-            let (r_from, r_to) = if us == Color::White { (0, 3) } else { (56, 59) };
-            
-            // Remove rook from old square
-            let idx_w_rem = nnue::halfkp_index(wk_sq, r_from, PieceType::Rook, us, Color::White);
-            let idx_b_rem = nnue::halfkp_index(bk_sq, r_from, PieceType::Rook, us, Color::Black);
-            batch_w[count] = (idx_w_rem, if forward { false } else { true });
-            batch_b[count] = (idx_b_rem, if forward { false } else { true });
+            // Moving piece (remove from source)
+            changes[count] = (from, moving_piece, us, false);
             count += 1;
-            
-            // Add rook to new square
-            let idx_w_add = nnue::halfkp_index(wk_sq, r_to, PieceType::Rook, us, Color::White);
-            let idx_b_add = nnue::halfkp_index(bk_sq, r_to, PieceType::Rook, us, Color::Black);
-            batch_w[count] = (idx_w_add, if forward { true } else { false });
-            batch_b[count] = (idx_b_add, if forward { true } else { false });
+
+            // Capture handling
+            if let Some(cap_pt) = captured {
+                let (cap_sq, cap_pt) = if flag == moves::EN_PASSANT_CAPTURE_FLAG {
+                    (if us == Color::White { to - 8 } else { to + 8 }, PieceType::Pawn)
+                } else {
+                    (to, cap_pt)
+                };
+                changes[count] = (cap_sq, cap_pt, them, false);
+                count += 1;
+            }
+
+            // Moving piece or Promotion
+            let dest_pt = if moves::is_promotion(m) { moves::promotion_piece(m) } else { moving_piece };
+            changes[count] = (to, dest_pt, us, true);
             count += 1;
         }
 
-        // Apply batches
-        nnue::update_feature_batch(&mut self.accumulator[0], &batch_w[..count]);
-        nnue::update_feature_batch(&mut self.accumulator[1], &batch_b[..count]);
+        for perspective in [Color::White, Color::Black] {
+            if moving_piece == PieceType::King && perspective == us {
+                self.accumulator[us as usize] = nnue::refresh_perspective(self, us);
+                continue;
+            }
+
+            let king_sq = self.king_sq[perspective as usize];
+            let mut batch = [(0usize, false); 4];
+            for (i, &(sq, pt, color, added)) in changes[..count].iter().enumerate() {
+                batch[i] = (nnue::halfkp_index(king_sq, sq, pt, color, perspective), added);
+            }
+            nnue::update_feature_batch(&mut self.accumulator[perspective as usize], &batch[..count]);
+        }
     }
 
     pub fn generate_pseudo_legal_moves(&self, list: &mut MoveList) {
@@ -664,83 +920,394 @@ impl Board {
 
         let mut nodes = 0;
         let mut move_list = MoveList::new();
-        self.generate_pseudo_legal_moves(&mut move_list);
+        movegen::generate_legal_moves(self, &mut move_list);
+
+        // Bulk count: the generator only emits legal moves, so leaves need no make/unmake
+        if depth == 1 {
+            return move_list.len() as u64;
+        }
 
         for &m in move_list.iter() {
             let undo = self.make_move(m);
-
-            let us = if self.side_to_move == Color::White {
-                Color::Black
-            } else {
-                Color::White
-            };
-            let king_sq =
-                self.pieces[PieceType::King as usize][us as usize].trailing_zeros() as Square;
-
-            if !self.is_square_attacked(king_sq, self.side_to_move) {
-                nodes += self.perft(depth - 1);
-            }
+            nodes += self.perft(depth - 1);
             self.unmake_move(m, undo);
         }
         nodes
     }
 
-    pub fn is_repetition(&self) -> bool {
-        let mut count = 0;
-        for undo in self.history.iter().rev() {
-            if undo.old_halfmove_clock == 0 {
-                break;
-            }
-            if undo.old_zobrist_hash == self.zobrist_hash {
-                count += 1;
-                if count >= 2 {
-                    return true;
-                }
-            }
+    /// `perft` that also classifies the moves made at the last ply.
+    pub fn perft_stats(&mut self, depth: u8) -> PerftStats {
+        let mut stats = PerftStats::default();
+        if depth == 0 {
+            stats.nodes = 1;
+        } else {
+            self.collect_perft_stats(depth, &mut stats);
         }
-        false
+        stats
     }
 
-    pub fn make_null_move(&mut self) -> Option<Square> {
-        let keys = zobrist::keys();
-        let old_ep = self.en_passant;
+    fn collect_perft_stats(&mut self, depth: u8, stats: &mut PerftStats) {
+        let mut move_list = MoveList::new();
+        movegen::generate_legal_moves(self, &mut move_list);
 
-        if let Some(ep) = self.en_passant {
-          let them = if self.side_to_move == Color::White {
-            Color::Black
-          } else {
-            Color::White
-          };
-          let our_pawns = self.pieces[PieceType::Pawn as usize][self.side_to_move as usize];
+        for &m in move_list.iter() {
+            let undo = self.make_move(m);
+            if depth > 1 {
+                self.collect_perft_stats(depth - 1, stats);
+            } else {
+                let flag = moves::flag(m);
+                stats.nodes += 1;
+                stats.captures += moves::is_capture(m) as u64;
+                stats.en_passant += (flag == moves::EN_PASSANT_CAPTURE_FLAG) as u64;
+                stats.castles +=
+                    (flag == moves::KING_CASTLE_FLAG || flag == moves::QUEEN_CASTLE_FLAG) as u64;
+                stats.promotions += moves::is_promotion(m) as u64;
+                if self.in_check() {
+                    stats.checks += 1;
+                    stats.checkmates += !self.has_legal_move() as u64;
+                }
+            }
+            self.unmake_move(m, undo);
+        }
+    }
 
-          if (movegen::pawn_attacks(them, ep) & our_pawns) != 0 {
-            self.zobrist_hash ^= keys.en_passant_file[(ep % 8) as usize];
-          }   
-          self.en_passant = None;
+    /// `perft` with the root moves dealt round-robin to `threads` threads,
+    /// each on its own board. Falls back to `perft` for one thread.
+    pub fn perft_parallel(&mut self, depth: u8, threads: usize) -> u64 {
+        if threads <= 1 || depth < 2 {
+            return self.perft(depth);
         }
 
-        self.zobrist_hash ^= keys.side_to_move;
-        self.side_to_move = if self.side_to_move == Color::White {
-            Color::Black
-        } else {
-            Color::White
-        };
+        let mut move_list = MoveList::new();
+        movegen::generate_legal_moves(self, &mut move_list);
+        let root_moves: Vec<Move> = move_list.iter().copied().collect();
+
+        let mut handles = Vec::with_capacity(threads);
+        for thread_id in 0..threads.min(root_moves.len()) {
+            let mut board = self.clone_for_search();
+            let share: Vec<Move> =
+                root_moves.iter().copied().skip(thread_id).step_by(threads).collect();
+
+            handles.push(thread::spawn(move || {
+                let mut nodes = 0;
+                for m in share {
+                    let undo = board.make_move(m);
+                    nodes += board.perft(depth - 1);
+                    board.unmake_move(m, undo);
+                }
+                nodes
+            }));
+        }
 
-        old_ep
+        handles.into_iter().map(|handle| handle.join().unwrap()).sum()
     }
 
-    pub fn unmake_null_move(&mut self, old_ep: Option<Square>) {
-        let keys = zobrist::keys();
-
-        self.side_to_move = if self.side_to_move == Color::White {
-            Color::Black
-        } else {
-            Color::White
-        };
+    /// `perft` with subtree counts cached in `tt`, keyed by the zobrist hash
+    /// and depth. Transpositions make deep perft much cheaper.
+    pub fn perft_hashed(&mut self, depth: u8, tt: &PerftTable) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        if let Some(nodes) = tt.probe(self.zobrist_hash, depth) {
+            return nodes;
+        }
 
-        self.zobrist_hash ^= keys.side_to_move;
+        let mut move_list = MoveList::new();
+        movegen::generate_legal_moves(self, &mut move_list);
+        if depth == 1 {
+            return move_list.len() as u64;
+        }
 
-        if let Some(ep) = old_ep {
+        let mut nodes = 0;
+        for &m in move_list.iter() {
+            let undo = self.make_move(m);
+            nodes += self.perft_hashed(depth - 1, tt);
+            self.unmake_move(m, undo);
+        }
+
+        tt.store(self.zobrist_hash, depth, nodes);
+        nodes
+    }
+
+    /// Splits `perft(depth)` by root move, for diffing against another
+    /// engine's `go perft`. The counts sum to `perft(depth)`.
+    pub fn perft_divide(&mut self, depth: u8) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+
+        let mut move_list = MoveList::new();
+        movegen::generate_legal_moves(self, &mut move_list);
+
+        let mut divide = Vec::with_capacity(move_list.len());
+        for &m in move_list.iter() {
+            let undo = self.make_move(m);
+            divide.push((m, self.perft(depth - 1)));
+            self.unmake_move(m, undo);
+        }
+        divide
+    }
+
+    /// Number of earlier occurrences of the current position since the last
+    /// irreversible move.
+    pub fn repetition_count(&self) -> usize {
+        let mut count = 0;
+        for undo in self.history.iter().rev() {
+            if undo.old_zobrist_hash == self.zobrist_hash {
+                count += 1;
+            }
+            if undo.old_halfmove_clock == 0 {
+                break;
+            }
+        }
+        count
+    }
+
+    pub fn is_repetition(&self) -> bool {
+        self.repetition_count() >= 2
+    }
+
+    /// Keys of the positions before this one since the last irreversible
+    /// move, oldest first. A search on a `clone_for_search` copy needs them
+    /// to see repetitions that started before its root.
+    pub fn repetition_keys(&self) -> Vec<ZHash> {
+        let start = self
+            .history
+            .iter()
+            .rposition(|undo| undo.old_halfmove_clock == 0)
+            .unwrap_or(0);
+        self.history[start..].iter().map(|undo| undo.old_zobrist_hash).collect()
+    }
+
+    /// True when neither side has enough material to deliver mate:
+    /// bare kings, a single minor piece, or bishops all on one square colour.
+    pub fn is_insufficient_material(&self) -> bool {
+        let heavy_or_pawns = self.pieces[PieceType::Pawn as usize][0]
+            | self.pieces[PieceType::Pawn as usize][1]
+            | self.pieces[PieceType::Rook as usize][0]
+            | self.pieces[PieceType::Rook as usize][1]
+            | self.pieces[PieceType::Queen as usize][0]
+            | self.pieces[PieceType::Queen as usize][1];
+        if heavy_or_pawns != 0 {
+            return false;
+        }
+
+        let knights =
+            self.pieces[PieceType::Knight as usize][0] | self.pieces[PieceType::Knight as usize][1];
+        let bishops =
+            self.pieces[PieceType::Bishop as usize][0] | self.pieces[PieceType::Bishop as usize][1];
+        if (knights | bishops).count_ones() <= 1 {
+            return true;
+        }
+
+        const LIGHT_SQUARES: Bitboard = 0x55AA_55AA_55AA_55AA;
+        knights == 0 && (bishops & LIGHT_SQUARES == 0 || bishops & !LIGHT_SQUARES == 0)
+    }
+
+    /// Game phase from non-pawn material: 256 with the full starting set
+    /// (or more), falling to 0 with only kings and pawns left.
+    pub fn phase(&self) -> i32 {
+        let count = |pt: PieceType| {
+            (self.pieces[pt as usize][0] | self.pieces[pt as usize][1]).count_ones() as i32
+        };
+        let material = count(PieceType::Knight)
+            + count(PieceType::Bishop)
+            + 2 * count(PieceType::Rook)
+            + 4 * count(PieceType::Queen);
+        material.min(24) * 256 / 24
+    }
+
+    /// True when the side to move may claim a draw by threefold repetition
+    /// or the fifty-move rule.
+    pub fn can_claim_draw(&self) -> bool {
+        self.halfmove_clock >= 100 || self.repetition_count() >= 2
+    }
+
+    /// True if the side to move's king is attacked.
+    pub fn in_check(&self) -> bool {
+        let us = self.side_to_move;
+        let them = if us == Color::White { Color::Black } else { Color::White };
+        self.is_square_attacked(self.king_sq[us as usize], them)
+    }
+
+    /// Enemy pieces giving check to the side to move; two bits set means
+    /// double check, where only king moves are legal.
+    pub fn checkers(&self) -> Bitboard {
+        movegen::checkers(self, self.side_to_move)
+    }
+
+    /// True if the pseudo-legal move `m` checks the opponent, worked out
+    /// without making it: a direct check from the destination (by the
+    /// promoted piece, or the rook when castling), or a discovered check by
+    /// one of our sliders once the from-square (and for en passant, the
+    /// captured pawn) is vacated.
+    pub fn gives_check(&self, m: Move) -> bool {
+        let us = self.side_to_move;
+        let them = if us == Color::White { Color::Black } else { Color::White };
+        let king_bb = 1u64 << self.king_sq[them as usize];
+        let from = moves::from_sq(m);
+        let to = moves::to_sq(m);
+        let flag = moves::flag(m);
+        let Some(moving_piece) = self.piece_type_on(from) else {
+            return false;
+        };
+
+        let mut occ = (self.occupancy[2] & !(1u64 << from)) | (1u64 << to);
+        if flag == moves::EN_PASSANT_CAPTURE_FLAG {
+            let captured_sq = if us == Color::White { to - 8 } else { to + 8 };
+            occ &= !(1u64 << captured_sq);
+        }
+
+        // Castling: only the rook on its new square can give check
+        if flag == moves::KING_CASTLE_FLAG || flag == moves::QUEEN_CASTLE_FLAG {
+            let (rook_from, rook_to) = self.castle_rook_squares(us, flag);
+            let occ = (self.occupancy[2] & !(1u64 << from) & !(1u64 << rook_from))
+                | (1u64 << to)
+                | (1u64 << rook_to);
+            return movegen::get_rook_attacks(rook_to, occ) & king_bb != 0;
+        }
+
+        let piece = if moves::is_promotion(m) {
+            moves::promotion_piece(m)
+        } else {
+            moving_piece
+        };
+        let direct = match piece {
+            PieceType::Pawn => movegen::pawn_attacks(us, to),
+            PieceType::Knight => movegen::knight_attacks(to),
+            PieceType::Bishop => movegen::get_bishop_attacks(to, occ),
+            PieceType::Rook => movegen::get_rook_attacks(to, occ),
+            PieceType::Queen => {
+                movegen::get_bishop_attacks(to, occ) | movegen::get_rook_attacks(to, occ)
+            }
+            PieceType::King => 0,
+        };
+        if direct & king_bb != 0 {
+            return true;
+        }
+
+        let ours = |pt: PieceType| self.pieces[pt as usize][us as usize] & !(1u64 << from);
+        let king_sq = self.king_sq[them as usize];
+        let diagonal = ours(PieceType::Bishop) | ours(PieceType::Queen);
+        let straight = ours(PieceType::Rook) | ours(PieceType::Queen);
+        movegen::get_bishop_attacks(king_sq, occ) & diagonal != 0
+            || movegen::get_rook_attacks(king_sq, occ) & straight != 0
+    }
+
+    /// Pieces of both colors in `occ` that attack `sq`, with sliders seeing
+    /// through anything missing from `occ`. SEE passes an occupancy with
+    /// the pieces already exchanged removed.
+    pub fn attackers_to(&self, sq: Square, occ: Bitboard) -> Bitboard {
+        let both = |pt: PieceType| self.pieces[pt as usize][0] | self.pieces[pt as usize][1];
+        let diagonal = both(PieceType::Bishop) | both(PieceType::Queen);
+        let straight = both(PieceType::Rook) | both(PieceType::Queen);
+
+        let attackers = (movegen::pawn_attacks(Color::Black, sq)
+            & self.pieces[PieceType::Pawn as usize][Color::White as usize])
+            | (movegen::pawn_attacks(Color::White, sq)
+                & self.pieces[PieceType::Pawn as usize][Color::Black as usize])
+            | (movegen::knight_attacks(sq) & both(PieceType::Knight))
+            | (movegen::king_attacks(sq) & both(PieceType::King))
+            | (movegen::get_bishop_attacks(sq, occ) & diagonal)
+            | (movegen::get_rook_attacks(sq, occ) & straight);
+        attackers & occ
+    }
+
+    /// True if the side to move has at least one legal move.
+    pub fn has_legal_move(&mut self) -> bool {
+        let mut move_list = MoveList::new();
+        self.generate_pseudo_legal_moves(&mut move_list);
+
+        let us = self.side_to_move;
+        for &m in move_list.iter() {
+            let undo = self.make_move(m);
+            let legal = !self.is_square_attacked(self.king_sq[us as usize], self.side_to_move);
+            self.unmake_move(m, undo);
+            if legal {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn is_checkmate(&mut self) -> bool {
+        self.in_check() && !self.has_legal_move()
+    }
+
+    pub fn is_stalemate(&mut self) -> bool {
+        !self.in_check() && !self.has_legal_move()
+    }
+
+    /// Classifies the position. Mate and stalemate take precedence; the
+    /// fifty-move and repetition draws count once they can be claimed, and
+    /// are reported as the automatic fivefold and 75-move draws once those
+    /// apply.
+    pub fn game_result(&mut self) -> GameResult {
+        if !self.has_legal_move() {
+            return if !self.in_check() {
+                GameResult::Draw(DrawReason::Stalemate)
+            } else if self.side_to_move == Color::White {
+                GameResult::BlackWins
+            } else {
+                GameResult::WhiteWins
+            };
+        }
+
+        if self.is_insufficient_material() {
+            GameResult::Draw(DrawReason::InsufficientMaterial)
+        } else if self.halfmove_clock >= 150 {
+            GameResult::Draw(DrawReason::SeventyFiveMoveRule)
+        } else if self.repetition_count() >= 4 {
+            GameResult::Draw(DrawReason::FivefoldRepetition)
+        } else if self.halfmove_clock >= 100 {
+            GameResult::Draw(DrawReason::FiftyMoveRule)
+        } else if self.is_repetition() {
+            GameResult::Draw(DrawReason::ThreefoldRepetition)
+        } else {
+            GameResult::Ongoing
+        }
+    }
+
+    pub fn make_null_move(&mut self) -> Option<Square> {
+        let keys = zobrist::keys();
+        let old_ep = self.en_passant;
+
+        if let Some(ep) = self.en_passant {
+          let them = if self.side_to_move == Color::White {
+            Color::Black
+          } else {
+            Color::White
+          };
+          let our_pawns = self.pieces[PieceType::Pawn as usize][self.side_to_move as usize];
+
+          if (movegen::pawn_attacks(them, ep) & our_pawns) != 0 {
+            self.zobrist_hash ^= keys.en_passant_file[(ep % 8) as usize];
+          }   
+          self.en_passant = None;
+        }
+
+        self.zobrist_hash ^= keys.side_to_move;
+        self.side_to_move = if self.side_to_move == Color::White {
+            Color::Black
+        } else {
+            Color::White
+        };
+
+        old_ep
+    }
+
+    pub fn unmake_null_move(&mut self, old_ep: Option<Square>) {
+        let keys = zobrist::keys();
+
+        self.side_to_move = if self.side_to_move == Color::White {
+            Color::Black
+        } else {
+            Color::White
+        };
+
+        self.zobrist_hash ^= keys.side_to_move;
+
+        if let Some(ep) = old_ep {
 
           let them = if self.side_to_move == Color::White {
             Color::Black
@@ -768,7 +1335,7 @@ impl fmt::Display for Board {
 
                 for pt_idx in 0..6 {
                     if (self.pieces[pt_idx][Color::White as usize] & bit) != 0 {
-                        piece_char = match pt_idx.into() {
+                        piece_char = match PieceType::from_index(pt_idx) {
                             PieceType::Pawn => 'P',
                             PieceType::Knight => 'N',
                             PieceType::Bishop => 'B',
@@ -780,7 +1347,7 @@ impl fmt::Display for Board {
                     }
 
                     if (self.pieces[pt_idx][Color::Black as usize] & bit) != 0 {
-                        piece_char = match pt_idx.into() {
+                        piece_char = match PieceType::from_index(pt_idx) {
                             PieceType::Pawn => 'p',
                             PieceType::Knight => 'n',
                             PieceType::Bishop => 'b',
@@ -837,11 +1404,126 @@ impl Default for Board {
             halfmove_clock: 0,
             fullmove_number: 1,
             zobrist_hash: 0,
+            pawn_hash: 0,
             history: Vec::new(),
             accumulator: [Accumulator::default(); 2],
+            accumulator_stack: Vec::new(),
             king_sq: [4, 60],
+            chess960: false,
+            castling_rooks: STANDARD_CASTLING_ROOKS,
+            castle_mask: castle_mask([4, 60], STANDARD_CASTLING_ROOKS),
+        }
+    }
+}
+
+/// Boards are equal when they hold the same position: pieces, side to
+/// move, castling rights and a capturable en passant square. Move counters
+/// and history are ignored, so transpositions compare equal.
+impl PartialEq for Board {
+    fn eq(&self, other: &Self) -> bool {
+        let rights_match = self.castling_rights == other.castling_rights
+            && (0..4).all(|right| {
+                self.castling_rights & (1 << right) == 0
+                    || self.castling_rooks[right] == other.castling_rooks[right]
+            });
+        self.pieces == other.pieces
+            && self.side_to_move == other.side_to_move
+            && rights_match
+            && self.capturable_en_passant() == other.capturable_en_passant()
+    }
+}
+
+impl Eq for Board {}
+
+impl Hash for Board {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Equal boards always share a zobrist key
+        state.write_u64(self.zobrist_hash);
+    }
+}
+
+/// Wire format for `Board`: only the fields that define the position.
+/// Occupancy, king squares, the hash and the NNUE accumulator are rebuilt
+/// on deserialize; history is not kept.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct BoardRepr {
+  pieces: [[Bitboard; 2]; 6],
+  side_to_move: Color,
+  castling_rights: u8,
+  en_passant: Option<Square>,
+  halfmove_clock: u8,
+  fullmove_number: u32,
+  #[serde(default)]
+  chess960: bool,
+  #[serde(default = "standard_castling_rooks")]
+  castling_rooks: [Square; 4],
+}
+
+#[cfg(feature = "serde")]
+fn standard_castling_rooks() -> [Square; 4] {
+  STANDARD_CASTLING_ROOKS
+}
+
+#[cfg(feature = "serde")]
+impl From<Board> for BoardRepr {
+  fn from(board: Board) -> Self {
+    BoardRepr {
+      pieces: board.pieces,
+      side_to_move: board.side_to_move,
+      castling_rights: board.castling_rights,
+      en_passant: board.en_passant,
+      halfmove_clock: board.halfmove_clock,
+      fullmove_number: board.fullmove_number,
+      chess960: board.chess960,
+      castling_rooks: board.castling_rooks,
+    }
+  }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<BoardRepr> for Board {
+  type Error = &'static str;
+
+  fn try_from(repr: BoardRepr) -> Result<Self, Self::Error> {
+    let mut board = Board {
+      pieces: repr.pieces,
+      side_to_move: repr.side_to_move,
+      castling_rights: repr.castling_rights & 0b1111,
+      en_passant: repr.en_passant,
+      halfmove_clock: repr.halfmove_clock,
+      fullmove_number: repr.fullmove_number,
+      chess960: repr.chess960,
+      castling_rooks: repr.castling_rooks,
+      ..Board::default()
+    };
+
+    for pt in 0..6 {
+      for c in 0..2 {
+        let bb = board.pieces[pt][c];
+        if board.occupancy[2] & bb != 0 {
+          return Err("Invalid board: two pieces on one square");
         }
+        board.occupancy[c] |= bb;
+        board.occupancy[2] |= bb;
+      }
+    }
+    board.validate_material()?;
+    if board.en_passant.is_some_and(|sq| sq >= 64) || board.castling_rooks.iter().any(|&sq| sq >= 64) {
+      return Err("Invalid board: square out of range");
     }
+
+    for c in [Color::White, Color::Black] {
+      board.king_sq[c as usize] = board.pieces[PieceType::King as usize][c as usize].trailing_zeros() as Square;
+    }
+    board.castle_mask = castle_mask(board.king_sq, board.castling_rooks);
+    board.zobrist_hash = board.calculate_zobrist_hash();
+    board.pawn_hash = board.calculate_pawn_hash();
+    if nnue::is_enabled() {
+      board.accumulator = nnue::refresh_accumulator(&board);
+    }
+    Ok(board)
+  }
 }
 
 #[cfg(test)]
@@ -849,6 +1531,169 @@ mod tests {
     use super::*;
     use crate::movegen;
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        movegen::init();
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2",
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+        ];
+        for fen in fens {
+            let mut board = Board::from_fen(fen).unwrap();
+            let m = board.perft_divide(1)[0].0;
+            board.make_move(m);
+
+            let json = serde_json::to_string(&board).unwrap();
+            assert!(!json.contains("history") && !json.contains("accumulator"));
+            let restored: Board = serde_json::from_str(&json).unwrap();
+            let expected = Board::from_fen(&board.to_fen()).unwrap();
+            assert!(restored == expected);
+            assert_eq!(restored.to_fen(), expected.to_fen());
+            assert_eq!(restored.zobrist_hash, expected.zobrist_hash);
+            assert_eq!(restored.occupancy, expected.occupancy);
+            assert_eq!(restored.king_sq, expected.king_sq);
+            assert!(restored.history.is_empty());
+        }
+
+        let mut json = serde_json::to_value(Board::from_fen(fens[0]).unwrap()).unwrap();
+        json["pieces"][5][0] = serde_json::json!(0);
+        assert!(serde_json::from_value::<Board>(json).is_err());
+    }
+
+    #[test]
+    fn chess960_perft() {
+        movegen::init();
+        let cases = [
+            ("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9", [21, 528, 12189]),
+            ("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w KQkq - 1 9", [21, 807, 18002]),
+        ];
+        for (fen, expected) in cases {
+            let mut board = Board::from_fen(fen).unwrap();
+            assert!(board.chess960, "{}", fen);
+            for (depth, &nodes) in expected.iter().enumerate() {
+                assert_eq!(board.perft(depth as u8 + 1), nodes, "{} depth {}", fen, depth + 1);
+            }
+        }
+
+        let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert!(!board.chess960);
+    }
+
+    #[test]
+    fn shredder_fen_castling() {
+        movegen::init();
+        let fens = [
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1",
+            "1r2k2r/8/8/8/8/8/8/R3K1R1 w Gb - 0 1",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+            assert!(board.chess960, "{}", fen);
+            assert_eq!(board.to_fen(), fen);
+        }
+
+        // The same rights in X-FEN and Shredder form
+        let x_fen = Board::from_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9").unwrap();
+        let shredder = Board::from_fen(&x_fen.to_fen()).unwrap();
+        assert_eq!(x_fen.castling_rooks, shredder.castling_rooks);
+        assert_eq!(x_fen.castling_rights, shredder.castling_rights);
+
+        // An inner rook by file letter, with an outer rook on the same side
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/R1R1K3 w C - 0 1").unwrap();
+        assert_eq!(board.castling_rooks[1], 2);
+        assert_eq!(board.castling_rights, 0b0010);
+
+        // A letter with no rook behind it is dropped
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w HA - 0 1").unwrap();
+        assert_eq!(board.castling_rights, 0b0001);
+    }
+
+    #[test]
+    fn chess960_castling_onto_start_squares() {
+        movegen::init();
+        // Queen side the king lands on its own square's neighbour and the
+        // rook passes it; king side the king stays put on g1
+        let cases = [
+            ("4k3/8/8/8/8/8/8/RK5R w HA - 0 1", 1, 2, moves::QUEEN_CASTLE_FLAG, "4k3/8/8/8/8/8/8/2KR3R b - - 1 1"),
+            ("4k3/8/8/8/8/8/8/R5KR w HA - 0 1", 6, 6, moves::KING_CASTLE_FLAG, "4k3/8/8/8/8/8/8/R4RK1 b - - 1 1"),
+        ];
+        for (fen, from, to, flag, after) in cases {
+            let mut board = Board::from_fen(fen).unwrap();
+            let m = moves::new(from, to, flag);
+            assert!(board.is_legal(m), "{}", fen);
+
+            let undo = board.make_move(m);
+            assert_eq!(board.to_fen(), after);
+            assert_eq!(board.zobrist_hash, board.calculate_zobrist_hash());
+            assert_eq!(board.king_sq[Color::White as usize], to);
+
+            board.unmake_move(m, undo);
+            assert_eq!(board.to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn epd_operations() {
+        let epd = r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001; test";"#;
+        let (board, ops) = Board::from_epd(epd).unwrap();
+        assert_eq!(board.to_fen(), "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1");
+        assert_eq!(ops.len(), 2);
+        assert_eq!(ops["bm"], "Qg6");
+        assert_eq!(ops["id"], "WAC.001; test");
+
+        let (board, ops) = Board::from_epd("4k3/8/8/8/8/8/8/4K2R w K - am Rh8 Kd1; hmvc 7; fmvn 30").unwrap();
+        assert_eq!(ops["am"], "Rh8 Kd1");
+        assert_eq!((board.halfmove_clock, board.fullmove_number), (7, 30));
+
+        let (_, ops) = Board::from_epd("4k3/8/8/8/8/8/8/4K3 b - -").unwrap();
+        assert!(ops.is_empty());
+
+        assert!(Board::from_epd("4k3/8/8/8/8/8/8/4K3 w -").is_err());
+        assert!(Board::from_epd("4k3/8/8/8/8/8/8/4K3 w - - id \"open").is_err());
+        assert!(Board::from_epd("4k3/8/8/8/8/8/8/4K3 w - - hmvc x;").is_err());
+    }
+
+    #[test]
+    fn transpositions_compare_equal() {
+        movegen::init();
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let play = |line: &[&str]| {
+            let mut board = Board::from_fen(start).unwrap();
+            for uci in line {
+                let m = crate::engine::find_move(&board, uci).unwrap();
+                board.make_move(m);
+            }
+            board
+        };
+
+        let a = play(&["g1f3", "g8f6", "b1c3", "b8c6"]);
+        let b = play(&["b1c3", "b8c6", "g1f3", "g8f6"]);
+        assert!(a == b);
+        assert_eq!(a.zobrist_hash, b.zobrist_hash);
+
+        let mut seen = std::collections::HashSet::new();
+        seen.insert(a);
+        assert!(seen.contains(&b));
+
+        // Knights out and back: same position, different clocks and history
+        let c = play(&["g1f3", "g8f6", "f3g1", "f6g8"]);
+        assert!(c == Board::from_fen(start).unwrap());
+
+        // An en passant square nobody can use does not count
+        let d = play(&["e2e4"]);
+        let e = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap();
+        assert!(d == e);
+
+        let f = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert!(e != f);
+        let g = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b Kkq - 0 1").unwrap();
+        assert!(e != g);
+    }
+
     #[test]
     fn fen_round_trip() {
         let fens = [
@@ -863,6 +1708,78 @@ mod tests {
         }
     }
 
+    #[test]
+    fn fen_without_move_counters() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w K -").unwrap();
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/4K2R w K - 0 1");
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R b - - 7").unwrap();
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/4K2R b - - 7 1");
+
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4K2R w").is_err());
+
+        let board = Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6").unwrap();
+        assert_eq!(board.en_passant, Some(44));
+        assert_eq!((board.halfmove_clock, board.fullmove_number), (0, 1));
+    }
+
+    #[test]
+    fn fen_rejects_malformed_fields() {
+        let bad = [
+            "4k3/8/8/8/8/8/8/4K2R x - - 0 1",
+            "4k3/8/8/8/8/8/8/4K2R w KX - 0 1",
+            "4k3/8/8/8/8/8/8/4K2R w K- - 0 1",
+            "4k3/8/8/8/8/8/8/4K2R w - e 0 1",
+            "4k3/8/8/8/8/8/8/4K2R w - e3 0 1",
+            "4k3/8/8/8/8/8/8/4K2R w - i6 0 1",
+            "4k3/8/8/8/8/8/8/4K2R w - - 0 1 extra",
+        ];
+        for fen in bad {
+            assert!(Board::from_fen(fen).is_err(), "{}", fen);
+        }
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w - - x -1").unwrap();
+        assert_eq!((board.halfmove_clock, board.fullmove_number), (0, 1));
+    }
+
+    #[test]
+    fn fen_rejects_bad_king_counts() {
+        assert!(Board::from_fen("8/8/8/8/8/8/8/4K3 w - - 0 1").is_err());
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/8 w - - 0 1").is_err());
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/3KK3 w - - 0 1").is_err());
+    }
+
+    #[test]
+    fn fen_errors_name_the_problem() {
+        let cases = [
+            ("4k3/8/8/8/8/8/4K3 w - - 0 1", "Invalid FEN: piece placement must have 8 ranks"),
+            ("4k3/8/8/8/8/8/8/8/4K3 w - - 0 1", "Invalid FEN: piece placement must have 8 ranks"),
+            ("4k3/8/8/8/8/8/8/4K4 w - - 0 1", "Invalid FEN: a rank has more than 8 squares"),
+            ("4k3/8/8/8/8/8/8/4KR3 w - - 0 1", "Invalid FEN: a rank has more than 8 squares"),
+            ("4k3/8/8/7/8/8/8/4K3 w - - 0 1", "Invalid FEN: a rank has fewer than 8 squares"),
+            ("4k3/8/8/8/8/8/8/4K03 w - - 0 1", "Invalid FEN: empty-square count must be 1 to 8"),
+            ("4k3/8/8/8/8/8/8/4K2X w - - 0 1", "Invalid FEN: unknown piece letter"),
+            ("4k3/8/8/8/8/8/8/8 w - - 0 1", "Invalid FEN: white has no king"),
+            ("8/8/8/8/8/8/8/4K3 w - - 0 1", "Invalid FEN: black has no king"),
+            ("4k3/8/8/8/8/8/8/3KK3 w - - 0 1", "Invalid FEN: white has more than one king"),
+            ("3kk3/8/8/8/8/8/8/4K3 w - - 0 1", "Invalid FEN: black has more than one king"),
+            ("4k3/8/8/8/8/8/8/P3K3 w - - 0 1", "Invalid FEN: pawn on the first or eighth rank"),
+            ("p3k3/8/8/8/8/8/8/4K3 w - - 0 1", "Invalid FEN: pawn on the first or eighth rank"),
+            ("4k3/8/8/8/8/8/8/4K3 x - - 0 1", "Invalid FEN: side to move must be w or b"),
+        ];
+        for (fen, message) in cases {
+            assert_eq!(Board::from_fen(fen).err(), Some(message), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn fen_rejects_impossible_material() {
+        assert!(Board::from_fen("4k3/pppppppp/p7/8/8/8/8/4K3 b - - 0 1").is_err());
+        assert!(Board::from_fen("4k2P/8/8/8/8/8/8/4K3 w - - 0 1").is_err());
+        assert!(Board::from_fen("4k3/pppppppp/8/8/8/8/8/Q2QK3 w - - 0 1").is_ok());
+        assert!(Board::from_fen("4k3/8/8/8/8/8/PPPPPPP1/QQQK4 w - - 0 1").is_err());
+    }
+
     #[test]
     fn make_unmake_move() {
         let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
@@ -878,6 +1795,382 @@ mod tests {
         assert_eq!(original_hash, board.zobrist_hash);
     }
 
+    #[test]
+    fn make_unmake_restores_pawn_hash() {
+        // Pawn push, en passant, capture-promotion and a pawn captured by a piece
+        let cases = [
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", moves::new(12, 28, moves::DOUBLE_PAWN_PUSH_FLAG)),
+            ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", moves::new(36, 45, moves::EN_PASSANT_CAPTURE_FLAG)),
+            ("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", moves::new(48, 57, moves::QUEEN_PROMOTION_CAPTURE_FLAG)),
+            ("4k3/8/8/3p4/8/8/8/3RK3 w - - 0 1", moves::new(3, 35, moves::CAPTURE_FLAG)),
+        ];
+        for (fen, m) in cases {
+            let mut board = Board::from_fen(fen).unwrap();
+            let original_hash = board.pawn_hash;
+            assert_eq!(original_hash, board.calculate_pawn_hash());
+
+            let undo = board.make_move(m);
+            assert_ne!(board.pawn_hash, original_hash, "{}", fen);
+            assert_eq!(board.pawn_hash, board.calculate_pawn_hash(), "{}", fen);
+            board.unmake_move(m, undo);
+
+            assert_eq!(board.to_fen(), fen);
+            assert_eq!(board.pawn_hash, original_hash, "{}", fen);
+        }
+
+        // Pieces moving around leave it alone
+        let mut board = Board::from_fen(cases[0].0).unwrap();
+        let hash = board.pawn_hash;
+        board.make_move(moves::new(6, 21, moves::QUIET_MOVE_FLAG));
+        assert_eq!(board.pawn_hash, hash);
+    }
+
+    #[test]
+    fn capturing_h1_rook_clears_white_kingside() {
+        let mut board = Board::from_fen("r3k2r/8/8/8/8/8/6b1/R3K2R b KQkq - 0 1").unwrap();
+        board.make_move(moves::new(14, 7, moves::CAPTURE_FLAG)); // Bg2xh1
+        assert_eq!(board.castling_rights, 0b1110);
+        assert_eq!(board.to_fen(), "r3k2r/8/8/8/8/8/8/R3K2b w Qkq - 0 2");
+        assert_eq!(board.zobrist_hash, Board::from_fen(&board.to_fen()).unwrap().zobrist_hash);
+    }
+
+    #[test]
+    fn capturing_a8_rook_clears_black_queenside() {
+        let mut board = Board::from_fen("r3k2r/1B6/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        board.make_move(moves::new(49, 56, moves::CAPTURE_FLAG)); // Bb7xa8
+        assert_eq!(board.castling_rights, 0b0111);
+        assert_eq!(board.to_fen(), "B3k2r/8/8/8/8/8/8/R3K2R b KQk - 0 1");
+        assert_eq!(board.zobrist_hash, Board::from_fen(&board.to_fen()).unwrap().zobrist_hash);
+    }
+
+    #[test]
+    fn rook_returning_home_does_not_restore_rights() {
+        let mut board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let round_trip = [
+            moves::new(7, 15, moves::QUIET_MOVE_FLAG),  // Rh1h2
+            moves::new(56, 48, moves::QUIET_MOVE_FLAG), // Ra8a7
+            moves::new(15, 7, moves::QUIET_MOVE_FLAG),  // Rh2h1
+            moves::new(48, 56, moves::QUIET_MOVE_FLAG), // Ra7a8
+        ];
+        for &m in round_trip.iter() {
+            board.make_move(m);
+        }
+        assert_eq!(board.castling_rights, 0b0110);
+        assert_eq!(board.to_fen(), "r3k2r/8/8/8/8/8/8/R3K2R w Qk - 4 3");
+        assert_eq!(board.zobrist_hash, Board::from_fen(&board.to_fen()).unwrap().zobrist_hash);
+    }
+
+    #[test]
+    fn make_move_checked_rejects_empty_square() {
+        movegen::init();
+        let mut board =
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let before = board.to_fen();
+
+        let e3e4 = moves::new(20, 28, moves::QUIET_MOVE_FLAG);
+        assert_eq!(board.make_move_checked(e3e4).unwrap_err(), MoveError::NoPiece);
+        let e7e5 = moves::new(52, 36, moves::DOUBLE_PAWN_PUSH_FLAG);
+        assert_eq!(board.make_move_checked(e7e5).unwrap_err(), MoveError::NoPiece);
+        let e2e5 = moves::new(12, 36, moves::QUIET_MOVE_FLAG);
+        assert_eq!(board.make_move_checked(e2e5).unwrap_err(), MoveError::NotPseudoLegal);
+        assert_eq!(board.to_fen(), before);
+
+        let e2e4 = moves::new(12, 28, moves::DOUBLE_PAWN_PUSH_FLAG);
+        assert!(board.make_move_checked(e2e4).is_ok());
+    }
+
+    #[test]
+    fn is_legal_rejects_pinned_piece_and_keeps_state() {
+        movegen::init();
+        let mut board = Board::from_fen("4r1k1/8/8/8/8/8/4B3/4K3 w - - 0 1").unwrap();
+        let before = board.to_fen();
+        let hash = board.zobrist_hash;
+
+        assert!(!board.is_legal(moves::new(12, 19, moves::QUIET_MOVE_FLAG)));
+        assert!(board.is_legal(moves::new(4, 3, moves::QUIET_MOVE_FLAG)));
+        assert_eq!(board.to_fen(), before);
+        assert_eq!(board.zobrist_hash, hash);
+    }
+
+    #[test]
+    fn is_legal_rejects_en_passant_exposing_king() {
+        movegen::init();
+        let mut board = Board::from_fen("8/8/8/KPp4r/8/8/8/7k w - c6 0 1").unwrap();
+        let before = board.to_fen();
+
+        assert!(!board.is_legal(moves::new(33, 42, moves::EN_PASSANT_CAPTURE_FLAG)));
+        assert!(board.is_legal(moves::new(33, 41, moves::QUIET_MOVE_FLAG)));
+        assert_eq!(board.to_fen(), before);
+    }
+
+    #[test]
+    fn is_legal_accepts_normal_move() {
+        movegen::init();
+        let mut board =
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert!(board.is_legal(moves::new(12, 28, moves::DOUBLE_PAWN_PUSH_FLAG)));
+        assert!(!board.is_legal(moves::new(12, 36, moves::QUIET_MOVE_FLAG)));
+        assert!(!board.is_legal(moves::new(52, 36, moves::DOUBLE_PAWN_PUSH_FLAG)));
+    }
+
+    #[test]
+    fn detects_checkmate_and_stalemate() {
+        movegen::init();
+        let mut mated = Board::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 1 1").unwrap();
+        assert!(mated.is_checkmate());
+        assert!(!mated.is_stalemate());
+
+        let mut stalemate = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(stalemate.is_stalemate());
+        assert!(!stalemate.is_checkmate());
+
+        // In check but able to escape
+        let mut check = Board::from_fen("R5k1/5pp1/8/8/8/8/8/6K1 b - - 1 1").unwrap();
+        assert!(check.in_check());
+        assert!(!check.is_checkmate());
+        assert!(!check.is_stalemate());
+    }
+
+    #[test]
+    fn game_result_reports_winner_and_claimable_draws() {
+        movegen::init();
+        let mut mated = Board::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 1 1").unwrap();
+        assert_eq!(mated.game_result(), GameResult::WhiteWins);
+        let mut mated = Board::from_fen("6k1/8/8/8/8/8/5PPP/r5K1 w - - 1 1").unwrap();
+        assert_eq!(mated.game_result(), GameResult::BlackWins);
+
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 99 80").unwrap();
+        assert_eq!(board.game_result(), GameResult::Ongoing);
+        board.halfmove_clock = 100;
+        assert_eq!(board.game_result(), GameResult::Draw(DrawReason::FiftyMoveRule));
+
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 0 1").unwrap();
+        let shuffle = ["a1a2", "e8d8", "a2a1", "d8e8"];
+        for (i, mv) in shuffle.iter().chain(shuffle.iter()).enumerate() {
+            assert_eq!(board.game_result(), GameResult::Ongoing, "after {} plies", i);
+            let m = crate::engine::find_move(&board, mv).unwrap();
+            board.make_move(m);
+        }
+        assert_eq!(board.game_result(), GameResult::Draw(DrawReason::ThreefoldRepetition));
+    }
+
+    #[test]
+    fn game_result_reports_automatic_draws() {
+        movegen::init();
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 149 120").unwrap();
+        assert_eq!(board.game_result(), GameResult::Draw(DrawReason::FiftyMoveRule));
+        board.halfmove_clock = 150;
+        assert_eq!(board.game_result(), GameResult::Draw(DrawReason::SeventyFiveMoveRule));
+
+        // Mate on the 75th move still counts
+        let mut mated = Board::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 150 120").unwrap();
+        assert_eq!(mated.game_result(), GameResult::WhiteWins);
+
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 0 1").unwrap();
+        let shuffle = ["a1a2", "e8d8", "a2a1", "d8e8"];
+        for (i, mv) in shuffle.iter().cycle().take(16).enumerate() {
+            let expected = if i < 8 {
+                GameResult::Ongoing
+            } else {
+                GameResult::Draw(DrawReason::ThreefoldRepetition)
+            };
+            assert_eq!(board.game_result(), expected, "after {} plies", i);
+            let m = crate::engine::find_move(&board, mv).unwrap();
+            board.make_move(m);
+        }
+        assert_eq!(board.game_result(), GameResult::Draw(DrawReason::FivefoldRepetition));
+    }
+
+    #[test]
+    fn checkers_and_king_square_tracking() {
+        movegen::init();
+        let mut board = Board::from_fen("4k3/8/8/1B6/8/8/8/4R1K1 b - - 0 1").unwrap();
+        assert!(board.in_check());
+        assert_eq!(board.checkers(), (1u64 << 33) | (1u64 << 4));
+
+        let kf8 = moves::new(60, 61, moves::QUIET_MOVE_FLAG);
+        let undo = board.make_move(kf8);
+        assert_eq!(board.king_sq[Color::Black as usize], 61);
+        assert!(!board.in_check());
+        assert_eq!(board.checkers(), 0);
+        board.unmake_move(kf8, undo);
+        assert_eq!(board.king_sq[Color::Black as usize], 60);
+    }
+
+    #[test]
+    fn gives_check_matches_make_move() {
+        movegen::init();
+        fn walk(board: &mut Board, depth: u8) {
+            let mut list = MoveList::new();
+            movegen::generate_legal_moves(board, &mut list);
+            for &m in list.iter() {
+                let predicted = board.gives_check(m);
+                let undo = board.make_move(m);
+                assert_eq!(predicted, board.in_check(), "{} in {}", moves::format(m), board.to_fen());
+                if depth > 1 {
+                    walk(board, depth - 1);
+                }
+                board.unmake_move(m, undo);
+            }
+        }
+
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            // Castling check, en passant discovered check, promotion check
+            "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+            "8/8/8/R2Pp2k/8/8/8/4K3 w - e6 0 1",
+            "8/2P1k3/8/8/8/8/8/4K3 w - - 0 1",
+        ];
+        for fen in fens {
+            let mut board = Board::from_fen(fen).unwrap();
+            walk(&mut board, 2);
+        }
+
+        let board = Board::from_fen("5k2/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert!(board.gives_check(moves::new(4, 6, moves::KING_CASTLE_FLAG)));
+        let board = Board::from_fen("8/8/8/R2Pp2k/8/8/8/4K3 w - e6 0 1").unwrap();
+        assert!(board.gives_check(moves::new(35, 44, moves::EN_PASSANT_CAPTURE_FLAG)));
+    }
+
+    #[test]
+    fn attackers_to_sees_through_removed_pieces() {
+        movegen::init();
+        let board = Board::from_fen("4k3/8/2p5/3n4/4P3/8/3R4/3RK3 w - - 0 1").unwrap();
+        let d5 = 35;
+        let occ = board.occupancy[2];
+        assert_eq!(board.attackers_to(d5, occ), (1u64 << 42) | (1u64 << 28) | (1u64 << 11));
+
+        // With the front rook gone, the one behind it attacks d5
+        let occ = occ & !(1u64 << 11);
+        assert_eq!(board.attackers_to(d5, occ), (1u64 << 42) | (1u64 << 28) | (1u64 << 3));
+    }
+
+    #[test]
+    fn make_move_checked_rejects_self_check() {
+        movegen::init();
+        let mut board = Board::from_fen("4r1k1/8/8/8/8/8/4B3/4K3 w - - 0 1").unwrap();
+        let before = board.to_fen();
+        let hash = board.zobrist_hash;
+
+        let be2d3 = moves::new(12, 19, moves::QUIET_MOVE_FLAG);
+        assert_eq!(board.make_move_checked(be2d3).unwrap_err(), MoveError::LeavesKingInCheck);
+        assert_eq!(board.to_fen(), before);
+        assert_eq!(board.zobrist_hash, hash);
+    }
+
+    #[test]
+    fn zobrist_stays_consistent_over_random_play() {
+        use rand::{Rng, SeedableRng, rngs::StdRng};
+
+        movegen::init();
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ];
+        let mut rng = StdRng::seed_from_u64(0x2117);
+
+        for fen in fens {
+            for _ in 0..20 {
+                let mut board = Board::from_fen(fen).unwrap();
+                assert_eq!(board.zobrist_hash, board.calculate_zobrist_hash());
+
+                for _ in 0..80 {
+                    let mut legal = Vec::new();
+                    let mut move_list = MoveList::new();
+                    board.generate_pseudo_legal_moves(&mut move_list);
+                    for &m in move_list.iter() {
+                        let hash = board.zobrist_hash;
+                        let pawn_hash = board.pawn_hash;
+                        let undo = board.make_move(m);
+                        assert_eq!(board.zobrist_hash, board.calculate_zobrist_hash());
+                        assert_eq!(board.pawn_hash, board.calculate_pawn_hash());
+                        let us = if board.side_to_move == Color::White {
+                            Color::Black
+                        } else {
+                            Color::White
+                        };
+                        if !board.is_square_attacked(board.king_sq[us as usize], board.side_to_move)
+                        {
+                            legal.push(m);
+                        }
+                        board.unmake_move(m, undo);
+                        assert_eq!(board.zobrist_hash, hash);
+                        assert_eq!(board.pawn_hash, pawn_hash);
+                    }
+                    if legal.is_empty() {
+                        break;
+                    }
+
+                    let hash = board.zobrist_hash;
+                    let old_ep = board.make_null_move();
+                    assert_eq!(board.zobrist_hash, board.calculate_zobrist_hash());
+                    board.unmake_null_move(old_ep);
+                    assert_eq!(board.zobrist_hash, hash);
+
+                    board.make_move(legal[rng.random_range(0..legal.len())]);
+                    assert_eq!(board.zobrist_hash, board.calculate_zobrist_hash());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn can_claim_draw_fifty_moves() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w - - 100 80").unwrap();
+        assert!(board.can_claim_draw());
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w - - 99 80").unwrap();
+        assert!(!board.can_claim_draw());
+    }
+
+    #[test]
+    fn can_claim_draw_threefold() {
+        movegen::init();
+        let mut board =
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let shuffle = [
+            moves::new(6, 21, moves::QUIET_MOVE_FLAG),  // Ng1f3
+            moves::new(62, 45, moves::QUIET_MOVE_FLAG), // Ng8f6
+            moves::new(21, 6, moves::QUIET_MOVE_FLAG),  // Nf3g1
+            moves::new(45, 62, moves::QUIET_MOVE_FLAG), // Nf6g8
+        ];
+
+        for &m in shuffle.iter() {
+            board.make_move(m);
+        }
+        assert!(!board.can_claim_draw(), "second occurrence is not yet claimable");
+
+        for &m in shuffle.iter() {
+            board.make_move(m);
+        }
+        assert!(board.can_claim_draw(), "third occurrence should be claimable");
+    }
+
+    #[test]
+    fn insufficient_material() {
+        let drawn = [
+            "8/8/4k3/8/8/3K4/8/8 w - - 0 1",
+            "8/8/4k3/8/8/3K4/6N1/8 w - - 0 1",
+            "8/8/4k3/8/2b5/3K4/6B1/8 w - - 0 1",
+        ];
+        for fen in drawn {
+            assert!(Board::from_fen(fen).unwrap().is_insufficient_material(), "{}", fen);
+        }
+
+        let playable = [
+            "8/8/4k3/8/8/3K4/4P3/8 w - - 0 1",
+            "8/8/4k3/8/3b4/3K4/6B1/8 w - - 0 1",
+            "8/8/4k3/8/8/3K4/5NN1/8 w - - 0 1",
+        ];
+        for fen in playable {
+            assert!(!Board::from_fen(fen).unwrap().is_insufficient_material(), "{}", fen);
+        }
+    }
+
     #[test]
     fn perft_startpos() {
         movegen::init();
@@ -898,6 +2191,79 @@ mod tests {
         assert_eq!(board.perft(3), 97862);
     }
 
+    #[test]
+    fn perft_divide_sums_to_perft() {
+        movegen::init();
+        let mut board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        let divide = board.perft_divide(2);
+        assert_eq!(divide.len(), 48);
+        assert_eq!(divide.iter().map(|&(_, n)| n).sum::<u64>(), 2039);
+
+        let e1g1 = divide.iter().find(|&&(m, _)| moves::format(m) == "e1g1").unwrap();
+        assert_eq!(e1g1.1, 43);
+    }
+
+    #[test]
+    fn perft_stats_kiwipete() {
+        movegen::init();
+        let mut board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        assert_eq!(
+            board.perft_stats(3),
+            PerftStats {
+                nodes: 97862,
+                captures: 17102,
+                en_passant: 45,
+                castles: 3162,
+                promotions: 0,
+                checks: 993,
+                checkmates: 1,
+            }
+        );
+
+        // Position 3 exercises en passant, checks and mates
+        let mut board = Board::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        let stats = board.perft_stats(4);
+        assert_eq!((stats.nodes, stats.captures, stats.en_passant), (43238, 3348, 123));
+        assert_eq!((stats.checks, stats.checkmates), (1680, 17));
+    }
+
+    #[test]
+    fn perft_parallel_matches_perft() {
+        movegen::init();
+        let cases = [
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 3, 97862),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4, 43238),
+            ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 3, 62379),
+        ];
+        for (fen, depth, expected) in cases {
+            let mut board = Board::from_fen(fen).unwrap();
+            for threads in [1, 3, 64] {
+                assert_eq!(board.perft_parallel(depth, threads), expected, "{} x{}", fen, threads);
+            }
+        }
+    }
+
+    #[test]
+    fn perft_hashed_matches_perft() {
+        movegen::init();
+        let cases = [
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 3, 97862),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4, 43238),
+        ];
+        // A single-entry table is overwritten constantly; a 1 MB one is reused
+        for tt in [PerftTable::new(0), PerftTable::new(1)] {
+            for (fen, depth, expected) in cases {
+                let mut board = Board::from_fen(fen).unwrap();
+                assert_eq!(board.perft_hashed(depth, &tt), expected, "{}", fen);
+                assert_eq!(board.perft_hashed(depth, &tt), expected, "{}", fen);
+            }
+        }
+    }
+
     #[test]
     fn perft_position_3() {
         movegen::init();
@@ -1,108 +1,162 @@
 use std::fs::File;
 use std::io::{BufReader, Read, Seek, SeekFrom};
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use crate::moves::{BISHOP_PROMOTION_FLAG, KNIGHT_PROMOTION_FLAG, Move, QUEEN_PROMOTION_FLAG, QUIET_MOVE_FLAG, ROOK_PROMOTION_FLAG, new};
-use crate::board::ZHash;
+use crate::board::{Board, ZHash};
+use crate::moves::{MoveList, from_sq, to_sq};
 
 const ENTRY_SIZE: usize = 16;
 
-#[derive(Debug)]
+/// Book file the engine opens unless told otherwise.
+pub const DEFAULT_BOOK: &str = "Perfect2023.bin";
+
+/// Books up to this size are read into memory whole; larger ones are
+/// binary searched on disk.
+const MAX_IN_MEMORY_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
 struct Entry {
   key: u64,
   move_raw: u16,
   weight: u16,
   learn: u32,
 }
+
+enum Source {
+  /// Every entry, sorted by key.
+  Memory(Vec<Entry>),
+  Disk(BufReader<File>),
+}
+
+/// How a move is chosen among a position's book entries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BookSelection {
+  /// At random, in proportion to the entry weights.
+  #[default]
+  WeightedRandom,
+  /// Always the highest weighted entry, the first of any tie.
+  Best,
+}
+
 pub struct OpeningBook {
-  pub file: Option<BufReader<File>>
+  source: Option<Source>,
+  /// How `probe` chooses between book moves.
+  pub selection: BookSelection,
+  /// `probe` gives no move once this many plies have been played.
+  pub max_ply: u32,
+  rng: StdRng,
 }
 
 impl OpeningBook {
+  /// Opens the Polyglot book at `path`, in memory unless it is larger than
+  /// `MAX_IN_MEMORY_BYTES`. A missing file gives a book with no moves.
   pub fn new(path: &str) -> Self {
-    let file = File::open(path).ok().map(BufReader::new);
-    Self { file }
+    match std::fs::metadata(path) {
+      Ok(meta) if meta.len() > MAX_IN_MEMORY_BYTES => Self::streaming(path),
+      _ => Self::in_memory(path),
+    }
   }
 
-  pub fn get_move(&mut self, hash: ZHash) -> Option<Move> {
-    let reader = self.file.as_mut()?;
+  /// Reads the whole book once, so lookups never touch the disk.
+  pub fn in_memory(path: &str) -> Self {
+    let source = std::fs::read(path).ok().map(|bytes| {
+      // A truncated book can't be trusted, as on the disk path
+      let mut entries: Vec<Entry> = if bytes.len() % ENTRY_SIZE == 0 {
+        bytes.chunks_exact(ENTRY_SIZE).map(parse_entry).collect()
+      } else {
+        Vec::new()
+      };
+      entries.sort_by_key(|e| e.key);
+      Source::Memory(entries)
+    });
+    Self::with_source(source)
+  }
 
+  /// Binary searches the file on every lookup, for books too big to hold.
+  pub fn streaming(path: &str) -> Self {
+    let source = File::open(path).ok().map(|file| Source::Disk(BufReader::new(file)));
+    Self::with_source(source)
+  }
 
-    let file_len = reader.get_ref().metadata().ok()?.len();
-    let num_entries = file_len / ENTRY_SIZE as u64;
+  fn with_source(source: Option<Source>) -> Self {
+    Self {
+      source,
+      selection: BookSelection::default(),
+      max_ply: u32::MAX,
+      rng: StdRng::from_rng(&mut rand::rng()),
+    }
+  }
 
-    let mut low = 0;
-    let mut high = (file_len / ENTRY_SIZE as u64) - 1;
-    let mut found_idx = None;
+  /// Switches to the book at `path`, keeping the selection settings. A
+  /// missing file leaves the book without moves.
+  pub fn load(&mut self, path: &str) {
+    let book = Self::new(path);
+    self.source = book.source;
+  }
 
-    while low <= high {
-      let mid = (low + high) / 2;
-      reader.seek(SeekFrom::Start(mid * ENTRY_SIZE as u64)).ok()?;
+  /// Reseeds the generator `probe` draws from, making its weighted
+  /// choices reproducible.
+  pub fn seed(&mut self, seed: u64) {
+    self.rng = StdRng::seed_from_u64(seed);
+  }
 
-      let mut buf_key = [0u8; 8];
-      reader.read_exact(&mut buf_key).ok()?;
+  pub fn is_loaded(&self) -> bool {
+    self.source.is_some()
+  }
 
-      let entry_key = u64::from_be_bytes(buf_key[0..8].try_into().unwrap());
+  pub fn get_move(&mut self, hash: ZHash) -> Option<Move> {
+    self.get_move_with(hash, BookSelection::WeightedRandom, &mut rand::rng())
+  }
 
-      if entry_key == hash {
-        found_idx = Some(mid);
-        break;
-      } else if entry_key < hash {
-        low = mid + 1;
-      } else {
-        if mid == 0 { break; }
-        high = mid - 1;
-      }
-    }
+  /// Picks a book move for `hash` as `selection` says, drawing any random
+  /// choice from `rng`.
+  pub fn get_move_with(&mut self, hash: ZHash, selection: BookSelection, rng: &mut impl Rng) -> Option<Move> {
+    let entries = self.entries(hash);
+    let entry = choose(&entries, selection, rng)?;
+    Some(self.polygot_move_to_internal(entry.move_raw))
+  }
 
-    let idx = found_idx?;
+  /// Every book move for `hash` with its weight, in file order.
+  pub fn moves(&mut self, hash: ZHash) -> Vec<(Move, u16)> {
+    self.entries(hash)
+      .into_iter()
+      .map(|e| (self.polygot_move_to_internal(e.move_raw), e.weight))
+      .collect()
+  }
 
-    let mut first_idx = idx;
-    while first_idx > 0 {
-      reader.seek(SeekFrom::Start((first_idx - 1) * ENTRY_SIZE as u64)).ok()?;
-      let mut buf_key = [0u8; 8];
-      reader.read_exact(&mut buf_key).ok()?;
-      if u64::from_be_bytes(buf_key) == hash {
-        first_idx -= 1;
-      } else {
-        break;
+  fn entries(&mut self, hash: ZHash) -> Vec<Entry> {
+    match self.source.as_mut() {
+      None => Vec::new(),
+      Some(Source::Memory(entries)) => {
+        let first = entries.partition_point(|e| e.key < hash);
+        entries[first..].iter().take_while(|e| e.key == hash).cloned().collect()
       }
+      Some(Source::Disk(reader)) => read_entries(reader, hash).unwrap_or_default(),
     }
+  }
 
-    let mut entries = Vec::new();
-    let mut curr_idx = first_idx;
-
-    loop{
-      if curr_idx >= num_entries { break; }
-      reader.seek(SeekFrom::Start(curr_idx * ENTRY_SIZE as u64)).ok()?;
-      let mut buf = [0u8; 16];
-      reader.read_exact(&mut buf).ok()?;
-
-      let key = u64::from_be_bytes(buf[0..8].try_into().unwrap());
-      if key != hash { break; }
-
-      let move_raw = u16::from_be_bytes(buf[8..10].try_into().unwrap());
-      let weight = u16::from_be_bytes(buf[10..12].try_into().unwrap());
-      let learn = u32::from_be_bytes(buf[12..16].try_into().unwrap());
-
-      entries.push(Entry { key, move_raw, weight, learn});
-      curr_idx += 1;
+  /// Looks up a book move for `board` and maps it onto one of the
+  /// position's generated moves so the flags are correct. Moves that are
+  /// not legal here (e.g. from a hash collision) are ignored, and so is
+  /// every position from ply `max_ply` on.
+  pub fn probe(&mut self, board: &mut Board) -> Option<Move> {
+    let ply = (board.fullmove_number.max(1) - 1) * 2 + board.side_to_move as u32;
+    if ply >= self.max_ply {
+      return None;
     }
 
-    let total_weight: u32 = entries.iter().map(|e| e.weight as u32).sum();
-    if total_weight == 0 { return None; }
-
-    let mut rng = rand::rng();
-    let mut choice = rng.random_range(0..total_weight);
-
-    for entry in entries {
-      let w = entry.weight as u32;
-      if choice < w {
-        return Some(self.polygot_move_to_internal(entry.move_raw));
-      }
-      choice -= w;
-    }
-    None
+    let entries = self.entries(board.zobrist_hash);
+    let entry = choose(&entries, self.selection, &mut self.rng)?;
+    let book_move = self.polygot_move_to_internal(entry.move_raw);
 
+    let mut move_list = MoveList::new();
+    board.generate_pseudo_legal_moves(&mut move_list);
+    let m = move_list
+      .iter()
+      .copied()
+      .find(|&m| from_sq(m) == from_sq(book_move) && to_sq(m) == to_sq(book_move))?;
+    board.is_legal(m).then_some(m)
   }
 
   /// Converts ploygot moves to the engine moves
@@ -123,4 +177,204 @@ impl OpeningBook {
 
     new(from, to, flag)
   }
-}
\ No newline at end of file
+}
+
+fn read_entries(reader: &mut BufReader<File>, hash: ZHash) -> Option<Vec<Entry>> {
+  let file_len = reader.get_ref().metadata().ok()?.len();
+  let num_entries = file_len / ENTRY_SIZE as u64;
+
+  // A truncated or empty book can't be binary searched
+  if num_entries == 0 || file_len % ENTRY_SIZE as u64 != 0 {
+    return None;
+  }
+
+  let mut low = 0;
+  let mut high = num_entries - 1;
+  let mut found_idx = None;
+
+  while low <= high {
+    let mid = (low + high) / 2;
+    reader.seek(SeekFrom::Start(mid * ENTRY_SIZE as u64)).ok()?;
+
+    let mut buf_key = [0u8; 8];
+    reader.read_exact(&mut buf_key).ok()?;
+
+    let entry_key = u64::from_be_bytes(buf_key[0..8].try_into().unwrap());
+
+    if entry_key == hash {
+      found_idx = Some(mid);
+      break;
+    } else if entry_key < hash {
+      low = mid + 1;
+    } else {
+      if mid == 0 { break; }
+      high = mid - 1;
+    }
+  }
+
+  let idx = found_idx?;
+
+  let mut first_idx = idx;
+  while first_idx > 0 {
+    reader.seek(SeekFrom::Start((first_idx - 1) * ENTRY_SIZE as u64)).ok()?;
+    let mut buf_key = [0u8; 8];
+    reader.read_exact(&mut buf_key).ok()?;
+    if u64::from_be_bytes(buf_key) == hash {
+      first_idx -= 1;
+    } else {
+      break;
+    }
+  }
+
+  let mut entries = Vec::new();
+  let mut curr_idx = first_idx;
+
+  loop{
+    if curr_idx >= num_entries { break; }
+    reader.seek(SeekFrom::Start(curr_idx * ENTRY_SIZE as u64)).ok()?;
+    let mut buf = [0u8; 16];
+    reader.read_exact(&mut buf).ok()?;
+
+    let entry = parse_entry(&buf);
+    if entry.key != hash { break; }
+
+    entries.push(entry);
+    curr_idx += 1;
+  }
+
+  Some(entries)
+}
+
+/// Entries with no weight are never chosen.
+fn choose<'a>(entries: &'a [Entry], selection: BookSelection, rng: &mut impl Rng) -> Option<&'a Entry> {
+  let total_weight: u32 = entries.iter().map(|e| e.weight as u32).sum();
+  if total_weight == 0 { return None; }
+
+  match selection {
+    BookSelection::Best => entries.iter().rev().max_by_key(|e| e.weight),
+    BookSelection::WeightedRandom => {
+      let mut choice = rng.random_range(0..total_weight);
+      for entry in entries {
+        let w = entry.weight as u32;
+        if choice < w {
+          return Some(entry);
+        }
+        choice -= w;
+      }
+      None
+    }
+  }
+}
+
+fn parse_entry(buf: &[u8]) -> Entry {
+  Entry {
+    key: u64::from_be_bytes(buf[0..8].try_into().unwrap()),
+    move_raw: u16::from_be_bytes(buf[8..10].try_into().unwrap()),
+    weight: u16::from_be_bytes(buf[10..12].try_into().unwrap()),
+    learn: u32::from_be_bytes(buf[12..16].try_into().unwrap()),
+  }
+}
+
+/// Serializes `(key, move, weight)` triples as Polyglot entries, for tests.
+#[cfg(test)]
+pub(crate) fn book_bytes(entries: &[(u64, u16, u16)]) -> Vec<u8> {
+  let mut bytes = Vec::new();
+  for &(key, m, weight) in entries {
+    bytes.extend_from_slice(&key.to_be_bytes());
+    bytes.extend_from_slice(&m.to_be_bytes());
+    bytes.extend_from_slice(&weight.to_be_bytes());
+    bytes.extend_from_slice(&0u32.to_be_bytes());
+  }
+  bytes
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::moves::{from_sq, to_sq};
+
+  fn book_from_bytes(name: &str, bytes: &[u8]) -> (OpeningBook, std::path::PathBuf) {
+    let path = std::env::temp_dir().join(format!("book_{}_{}.bin", name, std::process::id()));
+    std::fs::write(&path, bytes).unwrap();
+    (OpeningBook::new(path.to_str().unwrap()), path)
+  }
+
+  #[test]
+  fn empty_book_returns_none() {
+    let (mut book, path) = book_from_bytes("empty", &[]);
+    assert_eq!(book.get_move(0x1234), None);
+    let _ = std::fs::remove_file(path);
+  }
+
+  #[test]
+  fn malformed_book_returns_none() {
+    let (mut book, path) = book_from_bytes("malformed", &[0u8; 17]);
+    assert_eq!(book.get_move(0), None);
+    let _ = std::fs::remove_file(path);
+  }
+
+  #[test]
+  fn single_entry_book() {
+    let key: u64 = 0x463b96181691fc9c;
+    let e2e4: u16 = (12 << 6) | 28;
+    let mut entry = Vec::with_capacity(ENTRY_SIZE);
+    entry.extend_from_slice(&key.to_be_bytes());
+    entry.extend_from_slice(&e2e4.to_be_bytes());
+    entry.extend_from_slice(&1u16.to_be_bytes());
+    entry.extend_from_slice(&0u32.to_be_bytes());
+
+    let (mut book, path) = book_from_bytes("single", &entry);
+    let m = book.get_move(key).expect("entry should be found");
+    assert_eq!((from_sq(m), to_sq(m)), (12, 28));
+    assert_eq!(book.get_move(key - 1), None);
+    assert_eq!(book.get_move(key + 1), None);
+    let _ = std::fs::remove_file(path);
+  }
+
+  #[test]
+  fn memory_and_disk_give_the_same_moves() {
+    let entries: [(u64, u16, u16); 6] = [
+      (0x1000, (12 << 6) | 28, 5),
+      (0x463b96181691fc9c, (12 << 6) | 28, 10),
+      (0x463b96181691fc9c, (11 << 6) | 27, 7),
+      (0x463b96181691fc9c, (6 << 6) | 21, 3),
+      (0x463b96181691fc9c, (10 << 6) | 26, 0),
+      (0x9000_0000_0000_0000, (1 << 6) | 18, 1),
+    ];
+    let path = std::env::temp_dir().join(format!("book_paths_{}.bin", std::process::id()));
+    std::fs::write(&path, book_bytes(&entries)).unwrap();
+    let mut memory = OpeningBook::in_memory(path.to_str().unwrap());
+    let mut disk = OpeningBook::streaming(path.to_str().unwrap());
+
+    for key in [0x1000, 0x463b96181691fc9c, 0x9000_0000_0000_0000, 0x5000, 0, u64::MAX] {
+      assert_eq!(memory.moves(key), disk.moves(key), "key {:x}", key);
+    }
+    assert_eq!(memory.moves(0x463b96181691fc9c).len(), 4);
+    let _ = std::fs::remove_file(path);
+  }
+
+  #[test]
+  fn best_and_seeded_selection_are_reproducible() {
+    let key: u64 = 0x463b96181691fc9c;
+    let (e2e4, d2d4, g1f3, c2c4) = ((12 << 6) | 28, (11 << 6) | 27, (6 << 6) | 21, (10 << 6) | 26);
+    let bytes = book_bytes(&[(key, e2e4, 4), (key, d2d4, 9), (key, g1f3, 9), (key, c2c4, 0)]);
+    let (mut book, path) = book_from_bytes("selection", &bytes);
+    let from_to = |m: Move| (from_sq(m), to_sq(m));
+
+    // The first of the two weight 9 entries
+    for _ in 0..5 {
+      let m = book.get_move_with(key, BookSelection::Best, &mut rand::rng()).unwrap();
+      assert_eq!(from_to(m), (11, 27));
+    }
+
+    let draw = |book: &mut OpeningBook, seed: u64| -> Vec<(u8, u8)> {
+      let mut rng = StdRng::seed_from_u64(seed);
+      (0..50).map(|_| from_to(book.get_move_with(key, BookSelection::WeightedRandom, &mut rng).unwrap())).collect()
+    };
+    let first = draw(&mut book, 7);
+    assert_eq!(first, draw(&mut book, 7));
+    assert!(first.contains(&(12, 28)) && first.contains(&(11, 27)) && first.contains(&(6, 21)));
+    assert!(!first.contains(&(10, 26)), "a weight 0 entry was chosen");
+    let _ = std::fs::remove_file(path);
+  }
+}
@@ -0,0 +1,571 @@
+use crate::{
+    board::Board,
+    book::{DEFAULT_BOOK, OpeningBook},
+    moves::{Move, MoveList, format_uci},
+    thread::ThreadPool,
+    types::Color,
+};
+use std::sync::atomic::Ordering;
+use std::thread::{self, JoinHandle};
+
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// Limits for a single `go`, mirroring the UCI `go` parameters.
+///
+/// The limits compose: the search stops at whichever of `depth`, `nodes` or
+/// the time budget (from `movetime`, or else the clock) is reached first.
+/// `infinite` only removes the time budget; depth and node caps still apply.
+/// `mate` additionally ends the search once a short enough mate is found.
+/// With `ponder` the time budget only starts counting on `ponderhit`.
+#[derive(Debug, Clone)]
+pub struct SearchLimits {
+    pub depth: u8,
+    /// Node cap, `0` for none.
+    pub nodes: u64,
+    pub infinite: bool,
+    pub ponder: bool,
+    pub wtime: u64,
+    pub btime: u64,
+    pub winc: u64,
+    pub binc: u64,
+    pub movetime: u64,
+    pub movestogo: Option<u64>,
+    /// Look for a mate in at most this many moves.
+    pub mate: Option<i32>,
+    /// Root moves in UCI notation to restrict the search to, empty for
+    /// all. Moves that are not legal in the position are ignored.
+    pub searchmoves: Vec<String>,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        Self {
+            depth: 64,
+            nodes: 0,
+            infinite: false,
+            ponder: false,
+            wtime: 0,
+            btime: 0,
+            winc: 0,
+            binc: 0,
+            movetime: 0,
+            movestogo: None,
+            mate: None,
+            searchmoves: Vec::new(),
+        }
+    }
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u8) -> Self {
+        Self {
+            depth,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the (soft, hard) time limits in milliseconds for `side`.
+    /// Both are `u128::MAX` when the search is depth-limited or infinite.
+    pub fn time_limits(&self, side: Color, move_overhead: u64) -> (u128, u128) {
+        let safety_margin = 50_u64;
+        let time_limit: u64;
+        let hard_limit: u64;
+
+        if self.infinite {
+            return (u128::MAX, u128::MAX);
+        } else if self.movetime > 0 {
+            let movetime = self.movetime;
+            let spendable = movetime
+                .saturating_sub(move_overhead)
+                .saturating_sub(safety_margin);
+            time_limit = spendable.max(5).min(movetime.saturating_sub(1).max(1));
+            hard_limit = movetime
+                .saturating_sub(5)
+                .max(time_limit + 10)
+                .min(movetime);
+        } else if self.wtime > 0 || self.btime > 0 {
+            let (time_left, inc) = if side == Color::White {
+                (self.wtime, self.winc)
+            } else {
+                (self.btime, self.binc)
+            };
+
+            let real_time_left = time_left.saturating_sub(move_overhead);
+            let usable = real_time_left.saturating_sub(safety_margin);
+
+            if usable == 0 {
+                if inc == 0 {
+                    time_limit = 500;
+                    hard_limit = 800;
+                } else {
+                    let inc_budget = inc.saturating_sub(safety_margin / 2).max(50);
+                    time_limit = inc_budget.min(inc);
+                    hard_limit = (inc_budget + safety_margin).max(time_limit + 50).min(inc);
+                }
+            } else {
+                let mtg = self.movestogo.unwrap_or(40).max(1);
+                let base = usable / mtg;
+                let inc_bonus = inc.saturating_mul(3) / 4;
+                let mut tl = base.saturating_add(inc_bonus).max(50);
+
+                if self.movestogo.is_none() {
+                    let greedy = usable / 5 + inc / 2;
+                    tl = tl.min(greedy);
+                }
+
+                time_limit = tl.min(usable);
+                hard_limit = (tl * 3 / 2 + safety_margin).min(
+                    real_time_left
+                        .saturating_sub(safety_margin / 2)
+                        .max(tl + 50),
+                );
+            }
+        } else {
+            // Infinite search or depth-only
+            return (u128::MAX, u128::MAX);
+        }
+
+        (time_limit as u128, hard_limit as u128)
+    }
+}
+
+/// Why `Engine::set_position` rejected a position.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PositionError {
+    /// The FEN could not be parsed.
+    BadFen(&'static str),
+    /// A move matches none of the position's moves.
+    UnknownMove(String),
+    /// A move leaves the mover's king in check.
+    IllegalMove(String),
+}
+
+/// Outcome of `Engine::go`.
+#[derive(Debug, Clone, Copy)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    /// Expected reply to `best_move` from the PV, for `bestmove ... ponder`.
+    pub ponder_move: Option<Move>,
+    pub score: i32,
+    pub nodes: u64,
+    pub from_book: bool,
+}
+
+/// Library entry point: owns the search threads, the current position and
+/// the opening book. Silent by default; the UCI driver turns output back on.
+pub struct Engine {
+    pub pool: ThreadPool,
+    pub board: Board,
+    pub book: OpeningBook,
+    pub hash_mb: usize,
+    pub move_overhead: u64,
+    active: Option<ActiveSearch>,
+}
+
+/// A search started by `Engine::start` that has not been waited for yet.
+struct ActiveSearch {
+    handle: JoinHandle<()>,
+    limits: SearchLimits,
+    /// Time limits to switch to on `ponderhit`.
+    ponder_limits: (u128, u128),
+}
+
+impl Engine {
+    pub fn new(num_threads: usize, hash_mb: usize) -> Self {
+        let mut pool = ThreadPool::new(num_threads, hash_mb);
+        pool.silent = true;
+
+        Self {
+            pool,
+            board: Board::from_fen(START_FEN).unwrap(),
+            book: OpeningBook::new(DEFAULT_BOOK),
+            hash_mb,
+            move_overhead: 0,
+            active: None,
+        }
+    }
+
+    /// Sets up `fen` (or the start position for `"startpos"`) and plays
+    /// `moves` in UCI notation on top of it.
+    pub fn set_position(&mut self, fen: &str, moves: &[&str]) -> Result<(), PositionError> {
+        let fen = if fen == "startpos" { START_FEN } else { fen };
+        let mut board = Board::from_fen(fen).map_err(PositionError::BadFen)?;
+
+        for &move_str in moves {
+            let m = find_uci_move(&board, move_str, self.pool.chess960)
+                .ok_or_else(|| PositionError::UnknownMove(move_str.to_string()))?;
+            board
+                .make_move_checked(m)
+                .map_err(|_| PositionError::IllegalMove(move_str.to_string()))?;
+        }
+
+        self.board = board;
+        Ok(())
+    }
+
+    pub fn go(&mut self, limits: &SearchLimits) -> SearchResult {
+        self.wait();
+        self.pool.root_moves = self.root_moves(limits);
+        self.pool.mate_limit = limits.mate;
+        self.pool.game_history = self.board.repetition_keys();
+
+        if self.pool.root_moves.is_empty()
+            && let Some(m) = self.book.probe(&mut self.board)
+        {
+            return SearchResult {
+                best_move: Some(m),
+                ponder_move: None,
+                score: 0,
+                nodes: 0,
+                from_book: true,
+            };
+        }
+
+        let (soft, hard) = limits.time_limits(self.board.side_to_move, self.move_overhead);
+        let node_limit = if limits.nodes > 0 { limits.nodes } else { u64::MAX };
+        let (score, best_move, ponder_move) =
+            self.pool
+                .search_limited(&mut self.board, limits.depth, soft, hard, node_limit);
+
+        SearchResult {
+            best_move,
+            ponder_move,
+            score,
+            nodes: self.pool.total_nodes(),
+            from_book: false,
+        }
+    }
+
+    /// Starts searching the current position in the background and calls
+    /// `on_done` with the result once it finishes or is stopped. A
+    /// pondering search only reports after `ponderhit` or `stop`.
+    pub fn start(
+        &mut self,
+        limits: &SearchLimits,
+        on_done: impl FnOnce(SearchResult) + Send + 'static,
+    ) {
+        self.wait();
+        self.pool.root_moves = self.root_moves(limits);
+        self.pool.mate_limit = limits.mate;
+        self.pool.game_history = self.board.repetition_keys();
+
+        if !limits.ponder
+            && self.pool.root_moves.is_empty()
+            && let Some(m) = self.book.probe(&mut self.board)
+        {
+            on_done(SearchResult {
+                best_move: Some(m),
+                ponder_move: None,
+                score: 0,
+                nodes: 0,
+                from_book: true,
+            });
+            return;
+        }
+
+        let ponder_limits = limits.time_limits(self.board.side_to_move, self.move_overhead);
+        let (soft, hard) = if limits.ponder {
+            (u128::MAX, u128::MAX)
+        } else {
+            ponder_limits
+        };
+        let node_limit = if limits.nodes > 0 { limits.nodes } else { u64::MAX };
+        let depth = limits.depth;
+
+        let pool = self.pool.clone();
+        let mut board = self.board.clone_for_search();
+        pool.prepare(limits.ponder);
+
+        let handle = thread::spawn(move || {
+            let (score, best_move, ponder_move) =
+                pool.run(&mut board, depth, soft, hard, node_limit);
+            on_done(SearchResult {
+                best_move,
+                ponder_move,
+                score,
+                nodes: pool.total_nodes(),
+                from_book: false,
+            });
+        });
+
+        self.active = Some(ActiveSearch {
+            handle,
+            limits: limits.clone(),
+            ponder_limits,
+        });
+    }
+
+    /// The expected move was played: keep searching, now on the clock.
+    pub fn ponderhit(&self) {
+        if let Some(active) = &self.active
+            && active.limits.ponder
+        {
+            let (soft, hard) = active.ponder_limits;
+            self.pool.shared.ponderhit(soft, hard);
+        }
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// True if the running search will not end without `stop` (or
+    /// `ponderhit`), i.e. it is pondering or `infinite`.
+    pub fn is_unbounded(&self) -> bool {
+        self.active.as_ref().is_some_and(|active| {
+            active.limits.infinite || self.pool.shared.ponder.load(Ordering::Acquire)
+        })
+    }
+
+    /// Blocks until the search started by `start`, if any, has finished.
+    pub fn wait(&mut self) {
+        if let Some(active) = self.active.take() {
+            let _ = active.handle.join();
+        }
+    }
+
+    pub fn set_threads(&mut self, num_threads: usize) {
+        self.wait();
+        self.rebuild_pool(num_threads, self.hash_mb);
+    }
+
+    pub fn set_hash(&mut self, hash_mb: usize) {
+        self.wait();
+        self.hash_mb = hash_mb;
+        self.rebuild_pool(self.pool.num_threads, hash_mb);
+    }
+
+    pub fn new_game(&mut self) {
+        self.wait();
+        self.pool.clear();
+    }
+
+    pub fn stop(&self) {
+        self.pool.stop();
+    }
+
+    /// The legal moves among `limits.searchmoves`.
+    fn root_moves(&self, limits: &SearchLimits) -> Vec<Move> {
+        let mut board = self.board.clone_for_search();
+        let found: Vec<Move> = limits
+            .searchmoves
+            .iter()
+            .filter_map(|s| find_uci_move(&board, s, self.pool.chess960))
+            .collect();
+        found.into_iter().filter(|&m| board.is_legal(m)).collect()
+    }
+
+    fn rebuild_pool(&mut self, num_threads: usize, hash_mb: usize) {
+        let mut pool = ThreadPool::new(num_threads, hash_mb);
+        pool.show_wdl = self.pool.show_wdl;
+        pool.silent = self.pool.silent;
+        pool.jitter = self.pool.jitter;
+        pool.analyse_mode = self.pool.analyse_mode;
+        pool.contempt = self.pool.contempt;
+        pool.debug = self.pool.debug;
+        pool.params = self.pool.params;
+        pool.multipv = self.pool.multipv;
+        pool.chess960 = self.pool.chess960;
+        self.pool = pool;
+    }
+}
+
+/// Finds the pseudo-legal move matching `move_str` in UCI notation.
+pub fn find_move(board: &Board, move_str: &str) -> Option<Move> {
+    find_uci_move(board, move_str, false)
+}
+
+/// `find_move`, reading castling as king takes rook when `chess960` is set
+/// (see `moves::format_uci`).
+pub fn find_uci_move(board: &Board, move_str: &str, chess960: bool) -> Option<Move> {
+    let mut move_list = MoveList::new();
+    board.generate_pseudo_legal_moves(&mut move_list);
+    move_list
+        .iter()
+        .copied()
+        .find(|&m| format_uci(m, board, chess960) == move_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{movegen, moves::format, score::Score};
+
+    #[test]
+    fn engine_solves_mate_in_one() {
+        movegen::init();
+        let mut engine = Engine::new(1, 16);
+        engine
+            .set_position("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", &[])
+            .unwrap();
+
+        let result = engine.go(&SearchLimits::depth(4));
+        assert_eq!(result.best_move.map(format).as_deref(), Some("a1a8"));
+        assert_eq!(Score(result.score).mate_in(), Some(1));
+        assert!(!result.from_book);
+    }
+
+    #[test]
+    fn set_position_applies_moves() {
+        movegen::init();
+        let mut engine = Engine::new(1, 16);
+        engine.set_position("startpos", &["e2e4", "e7e5"]).unwrap();
+        assert_eq!(
+            engine.board.to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2"
+        );
+
+        let unknown = engine.set_position("startpos", &["e2e5"]);
+        assert_eq!(unknown, Err(PositionError::UnknownMove("e2e5".to_string())));
+        // Pseudo-legal, but the bishop is pinned to the king
+        let pinned = "4k3/4r3/8/8/8/8/4B3/4K3 w - - 0 1";
+        let illegal = engine.set_position(pinned, &["e2d3"]);
+        assert_eq!(illegal, Err(PositionError::IllegalMove("e2d3".to_string())));
+        assert!(matches!(engine.set_position("8/8/8 w - -", &[]), Err(PositionError::BadFen(_))));
+        // A rejected position leaves the previous one in place
+        assert_eq!(
+            engine.board.to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2"
+        );
+    }
+
+    #[test]
+    fn depth_and_movetime_compose() {
+        movegen::init();
+        let kiwi = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut engine = Engine::new(1, 16);
+        engine.set_position(kiwi, &[]).unwrap();
+
+        // Time runs out long before depth 64
+        let start = std::time::Instant::now();
+        let limits = SearchLimits {
+            movetime: 200,
+            ..SearchLimits::depth(64)
+        };
+        assert!(engine.go(&limits).best_move.is_some());
+        assert!(start.elapsed().as_millis() < 1000);
+
+        // Depth 1 is reached long before the movetime
+        let start = std::time::Instant::now();
+        let limits = SearchLimits {
+            movetime: 60_000,
+            ..SearchLimits::depth(1)
+        };
+        assert!(engine.go(&limits).best_move.is_some());
+        assert!(start.elapsed().as_millis() < 5000);
+    }
+
+    #[test]
+    fn sees_repetitions_begun_before_the_root() {
+        movegen::init();
+        // A queen down, Black can go back to the start position a third time
+        let fen = "r5k1/5ppp/8/8/8/8/5PPP/3Q2K1 w - - 0 1";
+        let shuffle = ["g1h1", "a8b8", "h1g1", "b8a8", "g1h1", "a8b8", "h1g1"];
+        let mut engine = Engine::new(1, 16);
+        engine.set_position(fen, &shuffle).unwrap();
+
+        let result = engine.go(&SearchLimits::depth(6));
+        assert_eq!(result.best_move.map(format).as_deref(), Some("b8a8"));
+        assert_eq!(result.score, 0);
+
+        // Without the game, the same position is just lost
+        engine.set_position("1r4k1/5ppp/8/8/8/8/5PPP/3Q2K1 b - - 7 4", &[]).unwrap();
+        assert!(engine.go(&SearchLimits::depth(6)).score < -300);
+    }
+
+    #[test]
+    fn contempt_is_charged_to_the_searching_side() {
+        movegen::init();
+        let fen = "r5k1/5ppp/8/8/8/8/5PPP/3Q2K1 w - - 0 1";
+        let shuffle = ["g1h1", "a8b8", "h1g1", "b8a8", "g1h1", "a8b8", "h1g1"];
+        let mut engine = Engine::new(1, 16);
+        engine.set_position(fen, &shuffle).unwrap();
+
+        // Black still takes the repetition, but now values it at -contempt
+        for contempt in [50, -50] {
+            engine.pool.contempt = contempt;
+            engine.new_game();
+            let result = engine.go(&SearchLimits::depth(6));
+            assert_eq!(result.best_move.map(format).as_deref(), Some("b8a8"));
+            assert_eq!(result.score, -contempt);
+        }
+    }
+
+    #[test]
+    fn mate_limit_stops_once_mate_is_found() {
+        movegen::init();
+        let mut engine = Engine::new(1, 16);
+        engine
+            .set_position("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", &[])
+            .unwrap();
+
+        // Without a depth, node or time cap only the mate ends the search
+        let limits = SearchLimits {
+            mate: Some(2),
+            ..SearchLimits::default()
+        };
+        let start = std::time::Instant::now();
+        let result = engine.go(&limits);
+        assert!(start.elapsed().as_millis() < 2000, "took {:?}", start.elapsed());
+        assert_eq!(result.best_move.map(format).as_deref(), Some("a1a8"));
+        assert_eq!(Score(result.score).mate_in(), Some(1));
+    }
+
+    #[test]
+    fn searchmoves_restricts_the_root() {
+        movegen::init();
+        let mut engine = Engine::new(1, 16);
+        engine.set_position("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", &[]).unwrap();
+
+        let limits = SearchLimits {
+            searchmoves: vec!["e1f2".into(), "d2d3".into(), "d2d9".into()],
+            ..SearchLimits::depth(5)
+        };
+        let best = engine.go(&limits).best_move.map(format);
+        assert!(matches!(best.as_deref(), Some("e1f2" | "d2d3")), "got {:?}", best);
+
+        // Only illegal moves listed: the whole root is searched
+        let limits = SearchLimits {
+            searchmoves: vec!["d2e3".into(), "e7e5".into()],
+            ..SearchLimits::depth(5)
+        };
+        assert_eq!(engine.go(&limits).best_move.map(format).as_deref(), Some("d2d5"));
+    }
+
+    #[test]
+    fn node_limit_stops_search() {
+        movegen::init();
+        let kiwi = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut engine = Engine::new(1, 16);
+        engine.set_position(kiwi, &[]).unwrap();
+
+        let limits = SearchLimits {
+            nodes: 5000,
+            infinite: true,
+            ..SearchLimits::default()
+        };
+        let result = engine.go(&limits);
+        assert!(result.best_move.is_some());
+        assert!(result.nodes <= 5000 + 64, "searched {} nodes", result.nodes);
+    }
+
+    #[test]
+    fn node_limited_search_is_reproducible() {
+        movegen::init();
+        let kiwi = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let run = || {
+            let mut engine = Engine::new(1, 16);
+            engine.set_position(kiwi, &[]).unwrap();
+            let limits = SearchLimits {
+                nodes: 20_000,
+                ..SearchLimits::default()
+            };
+            let result = engine.go(&limits);
+            (result.nodes, result.best_move, result.score)
+        };
+
+        let first = run();
+        assert_eq!(first, run());
+        assert!(first.0 <= 20_000 + 64, "searched {} nodes", first.0);
+    }
+}
@@ -1,5 +1,7 @@
+use std::cell::RefCell;
+
 use crate::{
-  board::Board, nnue, types::{Color, PieceType}
+  board::{Board, ZHash}, movegen, nnue, types::{Bitboard, Color, PieceType, Square}
 };
 
 const PAWN_VALUE: i32 = 100;
@@ -8,6 +10,75 @@ const BISHOP_VALUE: i32 = 330;
 const ROOK_VALUE: i32 = 500;
 const QUEEN_VALUE: i32 = 900;
 const KING_VALUE: i32 = 20000;
+const BISHOP_PAIR_BONUS: i32 = 30;
+
+/// Entries in each thread's pawn hash table; a power of two.
+const PAWN_TABLE_SIZE: usize = 1 << 13;
+
+// King safety: a penalty per enemy attack on the squares around the king,
+// and for a king castled on a wing, its pawn shield and open files.
+const KING_ATTACK_WEIGHT: i32 = 6;
+const KING_ATTACK_CAP: i32 = 90;
+/// Shield pawn one or two ranks in front of the king.
+const SHIELD_PAWN_BONUS: [i32; 2] = [10, 5];
+/// A shield file without our pawns, and extra if it has none at all.
+const SEMI_OPEN_FILE_PENALTY: i32 = 12;
+const OPEN_FILE_PENALTY: i32 = 8;
+
+/// Passed pawn bonus by relative rank, doubled by the endgame.
+const PASSED_PAWN_BONUS: [i32; 8] = [0, 5, 10, 20, 35, 55, 80, 0];
+const PROTECTED_PASSER_BONUS: i32 = 10;
+const CONNECTED_PASSER_BONUS: i32 = 10;
+
+/// Pawn structure penalties: per extra pawn on a file, per pawn with no
+/// friendly pawn on an adjacent file, and per pawn left behind its
+/// neighbours whose stop square an enemy pawn controls.
+const DOUBLED_PAWN_PENALTY: i32 = 10;
+const ISOLATED_PAWN_PENALTY: i32 = 12;
+const BACKWARD_PAWN_PENALTY: i32 = 8;
+
+/// Mobility bonus per safe square for knights, bishops, rooks and queens.
+const MOBILITY_WEIGHTS: [i32; 4] = [4, 4, 2, 1];
+/// Most squares a knight, bishop, rook and queen can attack.
+const MAX_ATTACKS: [i32; 4] = [8, 13, 14, 27];
+
+// Rook activity: files without own pawns, and the 7th rank
+const ROOK_OPEN_FILE_BONUS: i32 = 20;
+const ROOK_SEMI_OPEN_FILE_BONUS: i32 = 10;
+const ROOK_SEVENTH_RANK_BONUS: i32 = 20;
+
+const FILE_A: Bitboard = 0x0101_0101_0101_0101;
+const FILE_H: Bitboard = FILE_A << 7;
+
+/// Squares that must hold no enemy pawn for a pawn of each color on a
+/// square to be passed: its own and the adjacent files, ahead of it.
+const PASSED_PAWN_MASKS: [[Bitboard; 64]; 2] = passed_pawn_masks();
+
+const fn passed_pawn_masks() -> [[Bitboard; 64]; 2] {
+  let mut masks = [[0; 64]; 2];
+  let mut sq = 0;
+  while sq < 64 {
+    let files = (FILE_A << (sq % 8)) | adjacent_files(sq % 8);
+    let rank = sq / 8;
+    let above = if rank == 7 { 0 } else { !0 << (8 * (rank + 1)) };
+    let below = if rank == 0 { 0 } else { !0 >> (8 * (8 - rank)) };
+    masks[Color::White as usize][sq] = files & above;
+    masks[Color::Black as usize][sq] = files & below;
+    sq += 1;
+  }
+  masks
+}
+
+const fn adjacent_files(file: usize) -> Bitboard {
+  let mut files = 0;
+  if file > 0 {
+    files |= FILE_A << (file - 1);
+  }
+  if file < 7 {
+    files |= FILE_A << (file + 1);
+  }
+  files
+}
 
 #[rustfmt::skip]
 const PAWN_TABLE: [i32; 64] = [
@@ -69,33 +140,80 @@ const QUEEN_TABLE: [i32; 64] = [
     -20,-10,-10, -5, -5,-10,-10,-20
 ];
 
+// The king tables are indexed from a1, so rank 1 comes first.
 #[rustfmt::skip]
-const KING_TABLE: [i32; 64] = [
+const KING_MG_TABLE: [i32; 64] = [
+     20, 30, 10,  0,  0, 10, 30, 20,
+     20, 20,  0,  0,  0,  0, 20, 20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+    -20,-30,-30,-40,-40,-30,-30,-20,
     -30,-40,-40,-50,-50,-40,-40,-30,
     -30,-40,-40,-50,-50,-40,-40,-30,
     -30,-40,-40,-50,-50,-40,-40,-30,
     -30,-40,-40,-50,-50,-40,-40,-30,
-    -20,-30,-30,-40,-40,-30,-30,-20,
-    -10,-20,-20,-20,-20,-20,-20,-10,
-     20, 20,  0,  0,  0,  0, 20, 20,
-     20, 30, 10,  0,  0, 10, 30, 20
 ];
 
+#[rustfmt::skip]
+const KING_EG_TABLE: [i32; 64] = [
+    -50,-30,-30,-30,-30,-30,-30,-50,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -50,-40,-30,-20,-20,-30,-40,-50,
+];
+
+/// Static evaluation in centipawns from the side to move's point of view:
+/// positive means the player to move is better. Both the classical and
+/// NNUE paths follow this contract, and negamax depends on it.
 pub fn evaluate(board: &Board) -> i32 {
   if nnue::is_enabled() {
     return nnue::evaluate(board);
   }
 
-  let mut score = 0;
+  let score = material_pst(board) + positional(board);
+  if board.side_to_move == Color::White {
+    score
+  } else {
+    -score
+  }
+}
+
+/// Like `evaluate`, but for the classical evaluator skips the piece terms
+/// when material, PST and pawns alone already fall further outside
+/// `[alpha, beta]` than `piece_terms_bound` allows. NNUE is always
+/// evaluated fully.
+pub fn evaluate_lazy(board: &Board, alpha: i32, beta: i32) -> i32 {
+  if nnue::is_enabled() {
+    return nnue::evaluate(board);
+  }
+
+  let sign = if board.side_to_move == Color::White { 1 } else { -1 };
+  let base = sign * (material_pst(board) + pawn_terms(board));
+  let margin = piece_terms_bound(board);
+  if base + margin < alpha || base - margin > beta {
+    return base;
+  }
+
+  base + sign * piece_terms(board)
+}
+
+/// Material (with the bishop pair) plus piece-square tables, from White's
+/// point of view.
+fn material_pst(board: &Board) -> i32 {
+  let phase = board.phase();
+  let mut score = bishop_pair(board, Color::White) - bishop_pair(board, Color::Black);
 
   for pt in 0..6 {
-    let piece_type = PieceType::from(pt);
+    let piece_type = PieceType::from_index(pt);
 
     let mut white_pieces = board.pieces[pt][Color::White as usize];
     while white_pieces != 0 {
       let sq = white_pieces.trailing_zeros() as usize;
       score += get_piece_value(piece_type);
-      score += get_pst_value(piece_type, sq, Color::White);
+      score += get_pst_value(piece_type, sq, Color::White, phase);
       white_pieces &= white_pieces - 1;
     }
 
@@ -103,11 +221,324 @@ pub fn evaluate(board: &Board) -> i32 {
     while black_pieces != 0 {
       let sq = black_pieces.trailing_zeros() as usize;
       score -= get_piece_value(piece_type);
-      score -= get_pst_value(piece_type, sq, Color::Black);
+      score -= get_pst_value(piece_type, sq, Color::Black, phase);
       black_pieces &= black_pieces - 1;
     }
   }
 
+  score
+}
+
+/// The positional terms from White's point of view.
+fn positional(board: &Board) -> i32 {
+  pawn_terms(board) + piece_terms(board)
+}
+
+/// Pawn structure and passed pawns, mostly served by the pawn hash table.
+fn pawn_terms(board: &Board) -> i32 {
+  let pawns = probe_pawn_table(board);
+  passed_pawns(board, pawns.passers) + pawns.structure
+}
+
+/// King safety, mobility and rook activity: the costly terms that lazy
+/// eval may skip.
+fn piece_terms(board: &Board) -> i32 {
+  king_safety(board) + mobility(board) + rook_activity(board)
+}
+
+/// Upper bound on the size of `piece_terms`, from the piece counts.
+fn piece_terms_bound(board: &Board) -> i32 {
+  // Each king's shelter lies between -(cap + 3 missing shield files) and
+  // +3 shield pawns, so the difference is at most the sum of the two
+  let king = KING_ATTACK_CAP
+    + 3 * (SEMI_OPEN_FILE_PENALTY + OPEN_FILE_PENALTY)
+    + 3 * SHIELD_PAWN_BONUS[0];
+  // Mobility and rook activity are never negative, so the larger side bounds
+  // their difference
+  let side = |color: Color| -> i32 {
+    let mut score = 0;
+    for (i, (weight, attacks)) in MOBILITY_WEIGHTS.iter().zip(MAX_ATTACKS).enumerate() {
+      score += board.pieces[i + 1][color as usize].count_ones() as i32 * weight * attacks;
+    }
+    let rooks = board.pieces[PieceType::Rook as usize][color as usize].count_ones() as i32;
+    score + rooks * (ROOK_OPEN_FILE_BONUS + ROOK_SEVENTH_RANK_BONUS)
+  };
+  king + side(Color::White).max(side(Color::Black))
+}
+
+/// Piece mobility from White's point of view.
+fn mobility(board: &Board) -> i32 {
+  mobility_score(board, Color::White) - mobility_score(board, Color::Black)
+}
+
+/// Weighted count of the squares `us`'s pieces attack that hold no friendly
+/// piece and are not guarded by an enemy pawn.
+fn mobility_score(board: &Board, us: Color) -> i32 {
+  let them = if us == Color::White { Color::Black } else { Color::White };
+  let occ = board.occupancy[2];
+  let safe = !board.occupancy[us as usize]
+    & !pawn_attack_set(board.pieces[PieceType::Pawn as usize][them as usize], them);
+
+  let mut score = 0;
+  for (i, weight) in MOBILITY_WEIGHTS.iter().enumerate() {
+    let piece_type = PieceType::from_index(i + 1);
+    let mut pieces = board.pieces[piece_type as usize][us as usize];
+    while pieces != 0 {
+      let sq = pieces.trailing_zeros() as Square;
+      let attacks = match piece_type {
+        PieceType::Knight => movegen::knight_attacks(sq),
+        PieceType::Bishop => movegen::get_bishop_attacks(sq, occ),
+        PieceType::Rook => movegen::get_rook_attacks(sq, occ),
+        _ => movegen::get_bishop_attacks(sq, occ) | movegen::get_rook_attacks(sq, occ),
+      };
+      score += (attacks & safe).count_ones() as i32 * weight;
+      pieces &= pieces - 1;
+    }
+  }
+
+  score
+}
+
+/// Rook activity from White's point of view.
+fn rook_activity(board: &Board) -> i32 {
+  rook_activity_score(board, Color::White) - rook_activity_score(board, Color::Black)
+}
+
+/// Bonus for `us`'s rooks on open or semi-open files and on the 7th rank.
+fn rook_activity_score(board: &Board, us: Color) -> i32 {
+  let them = if us == Color::White { Color::Black } else { Color::White };
+  let our_pawns = board.pieces[PieceType::Pawn as usize][us as usize];
+  let their_pawns = board.pieces[PieceType::Pawn as usize][them as usize];
+  let seventh = if us == Color::White { 6 } else { 1 };
+
+  let mut score = 0;
+  let mut rooks = board.pieces[PieceType::Rook as usize][us as usize];
+  while rooks != 0 {
+    let sq = rooks.trailing_zeros() as Square;
+    rooks &= rooks - 1;
+
+    let file = FILE_A << (sq % 8);
+    if our_pawns & file == 0 {
+      score += if their_pawns & file == 0 {
+        ROOK_OPEN_FILE_BONUS
+      } else {
+        ROOK_SEMI_OPEN_FILE_BONUS
+      };
+    }
+    if sq / 8 == seventh {
+      score += ROOK_SEVENTH_RANK_BONUS;
+    }
+  }
+
+  score
+}
+
+/// Squares attacked by `pawns` of `color`.
+fn pawn_attack_set(pawns: Bitboard, color: Color) -> Bitboard {
+  if color == Color::White {
+    ((pawns & !FILE_A) << 7) | ((pawns & !FILE_H) << 9)
+  } else {
+    ((pawns & !FILE_A) >> 9) | ((pawns & !FILE_H) >> 7)
+  }
+}
+
+/// Doubled, isolated and backward pawn penalties from White's point of view.
+fn pawn_structure(board: &Board) -> i32 {
+  pawn_structure_penalty(board, Color::Black) - pawn_structure_penalty(board, Color::White)
+}
+
+fn pawn_structure_penalty(board: &Board, us: Color) -> i32 {
+  let them = if us == Color::White { Color::Black } else { Color::White };
+  let ours = board.pieces[PieceType::Pawn as usize][us as usize];
+  let theirs = board.pieces[PieceType::Pawn as usize][them as usize];
+  let mut penalty = 0;
+
+  for file in 0..8 {
+    let count = (ours & (FILE_A << file)).count_ones() as i32;
+    if count > 1 {
+      penalty += (count - 1) * DOUBLED_PAWN_PENALTY;
+    }
+  }
+
+  let mut pawns = ours;
+  while pawns != 0 {
+    let sq = pawns.trailing_zeros() as Square;
+    pawns &= pawns - 1;
+
+    let neighbours = ours & adjacent_files((sq % 8) as usize);
+    if neighbours == 0 {
+      penalty += ISOLATED_PAWN_PENALTY;
+      continue;
+    }
+
+    // Backward: every neighbour is further up, and advancing is met by a pawn
+    let (at_or_behind, stop) = if us == Color::White {
+      (!0 >> (8 * (7 - sq / 8)), sq + 8)
+    } else {
+      (!0 << (8 * (sq / 8)), sq.wrapping_sub(8))
+    };
+    if neighbours & at_or_behind == 0
+      && stop < 64
+      && movegen::pawn_attacks(us, stop) & theirs != 0
+    {
+      penalty += BACKWARD_PAWN_PENALTY;
+    }
+  }
+
+  penalty
+}
+
+/// The pawn-only terms of a position, cached by `Board::pawn_hash`.
+#[derive(Clone, Copy, Default)]
+struct PawnEntry {
+  key: ZHash,
+  /// `pawn_structure`, from White's point of view.
+  structure: i32,
+  /// Passed pawns of each color.
+  passers: [Bitboard; 2],
+}
+
+impl PawnEntry {
+  fn compute(board: &Board) -> Self {
+    PawnEntry {
+      key: board.pawn_hash,
+      structure: pawn_structure(board),
+      passers: [passed_pawn_set(board, Color::White), passed_pawn_set(board, Color::Black)],
+    }
+  }
+}
+
+/// Always-replace table of `PawnEntry`. An empty slot has key 0, which is
+/// also the key and the (all zero) entry of a position without pawns.
+struct PawnHashTable {
+  entries: Vec<PawnEntry>,
+}
+
+impl PawnHashTable {
+  fn new() -> Self {
+    PawnHashTable { entries: vec![PawnEntry::default(); PAWN_TABLE_SIZE] }
+  }
+
+  fn probe(&mut self, board: &Board) -> PawnEntry {
+    let slot = &mut self.entries[board.pawn_hash as usize & (PAWN_TABLE_SIZE - 1)];
+    if slot.key != board.pawn_hash {
+      *slot = PawnEntry::compute(board);
+    }
+    *slot
+  }
+}
+
+thread_local! {
+  // One per search thread, so probes need no synchronization
+  static PAWN_HASH_TABLE: RefCell<PawnHashTable> = RefCell::new(PawnHashTable::new());
+}
+
+fn probe_pawn_table(board: &Board) -> PawnEntry {
+  PAWN_HASH_TABLE.with(|table| table.borrow_mut().probe(board))
+}
+
+/// Passed pawns from White's point of view, given each side's passers.
+fn passed_pawns(board: &Board, passers: [Bitboard; 2]) -> i32 {
+  passed_pawn_score(board, Color::White, passers[0])
+    - passed_pawn_score(board, Color::Black, passers[1])
+}
+
+fn passed_pawn_set(board: &Board, us: Color) -> Bitboard {
+  let them = if us == Color::White { Color::Black } else { Color::White };
+  let theirs = board.pieces[PieceType::Pawn as usize][them as usize];
+
+  let mut passers: Bitboard = 0;
+  let mut pawns = board.pieces[PieceType::Pawn as usize][us as usize];
+  while pawns != 0 {
+    let sq = pawns.trailing_zeros() as usize;
+    if PASSED_PAWN_MASKS[us as usize][sq] & theirs == 0 {
+      passers |= 1 << sq;
+    }
+    pawns &= pawns - 1;
+  }
+  passers
+}
+
+/// Rank-scaled bonus for `us`'s passed pawns, with extra for passers that
+/// are defended by a pawn or have a passer on an adjacent file.
+fn passed_pawn_score(board: &Board, us: Color, passers: Bitboard) -> i32 {
+  let them = if us == Color::White { Color::Black } else { Color::White };
+  let ours = board.pieces[PieceType::Pawn as usize][us as usize];
+
+  let endgame = 256 - board.phase();
+  let mut score = 0;
+  let mut rest = passers;
+  while rest != 0 {
+    let sq = rest.trailing_zeros() as Square;
+    let rank = if us == Color::White { sq / 8 } else { 7 - sq / 8 };
+    let mut bonus = PASSED_PAWN_BONUS[rank as usize];
+    if movegen::pawn_attacks(them, sq) & ours != 0 {
+      bonus += PROTECTED_PASSER_BONUS;
+    }
+    if adjacent_files((sq % 8) as usize) & passers != 0 {
+      bonus += CONNECTED_PASSER_BONUS;
+    }
+    score += bonus + bonus * endgame / 256;
+    rest &= rest - 1;
+  }
+
+  score
+}
+
+/// King safety from White's point of view, faded out as material leaves.
+fn king_safety(board: &Board) -> i32 {
+  let score = king_shelter(board, Color::White) - king_shelter(board, Color::Black);
+  score * board.phase() / 256
+}
+
+/// Safety of `us`'s king: enemy attacks next to it and, once it has
+/// castled to a wing, the pawns on its file and the adjacent ones.
+fn king_shelter(board: &Board, us: Color) -> i32 {
+  let them = if us == Color::White { Color::Black } else { Color::White };
+  let king_sq = board.king_sq[us as usize];
+  let enemies = board.occupancy[them as usize];
+
+  let mut attacks = 0;
+  let mut zone = movegen::king_attacks(king_sq);
+  while zone != 0 {
+    let sq = zone.trailing_zeros() as Square;
+    attacks += (board.attackers_to(sq, board.occupancy[2]) & enemies).count_ones() as i32;
+    zone &= zone - 1;
+  }
+  let mut score = -(attacks * KING_ATTACK_WEIGHT).min(KING_ATTACK_CAP);
+
+  let relative_rank = if us == Color::White { king_sq / 8 } else { 7 - king_sq / 8 };
+  let file = king_sq % 8;
+  if relative_rank != 0 || (3..=4).contains(&file) {
+    return score;
+  }
+
+  let our_pawns = board.pieces[PieceType::Pawn as usize][us as usize];
+  let their_pawns = board.pieces[PieceType::Pawn as usize][them as usize];
+  let rank_in_front = |n: u8| -> Bitboard {
+    if us == Color::White { 0xFF << (8 * n) } else { 0xFF << (8 * (7 - n)) }
+  };
+
+  for f in file.saturating_sub(1)..=(file + 1).min(7) {
+    let ours = our_pawns & (FILE_A << f);
+    if ours & rank_in_front(1) != 0 {
+      score += SHIELD_PAWN_BONUS[0];
+    } else if ours & rank_in_front(2) != 0 {
+      score += SHIELD_PAWN_BONUS[1];
+    } else if ours == 0 {
+      score -= SEMI_OPEN_FILE_PENALTY;
+      if their_pawns & (FILE_A << f) == 0 {
+        score -= OPEN_FILE_PENALTY;
+      }
+    }
+  }
+
+  score
+}
+
+/// Static evaluation in centipawns from White's point of view, for display.
+pub fn evaluate_white(board: &Board) -> i32 {
+  let score = evaluate(board);
   if board.side_to_move == Color::White {
     score
   } else {
@@ -115,6 +546,14 @@ pub fn evaluate(board: &Board) -> i32 {
   }
 }
 
+fn bishop_pair(board: &Board, color: Color) -> i32 {
+  if board.pieces[PieceType::Bishop as usize][color as usize].count_ones() >= 2 {
+    BISHOP_PAIR_BONUS
+  } else {
+    0
+  }
+}
+
 fn get_piece_value(pt: PieceType) -> i32 {
   match pt {
     PieceType::Pawn => PAWN_VALUE,
@@ -126,20 +565,17 @@ fn get_piece_value(pt: PieceType) -> i32 {
   }
 }
 
-fn get_pst_value(pt: PieceType, sq: usize, color: Color) -> i32 {
-  let table = match pt {
-    PieceType::Pawn => &PAWN_TABLE,
-    PieceType::Knight => &KNIGHT_TABLE,
-    PieceType::Bishop => &BISHOP_TABLE,
-    PieceType::Rook => &ROOK_TABLE,
-    PieceType::Queen => &QUEEN_TABLE,
-    PieceType::King => &KING_TABLE,
-  };
-
-  if color == Color::White {
-    table[sq]
-  } else {
-    table[sq ^ 56]
+/// PST bonus for a piece on `sq`. The king's is tapered between its
+/// middlegame and endgame tables by `phase` (see `Board::phase`).
+fn get_pst_value(pt: PieceType, sq: usize, color: Color, phase: i32) -> i32 {
+  let sq = if color == Color::White { sq } else { sq ^ 56 };
+  match pt {
+    PieceType::Pawn => PAWN_TABLE[sq],
+    PieceType::Knight => KNIGHT_TABLE[sq],
+    PieceType::Bishop => BISHOP_TABLE[sq],
+    PieceType::Rook => ROOK_TABLE[sq],
+    PieceType::Queen => QUEEN_TABLE[sq],
+    PieceType::King => (KING_MG_TABLE[sq] * phase + KING_EG_TABLE[sq] * (256 - phase)) / 256,
   }
 }
 
@@ -150,20 +586,172 @@ mod tests {
 
   #[test]
   fn test_eval_startpos() {
+    movegen::init();
     let board = Board::default();
     assert_eq!(evaluate(&board), 0);
   }
 
   #[test]
   fn test_eval_material_imbalance() {
+    movegen::init();
     let board = Board::from_fen("4k3/8/8/8/4P3/8/8/4K3 w - - 0 1").unwrap();
     let score = evaluate(&board);
     assert!(score > 0, "White should be winning with extra pawn");
-    assert_eq!(score, 100 + 25);
+    // Pawn, its e4 PST entry and, with no pieces left, a doubled passer
+    // bonus; it is also isolated
+    assert_eq!(score, 100 + 20 + 2 * PASSED_PAWN_BONUS[3] - ISOLATED_PAWN_PENALTY);
+  }
+
+  #[test]
+  fn test_king_centralizes_in_the_endgame() {
+    movegen::init();
+    let corner = Board::from_fen("8/8/8/8/8/4P3/8/K6k w - - 0 1").unwrap();
+    let center = Board::from_fen("8/8/8/8/3K4/4P3/8/7k w - - 0 1").unwrap();
+    assert_eq!(center.phase(), 0);
+    assert!(evaluate(&center) > evaluate(&corner));
+
+    // With all pieces on the board the castled king is still preferred
+    let castled =
+      Board::from_fen("rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQ1BKR w kq - 0 1").unwrap();
+    let central =
+      Board::from_fen("rnbqkbnr/pppppppp/8/8/8/4KN2/PPPPPPPP/RNBQ1B1R w kq - 0 1").unwrap();
+    assert_eq!(castled.phase(), 256);
+    assert!(evaluate(&castled) > evaluate(&central));
+  }
+
+  #[test]
+  fn test_king_shelter() {
+    movegen::init();
+    let shielded = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1").unwrap();
+    let open_g = Board::from_fen("6k1/5ppp/8/8/8/8/5P1P/6K1 w - - 0 1").unwrap();
+    let attacked = Board::from_fen("6k1/5ppp/8/8/8/6q1/5P1P/6K1 w - - 0 1").unwrap();
+    let white = |board: &Board| king_shelter(board, Color::White);
+    assert_eq!(white(&shielded), 3 * SHIELD_PAWN_BONUS[0]);
+    assert!(white(&shielded) > white(&open_g));
+    assert!(white(&open_g) > white(&attacked));
+    assert_eq!(king_shelter(&shielded, Color::Black), white(&shielded));
+  }
+
+  #[test]
+  fn test_king_safety_is_symmetric() {
+    movegen::init();
+    let kiwi = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    let mirrored = "r3k2r/pppbbppp/2n2q1P/1P2p3/3pn3/BN2PNP1/P1PPQPB1/R3K2R b KQkq - 0 1";
+    let kiwi = Board::from_fen(kiwi).unwrap();
+    let mirrored = Board::from_fen(mirrored).unwrap();
+    assert_eq!(king_safety(&kiwi), -king_safety(&mirrored));
+    assert_eq!(evaluate(&kiwi), evaluate(&mirrored));
+  }
+
+  #[test]
+  fn test_passed_pawns() {
+    movegen::init();
+    // Only the a-pawn is passed
+    let board = Board::from_fen("4k3/8/5p2/4p3/P3P3/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(passed_pawns(&board, PawnEntry::compute(&board).passers), 2 * PASSED_PAWN_BONUS[3]);
+    assert!(evaluate(&board) > 0);
+    assert!(evaluate(&Board::from_fen("4k3/8/5p2/4p3/P3P3/8/8/4K3 b - - 0 1").unwrap()) < 0);
+
+    // Side by side passers earn more than two lone ones
+    let connected = Board::from_fen("4k3/8/8/2PP4/8/8/8/4K3 w - - 0 1").unwrap();
+    let apart = Board::from_fen("4k3/8/8/P6P/8/8/8/4K3 w - - 0 1").unwrap();
+    let connected = passed_pawns(&connected, PawnEntry::compute(&connected).passers);
+    let apart = passed_pawns(&apart, PawnEntry::compute(&apart).passers);
+    assert!(connected > apart);
+    assert!(apart > 0);
+  }
+
+  #[test]
+  fn test_pawn_table_matches_recomputation() {
+    movegen::init();
+    let mut table = PawnHashTable::new();
+    let mut board =
+      Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+    let fresh = PawnEntry::compute(&board);
+    let first = table.probe(&board);
+    assert_eq!((first.structure, first.passers), (fresh.structure, fresh.passers));
+
+    // A hit returns the stored entry, and pawn moves and captures find a new one
+    let cached = table.probe(&board);
+    assert_eq!((cached.key, cached.structure), (fresh.key, fresh.structure));
+    for uci in ["d5e6", "a6e2", "e6f7", "e8f7"] {
+      let m = crate::engine::find_move(&board, uci).unwrap();
+      board.make_move(m);
+      let fresh = PawnEntry::compute(&board);
+      let probed = table.probe(&board);
+      assert_eq!(probed.key, board.pawn_hash);
+      assert_eq!((probed.structure, probed.passers), (fresh.structure, fresh.passers));
+    }
+  }
+
+  #[test]
+  fn test_pawn_structure() {
+    movegen::init();
+    assert_eq!(pawn_structure(&Board::default()), 0);
+
+    // Tripled isolated e-pawns against a healthy kingside trio, and the mirror
+    let tripled = Board::from_fen("4k3/5ppp/8/8/4P3/4P3/4P3/4K3 w - - 0 1").unwrap();
+    let mirrored = Board::from_fen("4k3/4p3/4p3/4p3/8/8/5PPP/4K3 b - - 0 1").unwrap();
+    assert_eq!(pawn_structure(&tripled), -(2 * DOUBLED_PAWN_PENALTY + 3 * ISOLATED_PAWN_PENALTY));
+    assert_eq!(pawn_structure(&mirrored), -pawn_structure(&tripled));
+    assert!(evaluate_white(&tripled) < evaluate_white(&mirrored));
+
+    // d3 trails c4 and e4, and c5 guards d4
+    let backward = Board::from_fen("4k3/8/8/2p5/2P1P3/3P4/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(pawn_structure_penalty(&backward, Color::White), BACKWARD_PAWN_PENALTY);
+  }
+
+  #[test]
+  fn test_mobility() {
+    movegen::init();
+    let start = Board::default();
+    assert_eq!(mobility(&start), 0);
+
+    // Same material, White developed
+    let developed =
+      Board::from_fen("rnbqkbnr/pppppppp/8/8/2B1P3/2N2N2/PPPP1PPP/R1BQK2R w KQkq - 0 1").unwrap();
+    assert!(mobility(&developed) > 0);
+    assert!(evaluate(&developed) > evaluate(&start));
+
+    // Squares guarded by enemy pawns do not count
+    let guarded = Board::from_fen("4k3/8/8/2p1p3/8/3N4/8/4K3 w - - 0 1").unwrap();
+    let free = Board::from_fen("4k3/8/8/8/8/3N4/8/4K3 w - - 0 1").unwrap();
+    let lost = mobility_score(&free, Color::White) - mobility_score(&guarded, Color::White);
+    assert_eq!(lost, 2 * MOBILITY_WEIGHTS[0]);
+  }
+
+  #[test]
+  fn test_rook_activity() {
+    movegen::init();
+    assert_eq!(rook_activity(&Board::default()), 0);
+
+    let behind_pawn = Board::from_fen("4k3/pp6/8/8/8/8/PPP5/R3K3 w - - 0 1").unwrap();
+    let open_file = Board::from_fen("4k3/pp6/8/8/8/8/PPP5/3RK3 w - - 0 1").unwrap();
+    assert_eq!(rook_activity(&behind_pawn), 0);
+    assert_eq!(rook_activity(&open_file), ROOK_OPEN_FILE_BONUS);
+    assert!(evaluate(&open_file) > evaluate(&behind_pawn));
+
+    let semi_open = Board::from_fen("4k3/p7/8/8/8/8/1PP5/R3K3 w - - 0 1").unwrap();
+    assert_eq!(rook_activity(&semi_open), ROOK_SEMI_OPEN_FILE_BONUS);
+
+    let seventh = Board::from_fen("4k3/R7/8/8/8/8/1PP5/4K3 w - - 0 1").unwrap();
+    assert_eq!(rook_activity(&seventh), ROOK_OPEN_FILE_BONUS + ROOK_SEVENTH_RANK_BONUS);
+  }
+
+  #[test]
+  fn test_bishop_pair() {
+    movegen::init();
+    let pair = Board::from_fen("2b1kn2/8/8/8/8/8/8/2B1KB2 w - - 0 1").unwrap();
+    let mirrored = Board::from_fen("2b1kb2/8/8/8/8/8/8/2B1KN2 w - - 0 1").unwrap();
+    assert_eq!(bishop_pair(&pair, Color::White), BISHOP_PAIR_BONUS);
+    assert_eq!(bishop_pair(&pair, Color::Black), 0);
+    assert!(evaluate(&pair) > 0);
+    assert!(evaluate(&mirrored) < 0);
   }
 
   #[test]
   fn test_eval_symmetry() {
+    movegen::init();
     let board_w = Board::from_fen("7k/8/8/8/8/8/8/N6K w - - 0 1").unwrap();
     let score_w = evaluate(&board_w);
 
@@ -172,4 +760,50 @@ mod tests {
 
     assert_eq!(score_w, score_b);
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn test_evaluate_white_perspective() {
+    movegen::init();
+    let board_w = Board::from_fen("4k3/8/8/3n4/8/2N1B3/8/4K3 w - - 0 1").unwrap();
+    let board_b = Board::from_fen("4k3/8/8/3n4/8/2N1B3/8/4K3 b - - 0 1").unwrap();
+
+    assert_eq!(evaluate(&board_w), -evaluate(&board_b));
+    assert_eq!(evaluate_white(&board_w), evaluate_white(&board_b));
+    assert!(evaluate_white(&board_b) > 0, "White is a bishop up");
+  }
+
+  #[test]
+  fn test_lazy_eval_matches_full_inside_window() {
+    movegen::init();
+    let fens = [
+      "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+      "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+    ];
+    for fen in fens {
+      let board = Board::from_fen(fen).unwrap();
+      let full = evaluate(&board);
+      assert_eq!(evaluate_lazy(&board, full - 50, full + 50), full, "{}", fen);
+    }
+  }
+
+  #[test]
+  fn test_lazy_eval_skips_positional_far_outside_window() {
+    movegen::init();
+    let board = Board::from_fen("4k3/8/8/8/8/8/8/QQ2K3 w - - 0 1").unwrap();
+    let base = material_pst(&board);
+    assert_eq!(evaluate_lazy(&board, -100, 100), base);
+  }
+
+  #[test]
+  fn test_positional_terms_are_not_clamped() {
+    movegen::init();
+    // Four connected passers on the 7th are worth far more than 300
+    let board = Board::from_fen("r3k3/PPPP4/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let full = evaluate(&board);
+    assert!(full - material_pst(&board) > 300);
+    for window in [-1000, -200, 0, 200, full - 1, full, full + 1, 2000] {
+      let lazy = evaluate_lazy(&board, window, window + 1);
+      assert_eq!(lazy > window, full > window, "window {}", window);
+    }
+  }
+}
@@ -0,0 +1,112 @@
+pub use crate::board::{DrawReason, GameResult};
+
+use crate::{
+    board::Board,
+    moves::Move,
+    thread::ThreadPool,
+    types::Color,
+};
+
+/// Per-side search limits for in-process games.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeControl {
+    pub depth: u8,
+    /// Milliseconds per move, `0` for a pure depth-limited search.
+    pub movetime: u64,
+}
+
+impl TimeControl {
+    pub fn depth(depth: u8) -> Self {
+        Self { depth, movetime: 0 }
+    }
+}
+
+/// Classifies the current position for adjudication.
+pub fn adjudicate(board: &mut Board) -> GameResult {
+    board.game_result()
+}
+
+/// Plays an engine-vs-engine game from `board`, each side searching with its
+/// own thread pool. Stops after `max_plies` with `GameResult::Ongoing`.
+pub fn play_game(
+    mut board: Board,
+    white: TimeControl,
+    black: TimeControl,
+    max_plies: usize,
+) -> (GameResult, Vec<Move>) {
+    let mut white_pool = ThreadPool::new(1, 16);
+    let mut black_pool = ThreadPool::new(1, 16);
+    white_pool.silent = true;
+    black_pool.silent = true;
+    let mut played = Vec::new();
+
+    loop {
+        let result = adjudicate(&mut board);
+        if result != GameResult::Ongoing || played.len() >= max_plies {
+            return (result, played);
+        }
+
+        let (pool, tc) = if board.side_to_move == Color::White {
+            (&white_pool, white)
+        } else {
+            (&black_pool, black)
+        };
+        let limit = if tc.movetime > 0 {
+            tc.movetime as u128
+        } else {
+            u128::MAX
+        };
+
+        let (_, best_move) = pool.search(&mut board, tc.depth, limit, limit);
+        match best_move {
+            Some(m) => {
+                board.make_move(m);
+                played.push(m);
+            }
+            None => return (GameResult::Ongoing, played),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen;
+
+    #[test]
+    fn plays_a_few_plies_from_startpos() {
+        movegen::init();
+        let board =
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let (result, played) =
+            play_game(board, TimeControl::depth(2), TimeControl::depth(2), 4);
+        assert_eq!(result, GameResult::Ongoing);
+        assert_eq!(played.len(), 4);
+    }
+
+    #[test]
+    fn terminates_on_mate_in_one() {
+        movegen::init();
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let (result, played) =
+            play_game(board, TimeControl::depth(3), TimeControl::depth(3), 20);
+        assert_eq!(result, GameResult::WhiteWins);
+        assert_eq!(played.len(), 1);
+    }
+
+    #[test]
+    fn adjudicates_terminal_positions() {
+        movegen::init();
+        let mut mated = Board::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 1 1").unwrap();
+        assert_eq!(adjudicate(&mut mated), GameResult::WhiteWins);
+
+        let mut stalemate = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(adjudicate(&mut stalemate), GameResult::Draw(DrawReason::Stalemate));
+
+        let mut bare = Board::from_fen("8/8/4k3/8/8/3K4/8/8 w - - 0 1").unwrap();
+        assert_eq!(
+            adjudicate(&mut bare),
+            GameResult::Draw(DrawReason::InsufficientMaterial)
+        );
+    }
+}
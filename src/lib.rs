@@ -12,4 +12,9 @@ pub mod polygot_keys;
 pub mod nnue;
 pub mod syzygy;
 pub mod see;
-pub mod thread;
\ No newline at end of file
+pub mod movepick;
+pub mod thread;
+pub mod game;
+pub mod engine;
+pub mod score;
+pub mod pgn;
\ No newline at end of file
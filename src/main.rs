@@ -1,13 +1,16 @@
 use chess_engine::{
     board::Board,
     movegen,
+    engine,
     moves::{self, Move},
     nnue,
+    score::Score,
     thread::ThreadPool,
     types::PieceType,
     uci,
 };
 use std::env;
+use std::fs;
 use std::time::Instant;
 
 const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
@@ -63,6 +66,20 @@ fn main() {
                 eprintln!("Usage: cargo run -- perft <depth> [optional_fen]");
             }
         }
+        "perft-divide" => {
+            if args.len() > 2 {
+                let depth = args[2].parse::<u8>().unwrap_or(1);
+                let mut board = if args.len() > 3 {
+                    Board::from_fen(&args[3]).expect("Invalid FEN")
+                } else {
+                    Board::from_fen(START_FEN).expect("Invalid Start FEN")
+                };
+
+                run_perft_divide(&mut board, depth);
+            } else {
+                eprintln!("Usage: cargo run -- perft-divide <depth> [optional_fen]");
+            }
+        }
         "search" => {
             // Usage: cargo run -- search <depth> [fen]
             if args.len() > 2 {
@@ -78,6 +95,16 @@ fn main() {
                 eprintln!("Usage: cargo run -- search <depth> [optional_fen]");
             }
         }
+        "epd" => {
+            // Usage: cargo run -- epd <file> [depth] [movetime_ms]
+            if args.len() > 2 {
+                let depth = args.get(3).and_then(|d| d.parse::<u8>().ok()).unwrap_or(8);
+                let movetime = args.get(4).and_then(|t| t.parse::<u128>().ok()).unwrap_or(0);
+                run_epd(&args[2], depth, movetime);
+            } else {
+                eprintln!("Usage: cargo run -- epd <file> [depth] [movetime_ms]");
+            }
+        }
         _ => print_help(),
     }
 }
@@ -104,6 +131,19 @@ fn run_perft(board: &mut Board, depth: u8) {
     println!("-----------------------------");
 }
 
+/// Prints one `move: nodes` line per root move, like Stockfish's `go perft`.
+fn run_perft_divide(board: &mut Board, depth: u8) {
+    let divide = board.perft_divide(depth);
+    let mut total = 0;
+    for (m, nodes) in divide {
+        println!("{}: {}", format_move(m), nodes);
+        total += nodes;
+    }
+
+    println!();
+    println!("Nodes searched: {}", total);
+}
+
 fn run_search(board: &mut Board, depth: u8) {
     println!("Searching depth {}...", depth);
     println!("{}", board);
@@ -140,12 +180,8 @@ fn run_search(board: &mut Board, depth: u8) {
     }
 
     // Pretty print score
-    if score > 30000 {
-        let moves_to_mate = (32000 - score + 1) / 2;
+    if let Some(moves_to_mate) = Score(score).mate_in() {
         println!("Score:     Mate in {}", moves_to_mate);
-    } else if score < -30000 {
-        let moves_to_mate = (32000 + score) / 2;
-        println!("Score:     Mate in -{}", moves_to_mate);
     } else {
         println!("Score:     {:.2}", score as f32 / 100.0);
     }
@@ -157,6 +193,84 @@ fn run_search(board: &mut Board, depth: u8) {
     println!("-----------------------------");
 }
 
+/// Searches every EPD record in `path` and counts how often the engine's
+/// move is one of the `bm` moves. A `movetime` of 0 searches to `depth` only.
+fn run_epd(path: &str, depth: u8, movetime: u128) {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!("Error: could not read {}: {}", path, e);
+            return;
+        }
+    };
+
+    let num_threads = std::thread::available_parallelism()
+        .map(|n| (n.get() / 2).max(1))
+        .unwrap_or(1);
+    let mut thread_pool = ThreadPool::new(num_threads, 128);
+    thread_pool.silent = true;
+
+    let limit = if movetime > 0 { movetime } else { u128::MAX };
+    let mut solved = 0;
+    let mut total = 0;
+    let start = Instant::now();
+
+    for (line_no, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (mut board, ops) = match Board::from_epd(line) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                eprintln!("line {}: {}", line_no + 1, e);
+                continue;
+            }
+        };
+        let id = ops.get("id").cloned().unwrap_or_else(|| format!("line {}", line_no + 1));
+        let Some(bm) = ops.get("bm") else {
+            eprintln!("{}: no bm operation, skipped", id);
+            continue;
+        };
+
+        // `bm` may list several moves, in SAN or coordinate notation
+        let best_moves: Vec<Move> = bm
+            .split_whitespace()
+            .filter_map(|b| moves::from_san(&board, b).or_else(|| engine::find_move(&board, b)))
+            .collect();
+        if best_moves.is_empty() {
+            eprintln!("{}: could not resolve bm \"{}\", skipped", id, bm);
+            continue;
+        }
+
+        thread_pool.clear();
+        let position_start = Instant::now();
+        let (_, best_move) = thread_pool.search(&mut board, depth, limit, limit);
+        let seconds = position_start.elapsed().as_secs_f64();
+
+        total += 1;
+        let found = best_move.is_some_and(|m| best_moves.contains(&m));
+        if found {
+            solved += 1;
+        }
+        let played = best_move.map_or("none".to_string(), |m| moves::to_san(&board, m));
+        println!(
+            "{:<16} {:<6} bm {:<12} got {:<8} {:.3} s",
+            id,
+            if found { "ok" } else { "FAIL" },
+            bm,
+            played,
+            seconds
+        );
+    }
+
+    println!("-----------------------------");
+    println!("Solved:    {} / {}", solved, total);
+    println!("Time:      {:.3} s", start.elapsed().as_secs_f64());
+    println!("-----------------------------");
+}
+
 fn format_move(m: Move) -> String {
     let from = moves::from_sq(m);
     let to = moves::to_sq(m);
@@ -192,6 +306,8 @@ fn print_help() {
     println!("Commands:");
     println!("  --fen \"<FEN>\"          : Print board from FEN");
     println!("  perft <depth>          : Run perft on starting position");
+    println!("  perft-divide <depth>   : Perft node counts per root move");
     println!("  search <depth>         : Run alpha-beta search");
     println!("  search <depth> \"<FEN>\" : Run search on specific position");
+    println!("  epd <file> [depth] [ms]: Score a test suite by its bm moves");
 }
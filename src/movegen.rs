@@ -156,8 +156,116 @@ pub fn is_square_attacked(board: &Board, sq: Square, attacker_color: Color) -> b
     false
 }
 
+/// Squares strictly between `a` and `b` if they share a rank, file or
+/// diagonal, otherwise empty. The two rays that meet give exactly that set.
+#[inline(always)]
+pub fn between(a: Square, b: Square) -> Bitboard {
+    let (a_bb, b_bb) = (1u64 << a, 1u64 << b);
+    if get_rook_attacks(a, 0) & b_bb != 0 {
+        get_rook_attacks(a, b_bb) & get_rook_attacks(b, a_bb)
+    } else if get_bishop_attacks(a, 0) & b_bb != 0 {
+        get_bishop_attacks(a, b_bb) & get_bishop_attacks(b, a_bb)
+    } else {
+        0
+    }
+}
+
+/// Enemy pieces giving check to the king of color `us`.
+pub fn checkers(board: &Board, us: Color) -> Bitboard {
+    let them = if us == Color::White { Color::Black } else { Color::White };
+    let sq = board.king_sq[us as usize];
+    let occ = board.occupancy[2];
+    let theirs = |pt: PieceType| board.pieces[pt as usize][them as usize];
+
+    (pawn_attacks(us, sq) & theirs(PieceType::Pawn))
+        | (knight_attacks(sq) & theirs(PieceType::Knight))
+        | (get_bishop_attacks(sq, occ) & (theirs(PieceType::Bishop) | theirs(PieceType::Queen)))
+        | (get_rook_attacks(sq, occ) & (theirs(PieceType::Rook) | theirs(PieceType::Queen)))
+}
+
+/// Pieces of color `us` pinned against their own king by an enemy slider.
+pub fn pinned_pieces(board: &Board, us: Color) -> Bitboard {
+    let them = if us == Color::White { Color::Black } else { Color::White };
+    let king_sq = board.king_sq[us as usize];
+    let occ = board.occupancy[2];
+    let queens = board.pieces[PieceType::Queen as usize][them as usize];
+
+    let rook_snipers = get_rook_attacks(king_sq, 0)
+        & (board.pieces[PieceType::Rook as usize][them as usize] | queens);
+    let bishop_snipers = get_bishop_attacks(king_sq, 0)
+        & (board.pieces[PieceType::Bishop as usize][them as usize] | queens);
+
+    let mut pinned = 0;
+    let mut snipers = rook_snipers | bishop_snipers;
+    while snipers != 0 {
+        let sq = snipers.trailing_zeros() as Square;
+        snipers &= snipers - 1;
+
+        let blockers = between(king_sq, sq) & occ;
+        if blockers.count_ones() == 1 {
+            pinned |= blockers & board.occupancy[us as usize];
+        }
+    }
+    pinned
+}
+
+/// True when `m` might leave the mover's king attacked and so must be
+/// verified after `make_move`. Moves of unpinned non-king pieces other than
+/// en passant are always legal when not in check.
+#[inline(always)]
+pub fn needs_legality_check(board: &Board, m: moves::Move, pinned: Bitboard, in_check: bool) -> bool {
+    let from = moves::from_sq(m);
+    in_check
+        || from == board.king_sq[board.side_to_move as usize]
+        || moves::flag(m) == moves::EN_PASSANT_CAPTURE_FLAG
+        || pinned & (1u64 << from) != 0
+}
+
 // --- Move Generation ---
 
+/// Generates only legal moves. Checkers and pins are found up front, so
+/// other pieces are filtered by masks: when in check they must capture or
+/// block the single checker, and a pinned piece must stay on its pin ray.
+/// King moves and en passant are still verified with make/unmake.
+pub fn generate_legal_moves(board: &mut Board, list: &mut MoveList) {
+    let us = board.side_to_move;
+    let them = if us == Color::White { Color::Black } else { Color::White };
+    let king_sq = board.king_sq[us as usize];
+    let checkers = checkers(board, us);
+    let pinned = pinned_pieces(board, us);
+
+    // Destinations that resolve check; nothing but the king may move in double check
+    let target = match checkers.count_ones() {
+        0 => !0,
+        1 => checkers | between(king_sq, checkers.trailing_zeros() as Square),
+        _ => 0,
+    };
+
+    let mut pseudo = MoveList::new();
+    generate_pseudo_legal_moves(board, &mut pseudo);
+
+    for &m in pseudo.iter() {
+        let from = moves::from_sq(m);
+        let to = moves::to_sq(m);
+
+        let legal = if from == king_sq || moves::flag(m) == moves::EN_PASSANT_CAPTURE_FLAG {
+            let undo = board.make_move(m);
+            let safe = !is_square_attacked(board, board.king_sq[us as usize], them);
+            board.unmake_move(m, undo);
+            safe
+        } else {
+            target & (1u64 << to) != 0
+                && (pinned & (1u64 << from) == 0
+                    || between(king_sq, to) & (1u64 << from) != 0
+                    || between(king_sq, from) & (1u64 << to) != 0)
+        };
+
+        if legal {
+            list.push(m);
+        }
+    }
+}
+
 pub fn generate_pseudo_legal_moves(board: &Board, list: &mut MoveList) {
     generate_pawn_moves(board, list);
     generate_knight_moves(board, list);
@@ -172,6 +280,83 @@ pub fn generate_captures(board: &Board, list: &mut MoveList) {
     generate_sliding_captures(board, list);
 }
 
+/// Generates every pseudo-legal non-capture, including castling.
+/// Quiet promotions are emitted here (all four pieces) because
+/// `generate_captures` only covers capture-promotions, so the two
+/// generators together produce exactly `generate_pseudo_legal_moves`.
+pub fn generate_quiets(board: &Board, list: &mut MoveList) {
+    generate_pawn_quiets(board, list);
+    generate_knight_quiets(board, list);
+    generate_king_quiets(board, list);
+    generate_sliding_quiets(board, list);
+}
+
+/// True if `generate_pseudo_legal_moves` would produce `m` here, decided
+/// without generating the other moves. Castling still goes through the
+/// generator for its path rules.
+pub fn is_pseudo_legal(board: &Board, m: moves::Move) -> bool {
+    let us = board.side_to_move;
+    let from = moves::from_sq(m);
+    let to = moves::to_sq(m);
+    let flag = moves::flag(m);
+    let to_bb = 1u64 << to;
+    let occ = board.occupancy[2];
+    let their_pieces = board.occupancy[if us == Color::White { 1 } else { 0 }];
+
+    if board.occupancy[us as usize] & (1u64 << from) == 0 {
+        return false;
+    }
+    let Some(pt) = board.piece_type_on(from) else {
+        return false;
+    };
+
+    if pt == PieceType::Pawn {
+        let (up, rank_start, rank_promo) = if us == Color::White {
+            (8i8, 0xFF00u64, 0xFF000000000000u64)
+        } else {
+            (-8i8, 0xFF000000000000u64, 0xFF00u64)
+        };
+        let from_bb = 1u64 << from;
+        if moves::is_promotion(m) != (from_bb & rank_promo != 0) {
+            return false;
+        }
+        let push = from as i8 + up;
+        return match flag {
+            moves::KING_CASTLE_FLAG | moves::QUEEN_CASTLE_FLAG | 0b0110 | 0b0111 => false,
+            moves::DOUBLE_PAWN_PUSH_FLAG => {
+                from_bb & rank_start != 0
+                    && to as i8 == push + up
+                    && occ & ((1u64 << push) | to_bb) == 0
+            }
+            moves::EN_PASSANT_CAPTURE_FLAG => {
+                board.en_passant == Some(to) && PAWN_ATTACKS[us as usize][from as usize] & to_bb != 0
+            }
+            _ if moves::is_capture(m) => PAWN_ATTACKS[us as usize][from as usize] & to_bb & their_pieces != 0,
+            _ => to as i8 == push && occ & to_bb == 0,
+        };
+    }
+
+    let target_ok = match flag {
+        moves::QUIET_MOVE_FLAG => occ & to_bb == 0,
+        moves::CAPTURE_FLAG => their_pieces & to_bb != 0,
+        moves::KING_CASTLE_FLAG | moves::QUEEN_CASTLE_FLAG if pt == PieceType::King => {
+            let mut castles = MoveList::new();
+            generate_castling_moves(board, &mut castles);
+            return castles.iter().any(|&c| c == m);
+        }
+        _ => false,
+    };
+    let attacks = match pt {
+        PieceType::Knight => KNIGHT_ATTACKS[from as usize],
+        PieceType::King => KING_ATTACKS[from as usize],
+        PieceType::Bishop => get_bishop_attacks(from, occ),
+        PieceType::Rook => get_rook_attacks(from, occ),
+        PieceType::Queen => get_bishop_attacks(from, occ) | get_rook_attacks(from, occ),
+        PieceType::Pawn => 0,
+    };
+    target_ok && attacks & to_bb != 0
+}
+
 fn generate_sliding_moves(board: &Board, list: &mut MoveList) {
     let us = board.side_to_move;
     let occ = board.occupancy[2];
@@ -221,6 +406,29 @@ fn generate_sliding_captures(board: &Board, list: &mut MoveList) {
     }
 }
 
+fn generate_sliding_quiets(board: &Board, list: &mut MoveList) {
+    let us = board.side_to_move;
+    let occ = board.occupancy[2];
+
+    let mut bishops = board.pieces[PieceType::Bishop as usize][us as usize]
+        | board.pieces[PieceType::Queen as usize][us as usize];
+    while bishops != 0 {
+        let from_sq = bishops.trailing_zeros() as Square;
+        let attacks = get_bishop_attacks(from_sq, occ) & !occ;
+        add_sliding_quiets(from_sq, attacks, list);
+        bishops &= bishops - 1;
+    }
+
+    let mut rooks = board.pieces[PieceType::Rook as usize][us as usize]
+        | board.pieces[PieceType::Queen as usize][us as usize];
+    while rooks != 0 {
+        let from_sq = rooks.trailing_zeros() as Square;
+        let attacks = get_rook_attacks(from_sq, occ) & !occ;
+        add_sliding_quiets(from_sq, attacks, list);
+        rooks &= rooks - 1;
+    }
+}
+
 fn add_sliding_moves(
     from_sq: Square,
     moves: Bitboard,
@@ -250,6 +458,14 @@ fn add_sliding_captures(from_sq: Square, mut captures: Bitboard, list: &mut Move
     }
 }
 
+fn add_sliding_quiets(from_sq: Square, mut quiets: Bitboard, list: &mut MoveList) {
+    while quiets != 0 {
+        let to_sq = quiets.trailing_zeros() as Square;
+        list.push(moves::new(from_sq, to_sq, moves::QUIET_MOVE_FLAG));
+        quiets &= quiets - 1;
+    }
+}
+
 fn generate_pawn_moves(board: &Board, list: &mut MoveList) {
   let us = board.side_to_move;
   let them = if us == Color::White { Color::Black } else { Color::White };
@@ -357,7 +573,6 @@ fn generate_king_moves(board: &Board, list: &mut MoveList) {
   let us = board.side_to_move;
   let them = if us == Color::White { Color::Black } else { Color::White };
   let our_pieces = board.occupancy[us as usize];
-  let all_pieces = board.occupancy[2];
 
   let king_sq = board.pieces[PieceType::King as usize][us as usize].trailing_zeros() as Square;
 
@@ -374,48 +589,59 @@ fn generate_king_moves(board: &Board, list: &mut MoveList) {
     attacks &= attacks - 1;
   }
 
-  // Castling
-  if is_square_attacked(board, king_sq, them) {
-    return;
-  }
+  generate_castling_moves(board, list);
+}
 
-  if us == Color::White {
-    // Kingside
-    if (board.castling_rights & 0b0001) != 0 
-       && (all_pieces & 0x60) == 0  // f1 g1
-       && !is_square_attacked(board,5, them)  
-       && !is_square_attacked(board,6, them)  
-    {
-        list.push(moves::new(4, 6, moves::KING_CASTLE_FLAG));
-    }
-    // Queenside
-    if (board.castling_rights & 0b0010) != 0 
-       && (all_pieces & 0xE) == 0  // d1 c1 b1
-       && !is_square_attacked(board, 3, them)  
-       && !is_square_attacked(board, 2, them)  
-    {
-        list.push(moves::new(4, 2, moves::QUEEN_CASTLE_FLAG));
+fn generate_castling_moves(board: &Board, list: &mut MoveList) {
+  let us = board.side_to_move;
+  let them = if us == Color::White { Color::Black } else { Color::White };
+  let king_sq = board.pieces[PieceType::King as usize][us as usize].trailing_zeros() as Square;
+  let rooks = board.pieces[PieceType::Rook as usize][us as usize];
+  let base = if us == Color::White { 0 } else { 56 };
+
+  // Rights are cleared once the king or rook leaves its start square, so
+  // only the path needs checking. The king always lands on the g/c-file and
+  // the rook beside it, which also covers Chess960.
+  for (flag, king_to) in [(moves::KING_CASTLE_FLAG, base + 6), (moves::QUEEN_CASTLE_FLAG, base + 2)] {
+    let right = us as usize * 2 + (flag == moves::QUEEN_CASTLE_FLAG) as usize;
+    if board.castling_rights & (1 << right) == 0 {
+      continue;
     }
-  } else {
-    // Black kingside
-    if (board.castling_rights & 0b0100) != 0 
-       && (all_pieces & 0x6000000000000000) == 0
-       && !is_square_attacked(board, 61, them) 
-       && !is_square_attacked(board, 62, them)
-    {
-        list.push(moves::new(60, 62, moves::KING_CASTLE_FLAG));
-    }
-    // Black queenside
-    if (board.castling_rights & 0b1000) != 0 
-       && (all_pieces & 0xE00000000000000) == 0
-       && !is_square_attacked(board, 59, them) 
-       && !is_square_attacked(board, 58, them)
-    {
-        list.push(moves::new(60, 58, moves::QUEEN_CASTLE_FLAG));
+
+    let (rook_from, rook_to) = board.castle_rook_squares(us, flag);
+    if rooks & (1u64 << rook_from) == 0 {
+      continue;
+    }
+
+    // Everything either piece crosses must be empty, bar the two of them
+    let movers = (1u64 << king_sq) | (1u64 << rook_from);
+    let king_path = rank_span(king_sq, king_to);
+    if board.occupancy[2] & (king_path | rank_span(rook_from, rook_to)) & !movers != 0 {
+      continue;
+    }
+
+    // No castling out of, through or into check
+    let mut path = king_path;
+    let mut safe = true;
+    while path != 0 {
+      if is_square_attacked(board, path.trailing_zeros() as Square, them) {
+        safe = false;
+        break;
+      }
+      path &= path - 1;
+    }
+    if safe {
+      list.push(moves::new(king_sq, king_to, flag));
     }
   }
 }
 
+/// Squares from `a` to `b` inclusive, both on the same rank.
+fn rank_span(a: Square, b: Square) -> Bitboard {
+  let (lo, hi) = (a.min(b), a.max(b));
+  (u64::MAX >> (63 - hi)) & (u64::MAX << lo)
+}
+
 fn generate_pawn_captures(board: &Board, list: &mut MoveList) {
   let us = board.side_to_move;
   let them = if us == Color::White { Color::Black } else { Color::White };
@@ -476,6 +702,82 @@ fn generate_knight_captures(board: &Board, list: &mut MoveList) {
   }
 }
 
+fn generate_pawn_quiets(board: &Board, list: &mut MoveList) {
+  let us = board.side_to_move;
+  let our_pawns = board.pieces[PieceType::Pawn as usize][us as usize];
+  let all_pieces = board.occupancy[2];
+
+  let (up, rank_start, rank_promo) = if us == Color::White {
+    (8i8, 0xFF00u64, 0xFF000000000000u64)
+  } else {
+    (-8i8, 0xFF000000000000u64, 0xFF00u64)
+  };
+
+  let mut pawns = our_pawns;
+  while pawns != 0 {
+    let from_sq = pawns.trailing_zeros() as Square;
+    let from_bb = 1 << from_sq;
+    let to_sq_i8 = from_sq as i8 + up;
+    if !(0..64).contains(&to_sq_i8) {
+      pawns &= pawns - 1;
+      continue;
+    }
+    let to_sq = to_sq_i8 as Square;
+
+    if (1 << to_sq) & all_pieces == 0 {
+      if (from_bb & rank_promo) != 0 {
+        list.push(moves::new(from_sq, to_sq, moves::QUEEN_PROMOTION_FLAG));
+        list.push(moves::new(from_sq, to_sq, moves::ROOK_PROMOTION_FLAG));
+        list.push(moves::new(from_sq, to_sq, moves::BISHOP_PROMOTION_FLAG));
+        list.push(moves::new(from_sq, to_sq, moves::KNIGHT_PROMOTION_FLAG));
+      } else {
+        list.push(moves::new(from_sq, to_sq, moves::QUIET_MOVE_FLAG));
+      }
+
+      if (from_bb & rank_start) != 0 {
+        let to_sq_double = (to_sq_i8 + up) as Square;
+        if (1 << to_sq_double) & all_pieces == 0 {
+          list.push(moves::new(from_sq, to_sq_double, moves::DOUBLE_PAWN_PUSH_FLAG));
+        }
+      }
+    }
+
+    pawns &= pawns - 1;
+  }
+}
+
+fn generate_knight_quiets(board: &Board, list: &mut MoveList) {
+  let us = board.side_to_move;
+  let all_pieces = board.occupancy[2];
+  let mut knights = board.pieces[PieceType::Knight as usize][us as usize];
+
+  while knights != 0 {
+    let from_sq = knights.trailing_zeros() as Square;
+    let mut quiet_moves = KNIGHT_ATTACKS[from_sq as usize] & !all_pieces;
+    while quiet_moves != 0 {
+      let to_sq = quiet_moves.trailing_zeros() as Square;
+      list.push(moves::new(from_sq, to_sq, moves::QUIET_MOVE_FLAG));
+      quiet_moves &= quiet_moves - 1;
+    }
+    knights &= knights - 1;
+  }
+}
+
+fn generate_king_quiets(board: &Board, list: &mut MoveList) {
+  let us = board.side_to_move;
+  let all_pieces = board.occupancy[2];
+  let king_sq = board.pieces[PieceType::King as usize][us as usize].trailing_zeros() as Square;
+
+  let mut quiets = KING_ATTACKS[king_sq as usize] & !all_pieces;
+  while quiets != 0 {
+    let to_sq = quiets.trailing_zeros() as Square;
+    list.push(moves::new(king_sq, to_sq, moves::QUIET_MOVE_FLAG));
+    quiets &= quiets - 1;
+  }
+
+  generate_castling_moves(board, list);
+}
+
 fn generate_king_captures(board: &Board, list: &mut MoveList) {
   let us = board.side_to_move;
   let their_pieces = board.occupancy[if us == Color::White { 1 } else { 0 }];
@@ -817,4 +1119,200 @@ static ROOK_MAGIC_NUMBERS: [u64; 64] = [
     0x192001001044802,
     0x1089000400860001,
     0x4100089020c201,
-];
\ No newline at end of file
+];
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_and_quiets_partition_pseudo_legal_moves() {
+        init();
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+
+            let mut all = MoveList::new();
+            generate_pseudo_legal_moves(&board, &mut all);
+            let mut split = MoveList::new();
+            generate_captures(&board, &mut split);
+            generate_quiets(&board, &mut split);
+
+            let mut expected: Vec<_> = all.iter().copied().collect();
+            let mut actual: Vec<_> = split.iter().copied().collect();
+            expected.sort();
+            actual.sort();
+            assert_eq!(expected, actual, "move sets differ for {}", fen);
+        }
+    }
+
+    #[test]
+    fn the_richest_known_position_fits_a_move_list() {
+        init();
+        let mut board = Board::from_fen("R6R/3Q4/1Q4Q1/4Q3/2Q4Q/Q4Q2/pp1Q4/kBNN1KB1 w - - 0 1").unwrap();
+
+        let mut legal = MoveList::new();
+        generate_legal_moves(&mut board, &mut legal);
+        assert_eq!(legal.len(), 218);
+
+        let mut all = MoveList::new();
+        generate_pseudo_legal_moves(&board, &mut all);
+        assert!(all.len() >= 218 && all.len() <= moves::MAX_MOVES);
+    }
+
+    #[test]
+    fn is_pseudo_legal_matches_the_generator() {
+        init();
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "4k3/2p5/8/3pP3/8/8/8/4K3 w - d6 0 2",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 0 1",
+            // Chess960: king on f1, rooks on b1 and h1
+            "1r3kr1/pppppppp/8/8/8/8/PPPPPPPP/1R3KR1 w KQkq - 0 1",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+            let mut list = MoveList::new();
+            generate_pseudo_legal_moves(&board, &mut list);
+            for m in 0..=u16::MAX {
+                let generated = list.iter().any(|&g| g == m);
+                assert_eq!(is_pseudo_legal(&board, m), generated, "{} in {}", moves::format(m), fen);
+            }
+        }
+    }
+
+    #[test]
+    fn no_castling_with_displaced_king() {
+        init();
+        // A king elsewhere on the back rank is a Chess960 start, so these
+        // kings are off it entirely
+        let fens = [
+            "4k3/8/8/8/8/8/3K4/R6R w KQ - 0 1",
+            "r6r/5k2/8/8/8/8/8/4K3 b kq - 0 1",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+            let mut list = MoveList::new();
+            generate_pseudo_legal_moves(&board, &mut list);
+            let castles = list.iter().filter(|&&m| {
+                matches!(moves::flag(m), moves::KING_CASTLE_FLAG | moves::QUEEN_CASTLE_FLAG)
+            });
+            assert_eq!(castles.count(), 0, "castle generated for {}", fen);
+        }
+    }
+
+    fn perft_with_pins(board: &mut Board, depth: u8) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let us = board.side_to_move;
+        let them = if us == Color::White { Color::Black } else { Color::White };
+        let in_check = is_square_attacked(board, board.king_sq[us as usize], them);
+        let pinned = pinned_pieces(board, us);
+
+        let mut list = MoveList::new();
+        generate_pseudo_legal_moves(board, &mut list);
+
+        let mut nodes = 0;
+        for &m in list.iter() {
+            let check = needs_legality_check(board, m, pinned, in_check);
+            let undo = board.make_move(m);
+            let legal = !is_square_attacked(board, board.king_sq[us as usize], them);
+            if !check {
+                assert!(legal, "fast path accepted illegal move {}", moves::format(m));
+            }
+            if legal {
+                nodes += perft_with_pins(board, depth - 1);
+            }
+            board.unmake_move(m, undo);
+        }
+        nodes
+    }
+
+    #[test]
+    fn pin_fast_path_matches_perft() {
+        init();
+        let cases = [
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 3, 97862),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4, 43238),
+            ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", 3, 9467),
+            ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 3, 62379),
+        ];
+        for (fen, depth, expected) in cases {
+            let mut board = Board::from_fen(fen).unwrap();
+            assert_eq!(perft_with_pins(&mut board, depth), expected, "{}", fen);
+        }
+    }
+
+    fn check_legal_generator(board: &mut Board, depth: u8) {
+        let us = board.side_to_move;
+        let them = if us == Color::White { Color::Black } else { Color::White };
+
+        let mut pseudo = MoveList::new();
+        generate_pseudo_legal_moves(board, &mut pseudo);
+        let mut expected = Vec::new();
+        for &m in pseudo.iter() {
+            let undo = board.make_move(m);
+            if !is_square_attacked(board, board.king_sq[us as usize], them) {
+                expected.push(m);
+            }
+            board.unmake_move(m, undo);
+        }
+
+        let mut legal = MoveList::new();
+        generate_legal_moves(board, &mut legal);
+        let got: Vec<_> = legal.iter().copied().collect();
+        assert_eq!(got, expected, "{}", board.to_fen());
+
+        if depth > 1 {
+            for m in got {
+                let undo = board.make_move(m);
+                check_legal_generator(board, depth - 1);
+                board.unmake_move(m, undo);
+            }
+        }
+    }
+
+    #[test]
+    fn legal_generator_matches_filtered_pseudo_legal() {
+        init();
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ];
+        for fen in fens {
+            let mut board = Board::from_fen(fen).unwrap();
+            check_legal_generator(&mut board, 3);
+        }
+    }
+
+    #[test]
+    fn checkers_finds_double_check() {
+        init();
+        let board = Board::from_fen("4k3/8/8/1B6/8/8/8/4R1K1 b - - 0 1").unwrap();
+        assert_eq!(checkers(&board, Color::Black), (1u64 << 33) | (1u64 << 4));
+        assert_eq!(between(4, 60).count_ones(), 6);
+        assert_eq!(between(4, 13), 0);
+    }
+
+    #[test]
+    fn pinned_pieces_on_file_and_diagonal() {
+        init();
+        let board = Board::from_fen("k3r3/8/8/8/1b6/8/3N4/4K3 w - - 0 1").unwrap();
+        assert_eq!(pinned_pieces(&board, Color::White), 1u64 << 11);
+
+        let board = Board::from_fen("k3r3/8/8/8/1b6/4N3/3N4/4K3 w - - 0 1").unwrap();
+        assert_eq!(pinned_pieces(&board, Color::White), (1u64 << 11) | (1u64 << 20));
+    }
+}
@@ -0,0 +1,247 @@
+use std::ops::Range;
+
+use crate::board::Board;
+use crate::movegen;
+use crate::moves::{self, MAX_MOVES, Move, MoveList};
+use crate::see;
+use crate::types::PieceType;
+
+// Captures that hold material by SEE are scored from GOOD_CAPTURE up, the
+// rest from BAD_CAPTURE, below any history score.
+const GOOD_CAPTURE: i32 = 1_000_000;
+const BAD_CAPTURE: i32 = -100_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stage {
+    TtMove,
+    GoodCaptures,
+    Refutations,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+/// Hands out the pseudo-legal moves of a node in stages: the TT move,
+/// captures that do not lose material, the killers and counter move, quiet
+/// moves by history, then the losing captures. Captures and quiets are only
+/// generated once a stage needs them, so a cutoff by the TT move or a good
+/// capture never pays for the quiets.
+pub struct MovePicker {
+    stage: Stage,
+    tt_move: Option<Move>,
+    /// Killers, then the counter move.
+    refutations: [Option<Move>; 3],
+    next_refutation: usize,
+    /// Captures and quiets share one list, each in the range it was
+    /// generated into.
+    moves: MoveList,
+    scores: [i32; MAX_MOVES],
+    captures: Option<Range<usize>>,
+    next_capture: usize,
+    quiets: Option<Range<usize>>,
+    next_quiet: usize,
+}
+
+impl MovePicker {
+    pub fn new(tt_move: Option<Move>, killers: [Option<Move>; 2], counter: Option<Move>) -> Self {
+        Self {
+            stage: Stage::TtMove,
+            tt_move,
+            refutations: [killers[0], killers[1], counter],
+            next_refutation: 0,
+            moves: MoveList::new(),
+            scores: [0; MAX_MOVES],
+            captures: None,
+            next_capture: 0,
+            quiets: None,
+            next_quiet: 0,
+        }
+    }
+
+    /// The next move to search, or `None` once every move has been given.
+    /// `quiet_score` orders the quiet moves, highest first.
+    pub fn next(&mut self, board: &Board, quiet_score: impl Fn(Move) -> i32) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::TtMove => {
+                    self.stage = Stage::GoodCaptures;
+                    // A TT move from a hash collision may not be playable here
+                    if let Some(m) = self.tt_move
+                        && board.is_pseudo_legal(m)
+                    {
+                        return Some(m);
+                    }
+                }
+                Stage::GoodCaptures => {
+                    let end = self.generate_captures(board).end;
+                    while self.next_capture < end {
+                        let i = self.next_capture;
+                        self.pick_best(i, end);
+                        if self.scores[i] < GOOD_CAPTURE {
+                            break;
+                        }
+                        self.next_capture += 1;
+                        let m = self.moves.get(i);
+                        if Some(m) != self.tt_move {
+                            return Some(m);
+                        }
+                    }
+                    self.stage = Stage::Refutations;
+                }
+                Stage::Refutations => {
+                    while self.next_refutation < self.refutations.len() {
+                        let i = self.next_refutation;
+                        self.next_refutation += 1;
+                        let Some(m) = self.refutations[i] else {
+                            continue;
+                        };
+                        if Some(m) != self.tt_move
+                            && !moves::is_capture(m)
+                            && !self.refutations[..i].contains(&Some(m))
+                            && board.is_pseudo_legal(m)
+                        {
+                            return Some(m);
+                        }
+                    }
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => {
+                    let end = self.generate_quiets(board, &quiet_score).end;
+                    while self.next_quiet < end {
+                        let i = self.next_quiet;
+                        self.pick_best(i, end);
+                        self.next_quiet += 1;
+                        let m = self.moves.get(i);
+                        if Some(m) != self.tt_move && !self.refutations.contains(&Some(m)) {
+                            return Some(m);
+                        }
+                    }
+                    self.stage = Stage::BadCaptures;
+                }
+                Stage::BadCaptures => {
+                    let end = self.captures.as_ref().map_or(0, |r| r.end);
+                    while self.next_capture < end {
+                        let i = self.next_capture;
+                        self.pick_best(i, end);
+                        self.next_capture += 1;
+                        let m = self.moves.get(i);
+                        if Some(m) != self.tt_move {
+                            return Some(m);
+                        }
+                    }
+                    self.stage = Stage::Done;
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    fn generate_captures(&mut self, board: &Board) -> Range<usize> {
+        if let Some(range) = &self.captures {
+            return range.clone();
+        }
+        let start = self.moves.len();
+        movegen::generate_captures(board, &mut self.moves);
+        for i in start..self.moves.len() {
+            let m = self.moves.get(i);
+            let bucket = if see::see_ge(board, m, 0) { GOOD_CAPTURE } else { BAD_CAPTURE };
+            self.scores[i] = bucket + mvv_lva(board, m);
+        }
+        self.next_capture = start;
+        self.captures = Some(start..self.moves.len());
+        start..self.moves.len()
+    }
+
+    fn generate_quiets(&mut self, board: &Board, quiet_score: &impl Fn(Move) -> i32) -> Range<usize> {
+        if let Some(range) = &self.quiets {
+            return range.clone();
+        }
+        let start = self.moves.len();
+        movegen::generate_quiets(board, &mut self.moves);
+        for i in start..self.moves.len() {
+            self.scores[i] = quiet_score(self.moves.get(i));
+        }
+        self.next_quiet = start;
+        self.quiets = Some(start..self.moves.len());
+        start..self.moves.len()
+    }
+
+    /// Swaps the highest scored move in `start..end` into `start`.
+    fn pick_best(&mut self, start: usize, end: usize) {
+        let mut best = start;
+        for j in start + 1..end {
+            if self.scores[j] > self.scores[best] {
+                best = j;
+            }
+        }
+        self.moves.as_mut_slice().swap(start, best);
+        self.scores.swap(start, best);
+    }
+}
+
+/// Most valuable victim, least valuable attacker: orders captures among
+/// themselves, always positive.
+pub fn mvv_lva(board: &Board, m: Move) -> i32 {
+    let value = |pt: PieceType| match pt {
+        PieceType::Pawn => 1,
+        PieceType::Knight => 2,
+        PieceType::Bishop => 3,
+        PieceType::Rook => 4,
+        PieceType::Queen => 5,
+        PieceType::King => 6,
+    };
+    let victim = board.piece_type_on(moves::to_sq(m)).unwrap_or(PieceType::Pawn);
+    let attacker = board.piece_type_on(moves::from_sq(m)).unwrap();
+    10 * value(victim) - value(attacker) + 10000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picked(board: &Board, tt_move: Option<Move>, killers: [Option<Move>; 2]) -> Vec<Move> {
+        let mut picker = MovePicker::new(tt_move, killers, None);
+        let mut picked = Vec::new();
+        while let Some(m) = picker.next(board, |_| 0) {
+            picked.push(m);
+        }
+        picked
+    }
+
+    #[test]
+    fn yields_every_move_once() {
+        movegen::init();
+        let kiwi = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let board = Board::from_fen(kiwi).unwrap();
+        let mut all = MoveList::new();
+        board.generate_pseudo_legal_moves(&mut all);
+        let mut expected: Vec<Move> = all.iter().copied().collect();
+        expected.sort_unstable();
+
+        // Be2 to e1, where our king stands: not a move here
+        let quiet = moves::new(12, 4, moves::QUIET_MOVE_FLAG);
+        let castle = expected.iter().copied().find(|&m| moves::format(m) == "e1g1").unwrap();
+        for (tt_move, killers) in [
+            (None, [None, None]),
+            (Some(castle), [Some(castle), Some(quiet)]),
+            (Some(quiet), [None, Some(castle)]),
+        ] {
+            let mut got = picked(&board, tt_move, killers);
+            if let Some(tt) = tt_move.filter(|m| expected.contains(m)) {
+                assert_eq!(got[0], tt);
+            }
+            got.sort_unstable();
+            assert_eq!(got, expected);
+        }
+    }
+
+    #[test]
+    fn losing_captures_come_last() {
+        movegen::init();
+        // Qxd5 loses the queen for a pawn, exd5 wins it cleanly
+        let board = Board::from_fen("4k3/8/4p3/3p4/4P3/8/3Q4/4K3 w - - 0 1").unwrap();
+        let got: Vec<String> = picked(&board, None, [None, None]).into_iter().map(moves::format).collect();
+        assert_eq!(got[0], "e4d5");
+        assert_eq!(got.last().map(String::as_str), Some("d2d5"));
+    }
+}
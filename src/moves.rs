@@ -1,5 +1,7 @@
 use std::fmt;
-use crate::types::{PieceType, Square};
+use crate::board::Board;
+use crate::movegen;
+use crate::types::{Color, PieceType, Square};
 /* 
   Bits 0-5 from square (64 squares) 
   Bits 6-11 to square (64 squares)
@@ -64,19 +66,28 @@ pub fn promotion_piece(m: Move) -> PieceType {
   }
 }
 
+/// Capacity of a `MoveList`. The richest known legal position has 218
+/// moves, so generation from a real game never comes close.
+pub const MAX_MOVES: usize = 256;
+
 pub struct MoveList {
-  moves: [Move; 256],
+  moves: [Move; MAX_MOVES],
   count: usize,
 }
 
 impl MoveList {
   pub fn new() -> Self {
-    MoveList { moves: [0; 256], count: 0 }
+    MoveList { moves: [0; MAX_MOVES], count: 0 }
   }
 
+  /// Appends `m`. A full list drops it instead: only a made-up FEN piled
+  /// with queens can get there, and debug builds assert it.
   pub fn push(&mut self, m: Move) {
-    self.moves[self.count] = m;
-    self.count += 1; 
+    debug_assert!(self.count < MAX_MOVES, "more than {} moves", MAX_MOVES);
+    if self.count < MAX_MOVES {
+      self.moves[self.count] = m;
+      self.count += 1;
+    }
   }
 
   pub fn len(&self) -> usize {
@@ -127,8 +138,275 @@ pub fn format(m: Move) -> String {
   s
 }
 
+/// Like `format`, but with `chess960` (`UCI_Chess960`) castling is written
+/// as the king taking its own rook, e.g. "g1h1" for a king already on g1.
+pub fn format_uci(m: Move, board: &Board, chess960: bool) -> String {
+  let flag = flag(m);
+  if chess960 && (flag == KING_CASTLE_FLAG || flag == QUEEN_CASTLE_FLAG) {
+    let us = if from_sq(m) < 8 { Color::White } else { Color::Black };
+    let (rook_from, _) = board.castle_rook_squares(us, flag);
+    return format!("{}{}", format_square(from_sq(m)), format_square(rook_from));
+  }
+  format(m)
+}
+
+fn piece_letter(pt: PieceType) -> char {
+  match pt {
+    PieceType::Pawn => 'P',
+    PieceType::Knight => 'N',
+    PieceType::Bishop => 'B',
+    PieceType::Rook => 'R',
+    PieceType::Queen => 'Q',
+    PieceType::King => 'K',
+  }
+}
+
+/// Standard algebraic notation for the legal move `m` in `board`, e.g.
+/// `Nbd7`, `exd6`, `O-O-O` or `e8=Q+`. When another piece of the same kind
+/// can reach the square, the from-file is added, else the rank, else both.
+pub fn to_san(board: &Board, m: Move) -> String {
+  let mut board = board.clone_for_search();
+  let from = from_sq(m);
+  let to = to_sq(m);
+
+  let mut san = match flag(m) {
+    KING_CASTLE_FLAG => "O-O".to_string(),
+    QUEEN_CASTLE_FLAG => "O-O-O".to_string(),
+    _ => {
+      let piece = board.piece_type_on(from).unwrap_or(PieceType::Pawn);
+      let mut san = String::new();
+
+      if piece == PieceType::Pawn {
+        if is_capture(m) {
+          san.push((b'a' + from % 8) as char);
+        }
+      } else {
+        san.push(piece_letter(piece));
+
+        let mut legal = MoveList::new();
+        movegen::generate_legal_moves(&mut board, &mut legal);
+        let (mut ambiguous, mut same_file, mut same_rank) = (false, false, false);
+        for &other in legal.iter() {
+          let other_from = from_sq(other);
+          if to_sq(other) == to
+            && other_from != from
+            && board.piece_type_on(other_from) == Some(piece)
+          {
+            ambiguous = true;
+            same_file |= other_from % 8 == from % 8;
+            same_rank |= other_from / 8 == from / 8;
+          }
+        }
+
+        if ambiguous {
+          let square = format_square(from);
+          if !same_file {
+            san.push_str(&square[..1]);
+          } else if !same_rank {
+            san.push_str(&square[1..]);
+          } else {
+            san.push_str(&square);
+          }
+        }
+      }
+
+      if is_capture(m) {
+        san.push('x');
+      }
+      san.push_str(&format_square(to));
+      if is_promotion(m) {
+        san.push('=');
+        san.push(piece_letter(promotion_piece(m)));
+      }
+      san
+    }
+  };
+
+  board.make_move(m);
+  if board.in_check() {
+    san.push(if board.has_legal_move() { '+' } else { '#' });
+  }
+  san
+}
+
+/// Resolves a SAN move such as `Nbd7`, `exd6`, `O-O-O` or `e8=Q+` against
+/// the legal moves of `board`. Trailing `+`, `#`, `!` and `?` are ignored
+/// and `0-0` is accepted for castling. Returns `None` when the text is
+/// malformed, illegal here, or matches more than one move.
+pub fn from_san(board: &Board, san: &str) -> Option<Move> {
+  let san = san.trim().trim_end_matches(['+', '#', '!', '?']);
+  let mut board = board.clone_for_search();
+  let mut legal = MoveList::new();
+  movegen::generate_legal_moves(&mut board, &mut legal);
+
+  let castle = match san {
+    "O-O" | "0-0" => Some(KING_CASTLE_FLAG),
+    "O-O-O" | "0-0-0" => Some(QUEEN_CASTLE_FLAG),
+    _ => None,
+  };
+  if let Some(castle) = castle {
+    return legal.iter().copied().find(|&m| flag(m) == castle);
+  }
+
+  let (piece, rest) = match san.chars().next()? {
+    'N' => (PieceType::Knight, &san[1..]),
+    'B' => (PieceType::Bishop, &san[1..]),
+    'R' => (PieceType::Rook, &san[1..]),
+    'Q' => (PieceType::Queen, &san[1..]),
+    'K' => (PieceType::King, &san[1..]),
+    _ => (PieceType::Pawn, san),
+  };
+
+  // Promotion suffix, with or without '='
+  let (rest, promotion) = match rest.as_bytes() {
+    [.., b'=', p] | [.., b'1'..=b'8', p] if piece == PieceType::Pawn && p.is_ascii_uppercase() => {
+      let promotion = match p {
+        b'N' => PieceType::Knight,
+        b'B' => PieceType::Bishop,
+        b'R' => PieceType::Rook,
+        b'Q' => PieceType::Queen,
+        _ => return None,
+      };
+      (rest[..rest.len() - 1].trim_end_matches('='), Some(promotion))
+    }
+    _ => (rest, None),
+  };
+
+  if rest.len() < 2 || !rest.is_ascii() {
+    return None;
+  }
+  let (hint, dest) = rest.split_at(rest.len() - 2);
+  let to = parse_square(dest)?;
+  let capture = hint.contains('x');
+
+  let mut hint_file = None;
+  let mut hint_rank = None;
+  for c in hint.chars().filter(|&c| c != 'x') {
+    match c {
+      'a'..='h' if hint_file.is_none() => hint_file = Some(c as u8 - b'a'),
+      '1'..='8' if hint_rank.is_none() => hint_rank = Some(c as u8 - b'1'),
+      _ => return None,
+    }
+  }
+
+  let mut found = None;
+  for &m in legal.iter() {
+    let from = from_sq(m);
+    let matches = to_sq(m) == to
+      && board.piece_type_on(from) == Some(piece)
+      && hint_file.is_none_or(|f| from % 8 == f)
+      && hint_rank.is_none_or(|r| from / 8 == r)
+      && (piece != PieceType::Pawn || is_capture(m) == capture)
+      && (is_promotion(m).then(|| promotion_piece(m)) == promotion);
+
+    if matches {
+      if found.is_some() {
+        return None;
+      }
+      found = Some(m);
+    }
+  }
+  found
+}
+
+fn parse_square(s: &str) -> Option<Square> {
+  match s.as_bytes() {
+    [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => Some((rank - b'1') * 8 + (file - b'a')),
+    _ => None,
+  }
+}
+
 impl fmt::Display for MoveList {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
       write!(f, "MoveList len={}", self.len())
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::engine::find_move;
+
+  fn san(fen: &str, uci: &str) -> String {
+    movegen::init();
+    let board = Board::from_fen(fen).unwrap();
+    to_san(&board, find_move(&board, uci).unwrap())
+  }
+
+  #[test]
+  fn san_basic_moves() {
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    assert_eq!(san(start, "e2e4"), "e4");
+    assert_eq!(san(start, "g1f3"), "Nf3");
+
+    let kiwi = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    assert_eq!(san(kiwi, "e1g1"), "O-O");
+    assert_eq!(san(kiwi, "e1c1"), "O-O-O");
+    assert_eq!(san(kiwi, "f3f6"), "Qxf6");
+    assert_eq!(san("k7/8/8/3pP3/8/8/8/K7 w - d6 0 1", "e5d6"), "exd6");
+  }
+
+  #[test]
+  fn san_disambiguation() {
+    assert_eq!(san("k7/8/8/8/8/8/8/R4R1K w - - 0 1", "a1d1"), "Rad1");
+    assert_eq!(san("7k/8/8/R7/8/8/8/R3K3 w - - 0 1", "a1a3"), "R1a3");
+    assert_eq!(san("7k/8/8/8/Q1Q5/8/Q7/K7 w - - 0 1", "a4b3"), "Qa4b3");
+  }
+
+  #[test]
+  fn san_round_trips_every_legal_move() {
+    movegen::init();
+    let fens = [
+      "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+      "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+      "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+      "7k/8/8/8/Q1Q5/8/Q7/K7 w - - 0 1",
+    ];
+    for fen in fens {
+      let mut board = Board::from_fen(fen).unwrap();
+      let mut legal = MoveList::new();
+      movegen::generate_legal_moves(&mut board, &mut legal);
+      for &m in legal.iter() {
+        let text = to_san(&board, m);
+        assert_eq!(from_san(&board, &text), Some(m), "{} in {}", text, fen);
+      }
+    }
+  }
+
+  #[test]
+  fn san_parsing_variants() {
+    movegen::init();
+    let board = Board::from_fen("r3k2r/1b4bq/8/8/8/8/7B/R3K2R b KQkq - 0 1").unwrap();
+    assert_eq!(from_san(&board, "0-0-0!?").map(format).as_deref(), Some("e8c8"));
+    assert_eq!(from_san(&board, "Bxa1").map(format).as_deref(), Some("g7a1"));
+    assert_eq!(from_san(&board, "Bgxh2"), None, "no piece on the g-file can capture");
+
+    let board = Board::from_fen("r1bqkb1r/pp1n1ppp/5n2/2p5/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap();
+    assert_eq!(from_san(&board, "Nbd7"), None, "d7 is occupied");
+    let board = Board::from_fen("rnbqkb1r/ppp2ppp/5n2/8/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap();
+    assert_eq!(from_san(&board, "Nd7"), None, "ambiguous");
+    assert_eq!(from_san(&board, "Nbd7").map(format).as_deref(), Some("b8d7"));
+
+    let board = Board::from_fen("k7/8/8/3pP3/8/8/8/K7 w - d6 0 1").unwrap();
+    assert_eq!(from_san(&board, "exd6").map(format).as_deref(), Some("e5d6"));
+    assert_eq!(from_san(&board, "ed6"), None);
+
+    let board = Board::from_fen("7k/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(from_san(&board, "e8=Q+").map(format).as_deref(), Some("e7e8q"));
+    assert_eq!(from_san(&board, "e8N").map(format).as_deref(), Some("e7e8n"));
+    assert_eq!(from_san(&board, "e8"), None, "promotion piece is required");
+
+    let board = Board::from_fen("k7/8/8/8/8/8/8/R4R1K w - - 0 1").unwrap();
+    assert_eq!(from_san(&board, "Rd1"), None, "ambiguous");
+    for junk in ["", "Ke3", "Zz9", "x", "Rad"] {
+      assert_eq!(from_san(&board, junk), None, "{:?}", junk);
+    }
+  }
+
+  #[test]
+  fn san_promotion_check_and_mate() {
+    assert_eq!(san("7k/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7e8q"), "e8=Q+");
+    assert_eq!(san("7k/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7e8n"), "e8=N");
+    assert_eq!(san("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8"), "Ra8#");
+  }
+}
@@ -1,5 +1,8 @@
+use std::fmt;
 use std::fs::File;
-use std::io::{self, BufReader, Read, Seek};
+use std::io::{self, BufRead, BufReader, Read};
+
+use flate2::read::GzDecoder;
 use std::sync::OnceLock;
 use std::mem::MaybeUninit;
 
@@ -16,6 +19,8 @@ const HALF_DIMENSIONS: usize = 256;
 const L2_SIZE: usize = 32;
 const L3_SIZE: usize = 32;
 const FV_SCALE: i32 = 16;
+/// Header version of Stockfish 12/13 era HalfKP networks.
+const NNUE_VERSION: u32 = 0x7AF32F16;
 const WEIGHT_SCALE_BITS: i32 = 6;
 
 const PS_W_PAWN: usize = 0;
@@ -46,64 +51,127 @@ pub struct Network {
     pub l3_weights: Vec<i8>,        // L3_SIZE
 }
 
+/// Why a network could not be loaded.
+#[derive(Debug)]
+pub enum NnueError {
+    /// The file could not be opened or read.
+    Io(io::Error),
+    /// The header's version or architecture string is not a HalfKP network.
+    BadMagic(String),
+    /// A layer size, or the file length, differs from the compiled layout.
+    SizeMismatch { what: &'static str, expected: usize, found: usize },
+    /// The data ends before the `expected` bytes a read needed.
+    Truncated { expected: usize, found: usize },
+}
+
+impl fmt::Display for NnueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NnueError::Io(e) => write!(f, "{}", e),
+            NnueError::BadMagic(header) => write!(f, "not a HalfKP network: {}", header),
+            NnueError::SizeMismatch { what, expected, found } => {
+                write!(f, "{} is {}, expected {}", what, found, expected)
+            }
+            NnueError::Truncated { expected, found } => {
+                write!(f, "file is truncated: {} bytes, needs at least {}", found, expected)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for NnueError {
+    fn from(e: io::Error) -> Self {
+        NnueError::Io(e)
+    }
+}
+
 impl Network {
-    pub fn load(path: &str) -> io::Result<Self> {
-        let f = File::open(path)?;
-        let mut reader = BufReader::new(f);
+    /// Loads a network from `path`, transparently inflating gzip files.
+    pub fn load(path: &str) -> Result<Self, NnueError> {
+        let reader = open_network_reader(path)?;
+        Self::from_reader(reader)
+    }
+
+    /// Loads a network held in memory, such as one embedded with
+    /// `include_bytes!`. Gzip data is inflated like in `load`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, NnueError> {
+        if bytes.starts_with(&GZIP_MAGIC) {
+            Self::from_reader(GzDecoder::new(bytes))
+        } else {
+            Self::parse(bytes)
+        }
+    }
 
-        let metadata = std::fs::metadata(path)?;
-        let file_len = metadata.len() as usize;
+    /// Reads an uncompressed network from any byte stream. Reading is
+    /// strictly sequential, so the stream need not be seekable.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, NnueError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Self::parse(&bytes)
+    }
+
+    fn parse(bytes: &[u8]) -> Result<Self, NnueError> {
+        let mut reader = bytes;
+        // Checked before every read, so a short file is reported, not read past
+        let need = |reader: &[u8], len: usize| {
+            if reader.len() < len {
+                let expected = bytes.len() - reader.len() + len;
+                return Err(NnueError::Truncated { expected, found: bytes.len() });
+            }
+            Ok(())
+        };
 
-        let mut version = [0u8; 4];
-        reader.read_exact(&mut version)?;
-        let version_num = u32::from_le_bytes(version);
+        need(reader, 12)?;
+        let version_num = read_u32(&mut reader)?;
         println!("info string NNUE version: 0x{:08X}", version_num);
+        if version_num != NNUE_VERSION {
+            return Err(NnueError::BadMagic(format!("version 0x{:08X}", version_num)));
+        }
 
-        let mut hash = [0u8; 4];
-        reader.read_exact(&mut hash)?;
-        let hash_num = u32::from_le_bytes(hash);
+        let hash_num = read_u32(&mut reader)?;
         println!("info string NNUE hash: 0x{:08X}", hash_num);
 
-        let mut desc_size_buf = [0u8; 4];
-        reader.read_exact(&mut desc_size_buf)?;
-        let desc_size = u32::from_le_bytes(desc_size_buf) as usize;
-        
+        let desc_size = read_u32(&mut reader)? as usize;
+        need(reader, desc_size)?;
         let mut desc = vec![0u8; desc_size];
         reader.read_exact(&mut desc)?;
         let desc_str = String::from_utf8_lossy(&desc);
         println!("info string NNUE arch: {}", desc_str.trim_end_matches('\0'));
+        check_architecture(desc_str.trim_end_matches('\0'))?;
 
-        let mut ft_hash = [0u8; 4];
-        reader.read_exact(&mut ft_hash)?;
-        println!("info string FT hash: 0x{:08X}", u32::from_le_bytes(ft_hash));
-
+        need(reader, 4 + HALF_DIMENSIONS * 2)?;
+        println!("info string FT hash: 0x{:08X}", read_u32(&mut reader)?);
         let ft_biases = read_i16_vec(&mut reader, HALF_DIMENSIONS)?;
-        println!("info string FT biases[0..8]: {:?}", &ft_biases[0..8]);
+        println!("info string FT biases[0..8]: {:?}", &ft_biases[..8.min(ft_biases.len())]);
 
+        need(reader, INPUT_SIZE * HALF_DIMENSIONS * 2)?;
         let ft_weights = read_i16_vec(&mut reader, INPUT_SIZE * HALF_DIMENSIONS)?;
         println!("info string FT weights: {} values loaded", ft_weights.len());
 
-        let mut net_hash = [0u8; 4];
-        reader.read_exact(&mut net_hash)?;
-        println!("info string Network hash: 0x{:08X}", u32::from_le_bytes(net_hash));
-
+        need(reader, 4 + L2_SIZE * 4 + 512 * L2_SIZE)?;
+        println!("info string Network hash: 0x{:08X}", read_u32(&mut reader)?);
         let l1_biases = read_i32_vec(&mut reader, L2_SIZE)?;
-        println!("info string L1 biases[0..8]: {:?}", &l1_biases[0..8.min(L2_SIZE)]);
+        println!("info string L1 biases[0..8]: {:?}", &l1_biases[..8.min(l1_biases.len())]);
 
         let l1_weights_raw = read_i8_vec(&mut reader, 512 * L2_SIZE)?;
         println!("info string L1 weights: {} values", l1_weights_raw.len());
 
+        need(reader, L3_SIZE * 4 + L2_SIZE * L3_SIZE)?;
         let l2_biases = read_i32_vec(&mut reader, L3_SIZE)?;
         let l2_weights_raw = read_i8_vec(&mut reader, L2_SIZE * L3_SIZE)?;
 
+        need(reader, 4 + L3_SIZE)?;
         let l3_bias = read_i32_vec(&mut reader, 1)?[0];
         let l3_weights = read_i8_vec(&mut reader, L3_SIZE)?;
         
         println!("info string L3 bias: {}", l3_bias);
-        println!("info string L3 weights[0..8]: {:?}", &l3_weights[0..8.min(L3_SIZE)]);
+        println!("info string L3 weights[0..8]: {:?}", &l3_weights[..8.min(l3_weights.len())]);
 
-        let pos = reader.stream_position()? as usize;
-        println!("info string Read {} of {} bytes", pos, file_len);
+        let total = bytes.len() - reader.len();
+        if !reader.is_empty() {
+            return Err(NnueError::SizeMismatch { what: "file size", expected: total, found: bytes.len() });
+        }
+        println!("info string Read {} bytes", total);
 
         Ok(Self {
             ft_biases,
@@ -118,6 +186,47 @@ impl Network {
     }
 }
 
+/// Checks the layer sizes in the header's architecture string, such as
+/// `Features=HalfKP(Friend)[41024->256x2],Network=AffineTransform[1<-32](...)`,
+/// against the sizes this evaluator is compiled for.
+fn check_architecture(desc: &str) -> Result<(), NnueError> {
+    let dims = |s: &str, sep: &str| -> Option<(usize, usize)> {
+        let (dims, _) = s.split_once(']')?;
+        let (a, b) = dims.split_once(sep)?;
+        Some((a.parse().ok()?, b.trim_end_matches("x2").parse().ok()?))
+    };
+
+    let (inputs, outputs) = desc
+        .split_once("HalfKP(Friend)[")
+        .and_then(|(_, rest)| dims(rest, "->"))
+        .ok_or_else(|| NnueError::BadMagic(desc.to_string()))?;
+    let layers = desc
+        .split("AffineTransform[")
+        .skip(1)
+        .map(|rest| dims(rest, "<-"))
+        .collect::<Option<Vec<_>>>()
+        .filter(|layers| layers.len() == 3)
+        .ok_or_else(|| NnueError::BadMagic(desc.to_string()))?;
+
+    // Layers are listed from the output back to the feature transformer
+    let checks = [
+        ("feature count", INPUT_SIZE, inputs),
+        ("feature transformer size", HALF_DIMENSIONS, outputs),
+        ("first hidden layer size", L2_SIZE, layers[2].0),
+        ("first hidden layer inputs", 2 * HALF_DIMENSIONS, layers[2].1),
+        ("second hidden layer size", L3_SIZE, layers[1].0),
+        ("second hidden layer inputs", L2_SIZE, layers[1].1),
+        ("output layer size", 1, layers[0].0),
+        ("output layer inputs", L3_SIZE, layers[0].1),
+    ];
+    for (what, expected, found) in checks {
+        if expected != found {
+            return Err(NnueError::SizeMismatch { what, expected, found });
+        }
+    }
+    Ok(())
+}
+
 #[inline(always)]
 pub fn is_enabled() -> bool {
     unsafe { CACHED_NNUE_ENABLED }
@@ -412,49 +521,24 @@ unsafe fn add_weights_avx2_direct(acc: &mut Accumulator, weights: &[i16], offset
 }
 
 pub fn refresh_accumulator(board: &Board) -> [Accumulator; 2] {
+    [refresh_perspective(board, Color::White), refresh_perspective(board, Color::Black)]
+}
+
+/// Rebuild a single perspective from scratch. A king move only invalidates
+/// its own side's accumulator, since kings are not features.
+pub fn refresh_perspective(board: &Board, perspective: Color) -> Accumulator {
     let net = match NETWORK.get() { 
         Some(n) => n, 
-        None => return [Accumulator::default(); 2] 
+        None => return Accumulator::default() 
     };
     
-    let mut accs = [Accumulator::default(); 2];
-    accs[0].values.copy_from_slice(&net.ft_biases);
-    accs[1].values.copy_from_slice(&net.ft_biases);
-
-    let wk_sq = board.king_sq[Color::White as usize];
-    let bk_sq = board.king_sq[Color::Black as usize];
+    let mut acc = Accumulator::default();
+    acc.values.copy_from_slice(&net.ft_biases);
 
-    #[cfg(target_arch = "x86_64")]
-    if use_avx2() {
-        unsafe {
-            for pt_idx in 0..5 {
-                let pt = PieceType::from(pt_idx);
-                for c_idx in 0..2 {
-                    let pc = if c_idx == 0 { Color::White } else { Color::Black };
-                    let mut bb = board.pieces[pt_idx][c_idx];
-                    while bb != 0 {
-                        let sq = bb.trailing_zeros() as u8;
-                        bb &= bb - 1;
-                        
-                        let idx_w = make_index(Color::White, wk_sq, sq, pt, pc);
-                        let idx_b = make_index(Color::Black, bk_sq, sq, pt, pc);
-                        
-                        if idx_w != usize::MAX {
-                            add_weights_avx2_direct(&mut accs[0], &net.ft_weights, idx_w * HALF_DIMENSIONS);
-                        }
-                        if idx_b != usize::MAX {
-                            add_weights_avx2_direct(&mut accs[1], &net.ft_weights, idx_b * HALF_DIMENSIONS);
-                        }
-                    }
-                }
-            }
-        }
-        return accs;
-    }
+    let king_sq = board.king_sq[perspective as usize];
 
-    // Scalar fallback
     for pt_idx in 0..5 {
-        let pt = PieceType::from(pt_idx);
+        let pt = PieceType::from_index(pt_idx);
         for c_idx in 0..2 {
             let pc = if c_idx == 0 { Color::White } else { Color::Black };
             let mut bb = board.pieces[pt_idx][c_idx];
@@ -462,25 +546,26 @@ pub fn refresh_accumulator(board: &Board) -> [Accumulator; 2] {
                 let sq = bb.trailing_zeros() as u8;
                 bb &= bb - 1;
                 
-                let idx_w = make_index(Color::White, wk_sq, sq, pt, pc);
-                let idx_b = make_index(Color::Black, bk_sq, sq, pt, pc);
-                
-                if idx_w != usize::MAX {
-                    let off = idx_w * HALF_DIMENSIONS;
-                    for i in 0..HALF_DIMENSIONS {
-                        accs[0].values[i] = accs[0].values[i].saturating_add(net.ft_weights[off + i]);
-                    }
+                let idx = make_index(perspective, king_sq, sq, pt, pc);
+                if idx == usize::MAX {
+                    continue;
                 }
-                if idx_b != usize::MAX {
-                    let off = idx_b * HALF_DIMENSIONS;
-                    for i in 0..HALF_DIMENSIONS {
-                        accs[1].values[i] = accs[1].values[i].saturating_add(net.ft_weights[off + i]);
-                    }
+
+                #[cfg(target_arch = "x86_64")]
+                if use_avx2() {
+                    unsafe { add_weights_avx2_direct(&mut acc, &net.ft_weights, idx * HALF_DIMENSIONS); }
+                    continue;
+                }
+
+                // Scalar fallback
+                let off = idx * HALF_DIMENSIONS;
+                for i in 0..HALF_DIMENSIONS {
+                    acc.values[i] = acc.values[i].saturating_add(net.ft_weights[off + i]);
                 }
             }
         }
     }
-    accs
+    acc
 }
 
 /// Clipped ReLU: clamp to [0, 127] for i16 input
@@ -794,7 +879,7 @@ pub fn debug_eval(board: &Board) {
     let mut b_features = 0usize;
     
     for pt_idx in 0..5 {
-        let _pt = PieceType::from(pt_idx);
+        let _pt = PieceType::from_index(pt_idx);
         for c in 0..2 {
             let _pc = if c == 0 { Color::White } else { Color::Black };
             let count = board.pieces[pt_idx][c].count_ones() as usize;
@@ -813,11 +898,31 @@ pub fn debug_eval(board: &Board) {
     println!("Acc[0] sum: {}, Acc[1] sum: {}", sum_w, sum_b);
     
     let score = evaluate(board);
-    println!("NNUE eval: {} cp", score);
+    let white_score = if board.side_to_move == Color::White { score } else { -score };
+    println!("NNUE eval: {} cp (side to move), {} cp (white)", score, white_score);
     println!("==================");
 }
 
 
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Opens a network file, routing through a gzip decoder when the file
+/// starts with the gzip magic bytes.
+fn open_network_reader(path: &str) -> io::Result<Box<dyn Read>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        Ok(Box::new(GzDecoder::new(reader)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut buffer = [0u8; 4];
+    reader.read_exact(&mut buffer)?;
+    Ok(u32::from_le_bytes(buffer))
+}
+
 fn read_i16_vec<R: Read>(reader: &mut R, len: usize) -> io::Result<Vec<i16>> {
     let mut buffer = vec![0u8; len * 2];
     reader.read_exact(&mut buffer)?;
@@ -847,6 +952,80 @@ fn read_i8_vec<R: Read>(reader: &mut R, len: usize) -> io::Result<Vec<i8>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use flate2::{Compression, write::GzEncoder};
+    use std::io::Write;
+
+    #[test]
+    fn test_gzip_detection_routes_to_decoder() {
+        let header: Vec<u8> = (0u8..64).chain([0x1f, 0x8b, 0x00]).collect();
+        let dir = std::env::temp_dir();
+        let raw_path = dir.join(format!("nnue_raw_{}.bin", std::process::id()));
+        let gz_path = dir.join(format!("nnue_gz_{}.bin.gz", std::process::id()));
+
+        std::fs::write(&raw_path, &header).unwrap();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&header).unwrap();
+        std::fs::write(&gz_path, encoder.finish().unwrap()).unwrap();
+
+        for path in [&raw_path, &gz_path] {
+            let mut out = Vec::new();
+            open_network_reader(path.to_str().unwrap())
+                .unwrap()
+                .read_to_end(&mut out)
+                .unwrap();
+            assert_eq!(out, header, "{}", path.display());
+        }
+
+        let _ = std::fs::remove_file(raw_path);
+        let _ = std::fs::remove_file(gz_path);
+    }
+
+    #[test]
+    fn test_from_bytes_matches_load() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/nn-62ef826d1a6d.nnue");
+        let bytes = std::fs::read(path).unwrap();
+        let from_file = Network::load(path).unwrap();
+        let from_bytes = Network::from_bytes(&bytes).unwrap();
+        assert_eq!(from_bytes.ft_biases, from_file.ft_biases);
+        assert_eq!(from_bytes.ft_weights, from_file.ft_weights);
+        assert_eq!(from_bytes.l3_weights, from_file.l3_weights);
+
+        let half = bytes.len() / 2;
+        let truncated = Network::from_bytes(&bytes[..half]).err().unwrap();
+        assert!(matches!(truncated, NnueError::Truncated { expected, found } if found == half && expected > half));
+        assert!(matches!(
+            Network::from_bytes(&bytes[..5]),
+            Err(NnueError::Truncated { expected: 12, found: 5 })
+        ));
+        assert!(matches!(Network::load("no_such_network.nnue"), Err(NnueError::Io(_))));
+    }
+
+    #[test]
+    fn test_rejects_mismatched_networks() {
+        let arch = "Features=HalfKP(Friend)[41024->256x2],Network=AffineTransform[1<-32](ClippedReLU[32](\
+                    AffineTransform[32<-32](ClippedReLU[32](AffineTransform[32<-512](InputSlice[512(0:512)])))))";
+        assert!(check_architecture(arch).is_ok());
+        let wider = arch.replace("256x2", "512x2").replace("32<-512", "32<-1024");
+        let err = check_architecture(&wider).err().unwrap();
+        assert_eq!(err.to_string(), "feature transformer size is 512, expected 256");
+        assert!(matches!(
+            check_architecture("Features=HalfKAv2[45056->512x2]"),
+            Err(NnueError::BadMagic(_))
+        ));
+
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/nn-62ef826d1a6d.nnue");
+        let mut bytes = std::fs::read(path).unwrap();
+        bytes.push(0);
+        let trailing = Network::from_bytes(&bytes).err().unwrap();
+        let len = bytes.len();
+        assert!(matches!(
+            trailing,
+            NnueError::SizeMismatch { what: "file size", expected, found } if expected == len - 1 && found == len
+        ));
+
+        bytes[0] ^= 1;
+        assert!(matches!(Network::from_bytes(&bytes), Err(NnueError::BadMagic(_))));
+    }
 
     #[test]
     fn test_make_index_bounds() {
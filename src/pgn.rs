@@ -0,0 +1,202 @@
+use std::fmt;
+
+use crate::{
+    board::Board,
+    moves::{self, Move},
+};
+
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// Why `parse_game` rejected its input.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PgnError {
+    /// The `[FEN]` tag could not be parsed.
+    InvalidFen(&'static str),
+    /// A tag pair is not of the form `[Name "value"]`.
+    MalformedTag(String),
+    /// A comment, variation or tag was still open at the end of the input.
+    Unterminated(char),
+    /// A movetext token did not resolve to exactly one legal move.
+    IllegalMove { ply: usize, token: String },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::InvalidFen(e) => write!(f, "invalid FEN tag: {}", e),
+            PgnError::MalformedTag(tag) => write!(f, "malformed tag pair: {}", tag),
+            PgnError::Unterminated(open) => write!(f, "unterminated '{}'", open),
+            PgnError::IllegalMove { ply, token } => {
+                let number = ply / 2 + 1;
+                let dots = if ply % 2 == 0 { "." } else { "..." };
+                write!(f, "illegal or ambiguous move {}{} {}", number, dots, token)
+            }
+        }
+    }
+}
+
+/// Replays a single PGN game and returns the final position with the moves
+/// played. Honors a `[FEN]` tag and skips comments, NAGs and variations.
+pub fn parse_game(pgn: &str) -> Result<(Board, Vec<Move>), PgnError> {
+    let mut fen = None;
+    let mut tokens = Vec::new();
+    let mut chars = pgn.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => {
+                let mut tag = String::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some(c) => tag.push(c),
+                        None => return Err(PgnError::Unterminated('[')),
+                    }
+                }
+                let (name, value) = parse_tag(&tag)?;
+                if name == "FEN" {
+                    fen = Some(value);
+                }
+            }
+            '{' => {
+                if !chars.by_ref().any(|c| c == '}') {
+                    return Err(PgnError::Unterminated('{'));
+                }
+            }
+            ';' => {
+                chars.by_ref().find(|&c| c == '\n');
+            }
+            '(' => {
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.next() {
+                        Some('(') => depth += 1,
+                        Some(')') => depth -= 1,
+                        Some('{') => {
+                            if !chars.by_ref().any(|c| c == '}') {
+                                return Err(PgnError::Unterminated('{'));
+                            }
+                        }
+                        Some(_) => {}
+                        None => return Err(PgnError::Unterminated('(')),
+                    }
+                }
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut token = String::from(c);
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || "[{;()".contains(next) {
+                        break;
+                    }
+                    token.push(next);
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+        }
+    }
+
+    let fen = fen.as_deref().unwrap_or(START_FEN);
+    let mut board = Board::from_fen(fen).map_err(PgnError::InvalidFen)?;
+    let mut played = Vec::new();
+
+    for token in tokens {
+        if token.starts_with('$') || matches!(token.as_str(), "1-0" | "0-1" | "1/2-1/2" | "*") {
+            continue;
+        }
+
+        // Move numbers may be glued to the move, as in "1.e4" or "3...Nf6".
+        // Only digits followed by dots are one, so "0-0" stays a castle.
+        let rest = token.trim_start_matches(|c: char| c.is_ascii_digit());
+        let san = match rest.strip_prefix('.') {
+            Some(after) if rest.len() < token.len() => after.trim_start_matches('.'),
+            _ => token.as_str(),
+        };
+        if san.is_empty() {
+            continue;
+        }
+
+        let m = moves::from_san(&board, san).ok_or_else(|| PgnError::IllegalMove {
+            ply: played.len(),
+            token: token.clone(),
+        })?;
+        board.make_move(m);
+        played.push(m);
+    }
+
+    Ok((board, played))
+}
+
+fn parse_tag(tag: &str) -> Result<(&str, String), PgnError> {
+    let malformed = || PgnError::MalformedTag(tag.to_string());
+    let (name, rest) = tag.trim().split_once(char::is_whitespace).ok_or_else(malformed)?;
+    let value = rest
+        .trim()
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .ok_or_else(malformed)?;
+    Ok((name, value.replace("\\\"", "\"").replace("\\\\", "\\")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen;
+
+    #[test]
+    fn replays_game_skipping_annotations() {
+        movegen::init();
+        let pgn = r#"[Event "Test"]
+[White "A"]
+[Black "B"]
+
+1. e4 e5 2. Nf3 {developing} Nc6 $1 3. Bb5 (3. Bc4 Bc5 (3... Nf6) 4. c3) 3...a6
+; a rest-of-line comment
+4.Ba4 Nf6 5. O-O! Be7 1-0"#;
+        let (board, played) = parse_game(pgn).unwrap();
+        assert_eq!(played.len(), 10);
+        assert_eq!(moves::format(played[8]), "e1g1");
+        assert_eq!(
+            board.to_fen(),
+            "r1bqk2r/1pppbppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQ1RK1 w kq - 4 6"
+        );
+    }
+
+    #[test]
+    fn castles_written_with_zeros() {
+        movegen::init();
+        let (board, played) = parse_game("1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4.0-0 Nf6 5. d3 0-0").unwrap();
+        assert_eq!(moves::format(played[6]), "e1g1");
+        assert_eq!(moves::format(played[9]), "e8g8");
+        assert_eq!(
+            board.to_fen(),
+            "r1bq1rk1/pppp1ppp/2n2n2/2b1p3/2B1P3/3P1N2/PPP2PPP/RNBQ1RK1 w - - 1 6"
+        );
+    }
+
+    #[test]
+    fn honors_fen_tag() {
+        movegen::init();
+        let pgn = r#"[SetUp "1"]
+[FEN "7k/4P3/8/8/8/8/8/4K3 w - - 0 1"]
+
+1. e8=Q+ Kh7 *"#;
+        let (board, played) = parse_game(pgn).unwrap();
+        assert_eq!(played.len(), 2);
+        assert_eq!(board.to_fen(), "4Q3/7k/8/8/8/8/8/4K3 w - - 1 2");
+    }
+
+    #[test]
+    fn reports_offending_token() {
+        movegen::init();
+        let err = parse_game("1. e4 e5 2. Nf3 Ke7 3. Ke3").err().unwrap();
+        assert_eq!(err, PgnError::IllegalMove { ply: 4, token: "Ke3".to_string() });
+        assert_eq!(err.to_string(), "illegal or ambiguous move 3. Ke3");
+
+        assert_eq!(parse_game("1. e4 {oops").err(), Some(PgnError::Unterminated('{')));
+        assert!(matches!(parse_game("[FEN \"bogus\"]").err(), Some(PgnError::InvalidFen(_))));
+        assert!(matches!(parse_game("[FEN]").err(), Some(PgnError::MalformedTag(_))));
+        assert_eq!(parse_game("[Event \"x\"").err(), Some(PgnError::Unterminated('[')));
+    }
+}
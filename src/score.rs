@@ -0,0 +1,106 @@
+use std::fmt;
+
+pub const INF: i32 = 32000;
+pub const MATE_SCORE: i32 = 31000;
+/// Scores strictly beyond this bound are mates; tablebase wins sit at or below it.
+pub const MATE_BOUND: i32 = 30000;
+
+/// A search score in centipawns, with mates encoded as `MATE_SCORE - ply`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Score(pub i32);
+
+impl Score {
+    /// Score for delivering mate `ply` plies from the root.
+    pub fn mate_in_plies(ply: i32) -> Self {
+        Score(MATE_SCORE - ply)
+    }
+
+    /// Score for being mated `ply` plies from the root.
+    pub fn mated_in_plies(ply: i32) -> Self {
+        Score(-MATE_SCORE + ply)
+    }
+
+    pub fn is_mate(self) -> bool {
+        self.0.abs() > MATE_BOUND
+    }
+
+    /// Full moves to mate, negative when the side to move is getting mated.
+    pub fn mate_in(self) -> Option<i32> {
+        if self.0 > MATE_BOUND {
+            Some((MATE_SCORE - self.0 + 1) / 2)
+        } else if self.0 < -MATE_BOUND {
+            Some(-((MATE_SCORE + self.0) / 2))
+        } else {
+            None
+        }
+    }
+
+    /// Converts a root-relative mate score into a node-relative one for storage.
+    pub fn to_tt(self, ply: i32) -> Self {
+        if self.0 > MATE_BOUND {
+            Score(self.0 + ply)
+        } else if self.0 < -MATE_BOUND {
+            Score(self.0 - ply)
+        } else {
+            self
+        }
+    }
+
+    /// Inverse of `to_tt`.
+    pub fn from_tt(self, ply: i32) -> Self {
+        if self.0 > MATE_BOUND {
+            Score(self.0 - ply)
+        } else if self.0 < -MATE_BOUND {
+            Score(self.0 + ply)
+        } else {
+            self
+        }
+    }
+}
+
+/// Formats as the UCI `score` payload: `cp <x>` or `mate <n>`.
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mate_in() {
+            Some(n) => write!(f, "mate {}", n),
+            None => write!(f, "cp {}", self.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mate_in_n_round_trips() {
+        for n in 1..50 {
+            assert_eq!(Score::mate_in_plies(2 * n - 1).mate_in(), Some(n));
+            assert_eq!(Score::mated_in_plies(2 * n).mate_in(), Some(-n));
+        }
+    }
+
+    #[test]
+    fn centipawns_and_tb_scores_are_not_mate() {
+        for s in [0, 150, -150, 29000, MATE_BOUND, -MATE_BOUND] {
+            assert!(!Score(s).is_mate());
+            assert_eq!(Score(s).mate_in(), None);
+        }
+    }
+
+    #[test]
+    fn tt_adjustment_is_symmetric() {
+        for ply in 0..64 {
+            for s in [Score::mate_in_plies(7), Score::mated_in_plies(12), Score(-35), Score(400)] {
+                assert_eq!(s.to_tt(ply).from_tt(ply), s);
+            }
+        }
+    }
+
+    #[test]
+    fn display_uses_uci_form() {
+        assert_eq!(Score(35).to_string(), "cp 35");
+        assert_eq!(Score::mate_in_plies(3).to_string(), "mate 2");
+        assert_eq!(Score::mated_in_plies(4).to_string(), "mate -2");
+    }
+}
@@ -1,22 +1,120 @@
 use crate::{
     board::Board,
-    book::OpeningBook,
+    book::{DEFAULT_BOOK, OpeningBook},
     eval, movegen,
+    movepick::{self, MovePicker},
     moves::{self, Move, MoveList},
+    score::{INF, MATE_BOUND, Score},
     see, syzygy,
     thread::SharedState,
     tt::TTFlag,
-    types::{Color, PieceType},
+    types::{Color, PieceType, Square},
+    zobrist::ZHash,
 };
-use std::sync::Arc;
 use std::sync::atomic::Ordering;
+use std::sync::{Arc, OnceLock};
 use std::time::Instant;
 
-const INF: i32 = 32000;
-pub const MATE_SCORE: i32 = 31000;
+pub use crate::score::MATE_SCORE;
 
 const NODE_UPDATE_INTERVAL: u64 = 16384;
 
+/// Longest line the PV table can hold.
+const MAX_PLY: usize = 128;
+
+// Logistic WDL model: a score of WDL_MIDPOINT cp wins half the time,
+// WDL_SCALE controls how quickly the curve saturates.
+const WDL_MIDPOINT: f64 = 200.0;
+const WDL_SCALE: f64 = 80.0;
+
+// Easy move: once the best move has held for a few iterations, check at
+// half depth whether every alternative is at least EASY_MOVE_MARGIN worse.
+const EASY_MOVE_MIN_DEPTH: u8 = 6;
+const EASY_MOVE_MARGIN: i32 = 150;
+
+// Root moves are announced with `currmove` once a search has run this long (ms).
+const CURRMOVE_MIN_TIME: u128 = 3000;
+
+// Singular extensions: from this depth, a TT move whose score no other move
+// comes within SINGULAR_MARGIN per ply of (at reduced depth) is extended.
+const SINGULAR_MIN_DEPTH: u8 = 8;
+const SINGULAR_MARGIN: i32 = 2;
+
+// ProbCut: from this depth, a capture that beats beta by the ProbCut margin
+// in a search reduced by PROBCUT_REDUCTION plies cuts the node.
+const PROBCUT_MIN_DEPTH: u8 = 5;
+const PROBCUT_REDUCTION: u8 = 4;
+
+// Quiescence also searches quiet checks in its first QS_CHECK_PLIES plies.
+const QS_CHECK_PLIES: u8 = 1;
+
+/// Late move reductions by `[depth][move number]`, both capped at 63.
+static LMR_TABLE: OnceLock<[[u8; 64]; 64]> = OnceLock::new();
+
+fn lmr_table() -> &'static [[u8; 64]; 64] {
+    LMR_TABLE.get_or_init(|| {
+        let mut table = [[0; 64]; 64];
+        for (depth, row) in table.iter_mut().enumerate().skip(1) {
+            for (move_number, r) in row.iter_mut().enumerate().skip(1) {
+                let reduction = 1.0 + (depth as f64).ln() * (move_number as f64).ln() / 2.0;
+                *r = (reduction as u8).min(depth as u8 - 1);
+            }
+        }
+        table
+    })
+}
+
+/// Pruning margins, kept together so they can be tuned at runtime.
+#[derive(Debug, Clone, Copy)]
+pub struct SearchParams {
+    /// Reverse futility margin per ply of depth.
+    pub rfp_margin: i32,
+    /// Futility margin per ply of depth.
+    pub futility_margin: i32,
+    /// Captures losing more than this per ply of depth (by SEE) are pruned.
+    pub see_margin: i32,
+    /// How far above beta a shallow capture search must land for ProbCut.
+    pub probcut_margin: i32,
+    /// Late move pruning move counts for depths 1 to 4.
+    pub lmp_thresholds: [i32; 4],
+    /// Share of the continuation history, in percent, added to a quiet
+    /// move's butterfly history when ordering.
+    pub continuation_weight: i32,
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        Self {
+            rfp_margin: 80,
+            futility_margin: 150,
+            see_margin: 20,
+            probcut_margin: 200,
+            lmp_thresholds: [3, 6, 10, 15],
+            continuation_weight: 50,
+        }
+    }
+}
+
+impl SearchParams {
+    /// Sets a parameter by its (case-insensitive) option name. Returns false
+    /// for unknown names.
+    pub fn set(&mut self, name: &str, value: i32) -> bool {
+        match name.to_lowercase().as_str() {
+            "rfpmargin" => self.rfp_margin = value,
+            "futilitymargin" => self.futility_margin = value,
+            "seemargin" => self.see_margin = value,
+            "probcutmargin" => self.probcut_margin = value,
+            "lmp1" => self.lmp_thresholds[0] = value,
+            "lmp2" => self.lmp_thresholds[1] = value,
+            "lmp3" => self.lmp_thresholds[2] = value,
+            "lmp4" => self.lmp_thresholds[3] = value,
+            "continuationweight" => self.continuation_weight = value,
+            _ => return false,
+        }
+        true
+    }
+}
+
 /// Thread-local search state for multi-threaded search
 pub struct SearchThread {
     pub thread_id: usize,
@@ -27,10 +125,62 @@ pub struct SearchThread {
     pub start_time: Instant,
     pub time_soft_limit: u128,
     pub time_hard_limit: u128,
+    /// Stop once this many nodes have been searched (`u64::MAX` for none).
+    pub node_limit: u64,
+    /// Deepest fully completed iteration of the last search.
+    pub completed_depth: u8,
+    /// Highest ply reached in the last search, quiescence included.
+    pub seldepth: i32,
     pub killers: [[Option<Move>; 2]; 64],
     pub history: [[[i32; 64]; 2]; 6],
     pub counter_moves: [[Option<Move>; 64]; 6],
     pub prev_move: Option<Move>,
+    /// Piece and destination of the moves one and two plies before the
+    /// current node, the keys into `continuation_history`.
+    pub prev_pieces: [Option<(PieceType, Square)>; 2],
+    /// Quiet move scores following the move one (`[0]`) or two (`[1]`)
+    /// plies back: `continuation_history[offset][prev_pt * 64 + prev_to][pt][to]`.
+    pub continuation_history: [Vec<[[i32; 64]; 6]>; 2],
+    pub show_wdl: bool,
+    /// Suppresses all `info` output, for library callers.
+    pub silent: bool,
+    /// Thread-dependent history noise for Lazy SMP. When off, helpers
+    /// diversify by starting iterative deepening at an offset instead.
+    pub jitter: bool,
+    /// Analysis mode (`UCI_AnalyseMode`): never cut thinking time short.
+    pub analyse_mode: bool,
+    /// Centipawns the side we search for gives up by drawing (`Contempt`).
+    pub contempt: i32,
+    /// Side to move at the root, the side `contempt` applies to.
+    root_color: Color,
+    pub params: SearchParams,
+    /// Number of root lines to find and report (`MultiPV`).
+    pub multipv: usize,
+    /// Principal variation of the last completed iteration.
+    pub pv: Vec<Move>,
+    /// `(score, pv)` of each root line from the last completed iteration,
+    /// best first. Only filled when `multipv > 1`.
+    pub root_lines: Vec<(i32, Vec<Move>)>,
+    /// Triangular PV table: row `ply` holds the line from `ply` onwards,
+    /// `pv_length[ply]` is where it ends.
+    pv_table: Vec<[Move; MAX_PLY]>,
+    pv_length: [usize; MAX_PLY],
+    /// Static eval of each node on the current line by ply, `-INF` where
+    /// the side to move was in check.
+    eval_stack: [i32; MAX_PLY],
+    /// If not empty, the only root moves searched (`go searchmoves`).
+    pub root_moves: Vec<Move>,
+    /// Positions played before the root, from `Board::repetition_keys`.
+    pub game_history: Vec<ZHash>,
+    /// Length of `board.history` at the root. Older entries are left to
+    /// `game_history`, so they are not counted twice.
+    root_history_len: usize,
+    /// Stop as soon as a mate in at most this many moves is found (`go mate`).
+    pub mate_limit: Option<i32>,
+    /// Print castling as king takes rook (`UCI_Chess960`).
+    pub chess960: bool,
+    /// Root moves skipped while searching for the next MultiPV line.
+    excluded_root_moves: Vec<Move>,
 }
 
 impl SearchThread {
@@ -44,10 +194,34 @@ impl SearchThread {
             start_time: Instant::now(),
             time_soft_limit: u128::MAX,
             time_hard_limit: u128::MAX,
+            node_limit: u64::MAX,
+            completed_depth: 0,
+            seldepth: 0,
             killers: [[None; 2]; 64],
             history: [[[0; 64]; 2]; 6],
             counter_moves: [[None; 64]; 6],
             prev_move: None,
+            prev_pieces: [None; 2],
+            continuation_history: [vec![[[0; 64]; 6]; 6 * 64], vec![[[0; 64]; 6]; 6 * 64]],
+            show_wdl: false,
+            silent: false,
+            jitter: true,
+            analyse_mode: false,
+            contempt: 0,
+            root_color: Color::White,
+            params: SearchParams::default(),
+            multipv: 1,
+            pv: Vec::new(),
+            root_lines: Vec::new(),
+            pv_table: vec![[0; MAX_PLY]; MAX_PLY],
+            pv_length: [0; MAX_PLY],
+            eval_stack: [-INF; MAX_PLY],
+            root_moves: Vec::new(),
+            game_history: Vec::new(),
+            root_history_len: 0,
+            mate_limit: None,
+            chess960: false,
+            excluded_root_moves: Vec::new(),
         }
     }
 
@@ -61,17 +235,41 @@ impl SearchThread {
         self.shared.stop.store(true, Ordering::Relaxed);
     }
 
+    /// Adopts the time limits published on `ponderhit`. Main thread only.
+    #[inline]
+    fn sync_clock(&mut self) {
+        if let Some(clock) = self.shared.take_clock_update() {
+            self.start_time = clock.start;
+            self.time_soft_limit = clock.soft_limit;
+            self.time_hard_limit = clock.hard_limit;
+        }
+    }
+
     #[inline]
     fn increment_nodes(&mut self) {
         self.nodes += 1;
         self.local_nodes += 1;
 
+        if self.is_main && self.nodes >= self.node_limit {
+            self.set_stop();
+        }
+
         if self.local_nodes >= NODE_UPDATE_INTERVAL {
-            self.shared
+            let total = self
+                .shared
                 .nodes
-                .fetch_add(self.local_nodes, Ordering::Relaxed);
+                .fetch_add(self.local_nodes, Ordering::Relaxed)
+                + self.local_nodes;
             self.local_nodes = 0;
 
+            if self.is_main && total >= self.node_limit {
+                self.set_stop();
+            }
+
+            if self.is_main {
+                self.sync_clock();
+            }
+
             if self.is_main && self.time_hard_limit != u128::MAX {
                 let elapsed = self.start_time.elapsed().as_millis();
                 if elapsed >= self.time_hard_limit {
@@ -85,42 +283,63 @@ impl SearchThread {
         self.nodes = 0;
         self.local_nodes = 0;
         self.start_time = Instant::now();
+        self.completed_depth = 0;
+        self.seldepth = 0;
         self.killers = [[None; 2]; 64];
+        self.eval_stack = [-INF; MAX_PLY];
         self.age_history();
+        self.pv.clear();
+        self.root_lines.clear();
+        self.excluded_root_moves.clear();
+        self.root_history_len = board.history.len();
+        self.root_color = board.side_to_move;
 
         let mut best_move = None;
         let mut score = 0;
 
+        // Never ask for more lines than there are legal moves
+        let mut num_lines = self.multipv.max(1);
+        if num_lines > 1 {
+            let mut legal_moves = MoveList::new();
+            movegen::generate_legal_moves(board, &mut legal_moves);
+            let searchable = legal_moves.iter().filter(|&&m| !self.skips_root_move(m)).count();
+            num_lines = num_lines.min(searchable);
+        }
+        let mut line_scores: Vec<i32> = Vec::new();
+
         // Only main thread does early exit checks
         if self.is_main {
-            // Check for single legal move
-            let mut root_moves = MoveList::new();
-            board.generate_pseudo_legal_moves(&mut root_moves);
-            let mut legal_moves = Vec::new();
-            for &m in root_moves.iter() {
-                let undo = board.make_move(m);
-                let us = if board.side_to_move == Color::White {
-                    Color::Black
-                } else {
-                    Color::White
-                };
-                let king_sq =
-                    board.pieces[PieceType::King as usize][us as usize].trailing_zeros() as u8;
-                if !board.is_square_attacked(king_sq, board.side_to_move) {
-                    legal_moves.push(m);
+            if board.can_claim_draw() && !self.silent {
+                println!("info string draw claimable");
+            }
+
+            if !self.silent {
+                println!("info string static eval {}", eval::evaluate(board));
+
+                if self.analyse_mode
+                    && let Some(bound) = self.root_tb_bound(board)
+                {
+                    println!("info string tb bound {}", Score(bound));
                 }
-                board.unmake_move(m, undo);
             }
 
+            // Check for single legal move
+            let mut legal_moves = MoveList::new();
+            movegen::generate_legal_moves(board, &mut legal_moves);
+
             if legal_moves.len() == 1 {
-                return (0, Some(legal_moves[0]));
+                self.pv = vec![legal_moves.get(0)];
+                return (0, Some(legal_moves.get(0)));
             }
 
             // Syzygy DTZ Root Probing (only main thread)
-            if board.occupancy[2].count_ones() <= 6 {
+            if board.occupancy[2].count_ones() <= syzygy::max_pieces()
+                && self.root_moves.is_empty()
+            {
                 if let Some(tb) = crate::syzygy::get_global_syzygy() {
                     if board.occupancy[2].count_ones() <= tb.max_pieces() {
                         if let Some((from, to, promo, wdl)) = syzygy::probe_root(board, &tb) {
+                            self.shared.tbhits.fetch_add(1, Ordering::Relaxed);
                             let mut move_list = MoveList::new();
                             board.generate_pseudo_legal_moves(&mut move_list);
 
@@ -164,11 +383,14 @@ impl SearchThread {
                                                 -1 => -29000,
                                                 _ => 0,
                                             };
-                                            println!(
-                                                "info string TB root move: {} (wdl={})",
-                                                moves::format(m),
-                                                wdl
-                                            );
+                                            if !self.silent {
+                                                println!(
+                                                    "info string TB root move: {} (wdl={})",
+                                                    moves::format_uci(m, board, self.chess960),
+                                                    wdl
+                                                );
+                                            }
+                                            self.pv = vec![m];
                                             return (tb_score, Some(m));
                                         }
                                     }
@@ -183,16 +405,24 @@ impl SearchThread {
         let mut prev_best_move = None;
         let mut stability = 0;
         let mut last_iter_time = 0_u128;
+        let mut easy_move_checked = false;
 
-        let mut alpha = -INF;
-        let mut beta = INF;
+        let start_depth = if self.jitter || self.is_main {
+            1
+        } else {
+            (1 + self.thread_id % 2) as u8
+        };
 
         // Iterative Deepening with Aspiration Windows
-        for d in 1..=depth {
+        for d in start_depth.min(depth)..=depth {
             if self.should_stop() {
                 break;
             }
 
+            if self.is_main {
+                self.sync_clock();
+            }
+
             let elapsed = self.start_time.elapsed().as_millis();
             if self.is_main && d > 1 && self.time_soft_limit != u128::MAX {
                 let projected = elapsed + last_iter_time.saturating_mul(3) / 2 + 5;
@@ -203,52 +433,49 @@ impl SearchThread {
 
             let iter_start_time = self.start_time.elapsed().as_millis();
 
-            // Aspiration Windows
-            let mut delta = 50;
-            if d > 4 {
-                alpha = (-INF).max(score - delta);
-                beta = (INF).min(score + delta);
-            } else {
-                alpha = -INF;
-                beta = INF;
+            let (search_score, m) = self.aspiration_search(board, d, score);
+            if let Some(mv) = m {
+                best_move = Some(mv);
             }
 
-            let mut search_score;
-            loop {
-                let (s, m) = self.negamax(board, d, 0, alpha, beta, true);
-                search_score = s;
+            if self.should_stop() {
+                break;
+            }
 
-                if self.should_stop() {
-                    break;
-                }
+            let mut iter_score = search_score;
+            let mut iter_pv = best_move.map(|mv| self.root_pv(mv)).unwrap_or_default();
 
-                if s <= alpha {
-                    alpha = (-INF).max(alpha - delta);
-                    delta += delta / 2;
-                } else if s >= beta {
-                    if let Some(mv) = m {
-                        best_move = Some(mv);
-                    }
-                    beta = (INF).min(beta + delta);
-                    delta += delta / 2;
-                } else {
-                    if let Some(mv) = m {
-                        best_move = Some(mv);
-                    }
-                    break;
+            // MultiPV: each further line is the best root move not yet listed
+            if num_lines > 1
+                && let Some(mv) = best_move
+            {
+                let mut lines = vec![(search_score, iter_pv.clone())];
+                self.excluded_root_moves.push(mv);
+                while lines.len() < num_lines {
+                    let prev = line_scores.get(lines.len()).copied().unwrap_or(search_score);
+                    let (s, m) = self.aspiration_search(board, d, prev);
+                    let Some(m) = m.filter(|_| !self.should_stop()) else {
+                        break;
+                    };
+                    lines.push((s, self.root_pv(m)));
+                    self.excluded_root_moves.push(m);
                 }
+                self.excluded_root_moves.clear();
 
-                if delta > 3000 {
-                    alpha = -INF;
-                    beta = INF;
+                if self.should_stop() {
+                    break;
                 }
-            }
 
-            if self.should_stop() {
-                break;
+                lines.sort_by_key(|&(s, _)| std::cmp::Reverse(s));
+                line_scores = lines.iter().map(|&(s, _)| s).collect();
+                (iter_score, iter_pv) = lines[0].clone();
+                best_move = iter_pv.first().copied();
+                self.root_lines = lines;
             }
 
-            score = search_score;
+            score = iter_score;
+            self.pv = iter_pv;
+            self.completed_depth = d;
             if let Some(mv) = best_move {
                 if Some(mv) == prev_best_move {
                     stability += 1;
@@ -260,6 +487,7 @@ impl SearchThread {
 
             // Only main thread prints info and manages time
             if self.is_main {
+                self.sync_clock();
                 let time_elapsed = self.start_time.elapsed().as_millis();
                 last_iter_time = time_elapsed.saturating_sub(iter_start_time);
 
@@ -271,38 +499,42 @@ impl SearchThread {
 
                 let total_nodes = self.shared.nodes.load(Ordering::Relaxed);
 
-                let nps = if time_elapsed > 0 {
-                    (total_nodes as u128 * 1000) / time_elapsed
-                } else {
-                    0
-                };
+                if !self.silent {
+                    if self.root_lines.is_empty() {
+                        self.print_info(board, d, None, &self.pv, score, total_nodes, time_elapsed);
+                    } else {
+                        for (rank, (s, pv)) in self.root_lines.iter().enumerate() {
+                            let rank = Some(rank + 1);
+                            self.print_info(board, d, rank, pv, *s, total_nodes, time_elapsed);
+                        }
+                    }
+                }
 
-                print!("info depth {} score ", d);
-                if score > 30000 {
-                    let mate_in = (31000 - score + 1) / 2;
-                    print!("mate {}", mate_in);
-                } else if score < -30000 {
-                    let mate_in = (31000 + score) / 2;
-                    print!("mate -{}", mate_in);
-                } else {
-                    print!("cp {}", score);
-                }
-
-                print!(" pv");
-                let mut pv_board = board.clone();
-                for _ in 0..d {
-                    if let Some((mv, _, _, _)) = self.shared.tt.probe(pv_board.zobrist_hash) {
-                        if mv != 0 {
-                            print!(" {}", moves::format(mv));
-                            pv_board.make_move(mv);
-                        } else {
-                            break;
+                if !self.analyse_mode
+                    && !easy_move_checked
+                    && d >= EASY_MOVE_MIN_DEPTH
+                    && stability >= 2
+                    && self.time_soft_limit != u128::MAX
+                    && !Score(score).is_mate()
+                {
+                    easy_move_checked = true;
+                    if let Some(mv) = best_move
+                        && self.is_easy_move(board, mv, score, d / 2)
+                    {
+                        self.time_soft_limit /= 4;
+                        if !self.silent {
+                            let mv = moves::format_uci(mv, board, self.chess960);
+                            println!("info string easy move {}", mv);
                         }
-                    } else {
-                        break;
                     }
                 }
-                println!(" nodes {} nps {} time {}", total_nodes, nps, time_elapsed);
+
+                if let Some(limit) = self.mate_limit
+                    && Score(score).mate_in().is_some_and(|n| (1..=limit).contains(&n))
+                {
+                    self.set_stop();
+                    break;
+                }
 
                 if time_elapsed >= self.time_hard_limit {
                     self.set_stop();
@@ -329,6 +561,174 @@ impl SearchThread {
         (score, best_move)
     }
 
+    /// The PV of the last root search, or just `best` if the root line was
+    /// cut short (e.g. it never raised alpha).
+    fn root_pv(&self, best: Move) -> Vec<Move> {
+        let pv = &self.pv_table[0][..self.pv_length[0]];
+        if pv.first() == Some(&best) {
+            pv.to_vec()
+        } else {
+            vec![best]
+        }
+    }
+
+    /// Makes `m` followed by the child's line the PV at `ply`.
+    fn update_pv(&mut self, ply: i32, m: Move) {
+        let ply = ply as usize;
+        if ply >= MAX_PLY {
+            return;
+        }
+        let child_len = if ply + 1 < MAX_PLY { self.pv_length[ply + 1] } else { ply + 1 };
+        let (row, rest) = self.pv_table.split_at_mut(ply + 1);
+        row[ply][ply] = m;
+        if child_len > ply + 1 {
+            row[ply][ply + 1..child_len].copy_from_slice(&rest[0][ply + 1..child_len]);
+        }
+        self.pv_length[ply] = child_len.max(ply + 1);
+    }
+
+    /// True for root moves left out by `searchmoves` or already reported
+    /// as an earlier MultiPV line.
+    fn skips_root_move(&self, m: Move) -> bool {
+        self.excluded_root_moves.contains(&m)
+            || (!self.root_moves.is_empty() && !self.root_moves.contains(&m))
+    }
+
+    /// Searches the root at depth `d` with an aspiration window around
+    /// `prev_score`, widening it on fail high or low. Returns the score and
+    /// the last move that failed high or fell inside the window.
+    fn aspiration_search(&mut self, board: &mut Board, d: u8, prev_score: i32) -> (i32, Option<Move>) {
+        let mut delta = 50;
+        let (mut alpha, mut beta) = if d > 4 {
+            ((-INF).max(prev_score - delta), INF.min(prev_score + delta))
+        } else {
+            (-INF, INF)
+        };
+
+        let mut found = None;
+        loop {
+            let (s, m) = self.negamax(board, d, 0, alpha, beta, true, None);
+
+            if self.should_stop() {
+                return (s, found);
+            }
+
+            if s <= alpha {
+                alpha = (-INF).max(alpha - delta);
+                delta += delta / 2;
+            } else if s >= beta {
+                if m.is_some() {
+                    found = m;
+                }
+                beta = INF.min(beta + delta);
+                delta += delta / 2;
+            } else {
+                if m.is_some() {
+                    found = m;
+                }
+                return (s, found);
+            }
+
+            if delta > 3000 {
+                alpha = -INF;
+                beta = INF;
+            }
+        }
+    }
+
+    /// Prints one `info` line, labelled with `multipv` rank if given.
+    #[allow(clippy::too_many_arguments)]
+    fn print_info(
+        &self,
+        board: &Board,
+        d: u8,
+        multipv: Option<usize>,
+        pv: &[Move],
+        score: i32,
+        total_nodes: u64,
+        time_elapsed: u128,
+    ) {
+        let nps = if time_elapsed > 0 {
+            (total_nodes as u128 * 1000) / time_elapsed
+        } else {
+            0
+        };
+
+        print!("info depth {} seldepth {}", d, self.seldepth);
+        if let Some(rank) = multipv {
+            print!(" multipv {}", rank);
+        }
+        print!(" score {}", Score(score));
+
+        if self.show_wdl {
+            let (w, d, l) = wdl_from_score(score);
+            print!(" wdl {} {} {}", w, d, l);
+        }
+
+        print!(" pv");
+        for &mv in pv {
+            print!(" {}", moves::format_uci(mv, board, self.chess960));
+        }
+        println!(
+            " nodes {} nps {} hashfull {} tbhits {} time {}",
+            total_nodes,
+            nps,
+            self.shared.tt.hashfull(),
+            self.shared.tbhits.load(Ordering::Relaxed),
+            time_elapsed
+        );
+    }
+
+    /// Tablebase value of the root from the WDL table, in the same units
+    /// the root DTZ probe reports (`±29000` or `0`).
+    fn root_tb_bound(&self, board: &Board) -> Option<i32> {
+        let tb = syzygy::get_global_syzygy()?;
+        if board.occupancy[2].count_ones() > tb.max_pieces() {
+            return None;
+        }
+        let wdl = syzygy::probe_wdl(board, &tb)?;
+        self.shared.tbhits.fetch_add(1, Ordering::Relaxed);
+        Some(match wdl {
+            pyrrhic_rs::WdlProbeResult::Win => 29000,
+            pyrrhic_rs::WdlProbeResult::Loss => -29000,
+            _ => 0,
+        })
+    }
+
+    /// True when no other legal root move gets within `EASY_MOVE_MARGIN`
+    /// of `best`'s score in a null-window search at `depth`.
+    fn is_easy_move(&mut self, board: &mut Board, best: Move, score: i32, depth: u8) -> bool {
+        let bound = score - EASY_MOVE_MARGIN;
+        let mut move_list = MoveList::new();
+        board.generate_pseudo_legal_moves(&mut move_list);
+
+        for &m in move_list.iter() {
+            if m == best {
+                continue;
+            }
+
+            let undo = board.make_move(m);
+            let us = if board.side_to_move == Color::White {
+                Color::Black
+            } else {
+                Color::White
+            };
+            if board.is_square_attacked(board.king_sq[us as usize], board.side_to_move) {
+                board.unmake_move(m, undo);
+                continue;
+            }
+
+            let (s, _) = self.negamax(board, depth.max(1) - 1, 1, -bound - 1, -bound, true, None);
+            board.unmake_move(m, undo);
+
+            if self.should_stop() || -s > bound {
+                return false;
+            }
+        }
+        true
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn negamax(
         &mut self,
         board: &mut Board,
@@ -337,69 +737,82 @@ impl SearchThread {
         mut alpha: i32,
         beta: i32,
         do_null: bool,
+        exclude: Option<Move>,
     ) -> (i32, Option<Move>) {
+        if let Some(len) = self.pv_length.get_mut(ply as usize) {
+            *len = ply as usize;
+        }
+
         if self.nodes & 2047 == 0 && self.should_stop() {
             return (0, None);
         }
 
         let is_root = ply == 0;
-        if !is_root && (board.halfmove_clock >= 100 || board.is_repetition()) {
-            return (0, None);
+        if !is_root
+            && (board.halfmove_clock >= 100
+                || self.is_repetition(board)
+                || board.is_insufficient_material())
+        {
+            return (self.draw_score(board), None);
         }
 
         // Syzygy WDL Probing (non-root)
-        if !is_root && board.occupancy[2].count_ones() <= 6 {
-            if let Some(tb) = syzygy::get_global_syzygy() {
-                if board.occupancy[2].count_ones() <= tb.max_pieces() {
-                    if let Some(wdl) = syzygy::probe_wdl(board, &tb) {
-                        let tb_score = match wdl {
-                            pyrrhic_rs::WdlProbeResult::Win => 30000 - ply,
-                            pyrrhic_rs::WdlProbeResult::Loss => -30000 + ply,
-                            _ => 0,
-                        };
-
-                        match wdl {
-                            pyrrhic_rs::WdlProbeResult::Win => {
-                                if tb_score >= beta {
-                                    return (tb_score, None);
-                                }
-                            }
-                            pyrrhic_rs::WdlProbeResult::Loss => {
-                                if tb_score <= alpha {
-                                    return (tb_score, None);
-                                }
-                            }
-                            _ => {
-                                if tb_score >= beta || tb_score <= alpha {
-                                    return (tb_score, None);
-                                }
-                            }
-                        }
+        if !is_root
+            && exclude.is_none()
+            && board.occupancy[2].count_ones() <= syzygy::max_pieces()
+            && let Some(tb) = syzygy::get_global_syzygy()
+            && board.occupancy[2].count_ones() <= tb.max_pieces()
+            && let Some(wdl) = syzygy::probe_wdl(board, &tb)
+        {
+            self.shared.tbhits.fetch_add(1, Ordering::Relaxed);
+            // Wins nearer the fifty-move limit are worth less, so the search
+            // prefers lines that enter the table with a capture or pawn move
+            // and a fresh count. Inside a table like KRvK nothing resets the
+            // count and every move costs the same; there progress comes from
+            // the DTZ probe at the root, where one probe covers every move.
+            let clock = board.halfmove_clock as i32;
+            let tb_score = match wdl {
+                pyrrhic_rs::WdlProbeResult::Win => 30000 - ply - clock,
+                pyrrhic_rs::WdlProbeResult::Loss => -30000 + ply + clock,
+                _ => 0,
+            };
+
+            match wdl {
+                pyrrhic_rs::WdlProbeResult::Win => {
+                    if tb_score >= beta {
+                        return (tb_score, None);
+                    }
+                }
+                pyrrhic_rs::WdlProbeResult::Loss => {
+                    if tb_score <= alpha {
+                        return (tb_score, None);
+                    }
+                }
+                _ => {
+                    if tb_score >= beta || tb_score <= alpha {
+                        return (tb_score, None);
                     }
                 }
             }
         }
 
         // Check Extension
-        let them = if board.side_to_move == Color::White {
-            Color::Black
-        } else {
-            Color::White
-        };
-        let in_check = board.is_square_attacked(board.king_sq[board.side_to_move as usize], them);
+        let in_check = board.in_check();
 
         if in_check {
             depth += 1;
         }
 
         if depth == 0 {
-            return (self.quiescence(board, alpha, beta), None);
+            return (self.quiescence(board, ply, 0, alpha, beta), None);
         }
 
         self.increment_nodes();
+        self.seldepth = self.seldepth.max(ply);
 
         // TT Probe
         let mut tt_move = None;
+        let mut tt_entry = None;
         if let Some((mv, sc, d, flag)) = self.shared.tt.probe(board.zobrist_hash) {
             let is_valid = if mv != 0 {
                 let from = moves::from_sq(mv);
@@ -420,8 +833,10 @@ impl SearchThread {
 
             if is_valid {
                 tt_move = if mv != 0 { Some(mv) } else { None };
-                if !is_root && d >= depth {
-                    let tt_score = score_from_tt(sc, ply);
+                let tt_score = Score(sc).from_tt(ply).0;
+                tt_entry = Some((tt_score, d, flag));
+                // A search excluding a move must not reuse the full node's result
+                if !is_root && exclude.is_none() && d >= depth as i32 {
                     match flag {
                         TTFlag::Exact => return (tt_score, tt_move),
                         TTFlag::Beta => {
@@ -444,9 +859,13 @@ impl SearchThread {
         } else {
             -INF
         };
+        if let Some(slot) = self.eval_stack.get_mut(ply as usize) {
+            *slot = static_eval;
+        }
+        let improving = self.is_improving(ply, static_eval);
 
         // Null Move Pruning
-        if do_null && !in_check && !is_root && depth >= 3 {
+        if do_null && !in_check && !is_root && exclude.is_none() && depth >= 3 {
             let dominated_by_pawns = (board.pieces[PieceType::Knight as usize]
                 [board.side_to_move as usize]
                 | board.pieces[PieceType::Bishop as usize][board.side_to_move as usize]
@@ -457,11 +876,14 @@ impl SearchThread {
             if !dominated_by_pawns && static_eval >= beta {
                 let r = if depth > 6 { 3 } else { 2 };
                 let old_ep = board.make_null_move();
+                let old_pieces = self.prev_pieces;
+                self.prev_pieces = [None, old_pieces[0]];
                 let (score, _) =
-                    self.negamax(board, depth - 1 - r, ply + 1, -beta, -beta + 1, false);
+                    self.negamax(board, depth - 1 - r, ply + 1, -beta, -beta + 1, false, None);
+                self.prev_pieces = old_pieces;
                 board.unmake_null_move(old_ep);
                 let null_score = -score;
-                if null_score >= beta && null_score < 30000 {
+                if null_score >= beta && null_score < MATE_BOUND {
                     return (beta, None);
                 }
             }
@@ -469,67 +891,79 @@ impl SearchThread {
 
         // Reverse Futility Pruning
         if !is_root && !in_check && depth <= 6 {
-            let margin = 80 * (depth as i32);
+            let margin = self.params.rfp_margin * (depth as i32);
             if static_eval - margin >= beta {
                 return (static_eval - margin, None);
             }
         }
 
+        // ProbCut: only non-PV nodes, and not when the TT already says a
+        // search this deep stays below the raised beta
+        if !is_root
+            && !in_check
+            && exclude.is_none()
+            && depth >= PROBCUT_MIN_DEPTH
+            && beta - alpha == 1
+            && beta.abs() < MATE_BOUND
+        {
+            let probcut_beta = beta + self.params.probcut_margin;
+            let tt_rules_out = tt_entry.is_some_and(|(s, d, _)| {
+                d + PROBCUT_REDUCTION as i32 > depth as i32 && s < probcut_beta
+            });
+            if !tt_rules_out
+                && let Some(score) = self.probcut(board, depth, ply, probcut_beta, static_eval)
+            {
+                return (score, None);
+            }
+        }
+
         // IID
         if tt_move.is_none() && depth >= 4 {
             let iid_depth = depth - 2;
-            let (_, iid_move) = self.negamax(board, iid_depth, ply, alpha, beta, false);
+            let (_, iid_move) = self.negamax(board, iid_depth, ply, alpha, beta, false, None);
             if let Some(m) = iid_move {
                 tt_move = Some(m);
             }
         }
 
-        let mut move_list = MoveList::new();
-        board.generate_pseudo_legal_moves(&mut move_list);
-
-        // Score moves - add thread_id for slight move ordering variation (Lazy SMP)
-        let mut move_scores = [0i32; 256];
-        let move_slice = move_list.as_mut_slice();
-        for i in 0..move_slice.len() {
-            let m = move_slice[i];
-            if Some(m) == tt_move {
-                move_scores[i] = 2000000000;
-            } else if moves::is_capture(m) {
-                move_scores[i] = 1000000 + self.get_mvv_lva(m, board);
-            } else {
-                if ply < 64 {
-                    if self.killers[ply as usize][0] == Some(m) {
-                        move_scores[i] = 900000;
-                    } else if self.killers[ply as usize][1] == Some(m) {
-                        move_scores[i] = 800000;
-                    }
-                }
+        let pinned = if in_check {
+            0
+        } else {
+            movegen::pinned_pieces(board, board.side_to_move)
+        };
 
-                if let Some(prev) = self.prev_move {
-                    let prev_pt = board.piece_type_on(moves::to_sq(prev));
-                    if let Some(ppt) = prev_pt {
-                        let prev_to = moves::to_sq(prev);
-                        if self.counter_moves[ppt as usize][prev_to as usize] == Some(m) {
-                            move_scores[i] = 700000;
-                        }
-                    }
-                }
-                if move_scores[i] == 0 {
-                    if let Some(pt) = board.piece_type_on(moves::from_sq(m)) {
-                        let c = board.side_to_move;
-                        let to = moves::to_sq(m);
-                        move_scores[i] = self.history[pt as usize][c as usize][to as usize];
-                        // Add small thread-based variation for Lazy SMP diversity
-                        move_scores[i] += ((self.thread_id as i32) * 7) % 13;
-                    }
-                }
+        // Singular Extension: verify that every other move falls well short of
+        // the TT score. Not in check, which is already extended.
+        let mut singular_extension = 0;
+        if let (Some(tt_m), Some((tt_score, tt_depth, tt_flag))) = (tt_move, tt_entry)
+            && !is_root
+            && !in_check
+            && exclude.is_none()
+            && depth >= SINGULAR_MIN_DEPTH
+            && tt_flag != TTFlag::Alpha
+            && tt_depth + 3 >= depth as i32
+            && tt_score.abs() < MATE_BOUND
+        {
+            let singular_beta = tt_score - SINGULAR_MARGIN * depth as i32;
+            let (score, _) = self.negamax(
+                board,
+                (depth - 1) / 2,
+                ply,
+                singular_beta - 1,
+                singular_beta,
+                false,
+                Some(tt_m),
+            );
+            if score < singular_beta {
+                singular_extension = 1;
             }
         }
 
         // Futility Pruning Setup
         let mut futility_pruning = false;
         if !is_root && !in_check && depth <= 3 && alpha < beta - 1 {
-            let margin = 150 * (depth as i32);
+            let margin = self.params.futility_margin * (depth as i32)
+                + improving as i32 * self.params.futility_margin / 2;
             if static_eval + margin <= alpha {
                 futility_pruning = true;
             }
@@ -543,23 +977,18 @@ impl SearchThread {
         let mut searched_quiets: [Move; 64] = [0; 64];
         let mut quiet_count = 0;
 
-        for i in 0..move_list.len() {
-            // Selection Sort
-            let mut best_pick_score = i32::MIN;
-            let mut best_pick_idx = i;
-            for j in i..move_list.len() {
-                if move_scores[j] > best_pick_score {
-                    best_pick_score = move_scores[j];
-                    best_pick_idx = j;
-                }
-            }
-            {
-                let moves_slice = move_list.as_mut_slice();
-                moves_slice.swap(i, best_pick_idx);
-                move_scores.swap(i, best_pick_idx);
-            }
+        let killers = if ply < 64 { self.killers[ply as usize] } else { [None; 2] };
+        let counter = self.prev_move.and_then(|prev| {
+            let prev_to = moves::to_sq(prev);
+            let prev_pt = board.piece_type_on(prev_to)?;
+            self.counter_moves[prev_pt as usize][prev_to as usize]
+        });
+        let mut picker = MovePicker::new(tt_move, killers, counter);
 
-            let m = move_list.get(i);
+        while let Some(m) = picker.next(board, |m| self.quiet_score(board, m)) {
+            if (is_root && self.skips_root_move(m)) || Some(m) == exclude {
+                continue;
+            }
 
             // Futility Pruning Check
             if futility_pruning && !moves::is_capture(m) && !moves::is_promotion(m) {
@@ -568,15 +997,10 @@ impl SearchThread {
             }
 
             // LMP
-            let lmp_threshold = if depth <= 1 {
-                3
-            } else if depth <= 2 {
-                6
-            } else if depth <= 3 {
-                10
-            } else {
-                15
-            };
+            let mut lmp_threshold = self.params.lmp_thresholds[(depth.clamp(1, 4) - 1) as usize];
+            if !improving {
+                lmp_threshold = lmp_threshold * 2 / 3;
+            }
             if !is_root
                 && !in_check
                 && depth <= 4
@@ -589,34 +1013,54 @@ impl SearchThread {
 
             // SEE Pruning
             if !is_root && !in_check && depth >= 1 && moves::is_capture(m) && legal_moves > 0 {
-                let see_value = see::see(board, m);
-                let threshold = -20 * (depth as i32);
-                if see_value < threshold {
+                let threshold = -self.params.see_margin * (depth as i32);
+                if !see::see_ge(board, m, threshold) {
                     continue;
                 }
             }
 
+            let needs_check = movegen::needs_legality_check(board, m, pinned, in_check);
             let undo = board.make_move(m);
+            self.shared.tt.prefetch(board.zobrist_hash);
 
-            let us = if board.side_to_move == Color::White {
-                Color::Black
-            } else {
-                Color::White
-            };
+            if needs_check {
+                let us = if board.side_to_move == Color::White {
+                    Color::Black
+                } else {
+                    Color::White
+                };
 
-            if board.is_square_attacked(board.king_sq[us as usize], board.side_to_move) {
-                board.unmake_move(m, undo);
-                continue;
+                if board.is_square_attacked(board.king_sq[us as usize], board.side_to_move) {
+                    board.unmake_move(m, undo);
+                    continue;
+                }
             }
 
             legal_moves += 1;
 
+            if is_root
+                && self.is_main
+                && !self.silent
+                && self.start_time.elapsed().as_millis() >= CURRMOVE_MIN_TIME
+            {
+                println!(
+                    "info depth {} currmove {} currmovenumber {}",
+                    self.completed_depth + 1,
+                    moves::format_uci(m, board, self.chess960),
+                    legal_moves
+                );
+            }
+
             let mut score;
             let old_prev = self.prev_move;
             self.prev_move = Some(m);
+            let old_pieces = self.prev_pieces;
+            let to = moves::to_sq(m);
+            self.prev_pieces = [board.piece_type_on(to).map(|pt| (pt, to)), old_pieces[0]];
+            let new_depth = if Some(m) == tt_move { depth - 1 + singular_extension } else { depth - 1 };
 
             if legal_moves == 1 {
-                let (s, _) = self.negamax(board, depth - 1, ply + 1, -beta, -alpha, true);
+                let (s, _) = self.negamax(board, new_depth, ply + 1, -beta, -alpha, true, None);
                 score = -s;
             } else {
                 // LMR
@@ -627,35 +1071,32 @@ impl SearchThread {
                     && !moves::is_promotion(m)
                     && !in_check
                 {
-                    let lmr_depth = (depth as f64).ln();
-                    let lmr_move = (legal_moves as f64).ln();
-                    reduction = (1.0 + lmr_depth * lmr_move / 2.0) as u8;
-                    if reduction >= depth {
-                        reduction = depth - 1;
-                    }
+                    reduction = lmr_table()[(depth as usize).min(63)][(legal_moves as usize).min(63)];
                 }
 
                 let (s, _) = self.negamax(
                     board,
-                    depth - 1 - reduction,
+                    new_depth - reduction,
                     ply + 1,
                     -alpha - 1,
                     -alpha,
                     true,
+                    None,
                 );
                 score = -s;
 
                 if score > alpha && reduction > 0 {
-                    let (s, _) = self.negamax(board, depth - 1, ply + 1, -alpha - 1, -alpha, true);
+                    let (s, _) = self.negamax(board, new_depth, ply + 1, -alpha - 1, -alpha, true, None);
                     score = -s;
                 }
                 if score > alpha && score < beta {
-                    let (s, _) = self.negamax(board, depth - 1, ply + 1, -beta, -alpha, true);
+                    let (s, _) = self.negamax(board, new_depth, ply + 1, -beta, -alpha, true, None);
                     score = -s;
                 }
             }
 
             self.prev_move = old_prev;
+            self.prev_pieces = old_pieces;
             board.unmake_move(m, undo);
 
             if self.should_stop() {
@@ -672,6 +1113,7 @@ impl SearchThread {
                 best_move = Some(m);
                 if score > alpha {
                     alpha = score;
+                    self.update_pv(ply, m);
                     if !moves::is_capture(m) {
                         let pt = board.piece_type_on(moves::from_sq(m)).unwrap();
                         let c = board.side_to_move;
@@ -699,6 +1141,7 @@ impl SearchThread {
                     if self.history[pt as usize][c as usize][to as usize] > 20000 {
                         self.history[pt as usize][c as usize][to as usize] /= 2;
                     }
+                    self.update_continuation(pt, to, (depth as i32) * (depth as i32));
 
                     // History malus for failed quiets
                     for j in 0..quiet_count.saturating_sub(1) {
@@ -713,6 +1156,7 @@ impl SearchThread {
                                 self.history[pt as usize][board.side_to_move as usize]
                                     [to as usize] = -20000;
                             }
+                            self.update_continuation(pt, to, -(depth as i32) * (depth as i32));
                         }
                     }
 
@@ -734,12 +1178,14 @@ impl SearchThread {
         }
 
         if legal_moves == 0 {
-            if in_check {
-                return (-MATE_SCORE + ply, None);
+            if exclude.is_some() {
+                return (alpha, None);
+            } else if in_check {
+                return (Score::mated_in_plies(ply).0, None);
             } else if skipped_moves > 0 {
                 return (alpha, None);
             } else {
-                return (0, None);
+                return (self.draw_score(board), None);
             }
         }
 
@@ -751,46 +1197,225 @@ impl SearchThread {
             TTFlag::Exact
         };
 
-        self.shared.tt.store(
-            board.zobrist_hash,
-            best_move,
-            score_to_tt(best_score, ply),
-            depth,
-            flag,
-        );
+        // A root or node searched with moves left out is not the real result
+        let partial = exclude.is_some()
+            || (is_root && !(self.excluded_root_moves.is_empty() && self.root_moves.is_empty()));
+        if !partial {
+            self.shared.tt.store(
+                board.zobrist_hash,
+                best_move,
+                Score(best_score).to_tt(ply).0,
+                depth as i32,
+                flag,
+            );
+        }
         (best_score, best_move)
     }
 
-    fn quiescence(&mut self, board: &mut Board, mut alpha: i32, beta: i32) -> i32 {
+    /// Searches the captures that win at least `probcut_beta - static_eval`
+    /// by SEE, first in quiescence and then at reduced depth. Returns the
+    /// score of the first one that holds at or above `probcut_beta`.
+    fn probcut(
+        &mut self,
+        board: &mut Board,
+        depth: u8,
+        ply: i32,
+        probcut_beta: i32,
+        static_eval: i32,
+    ) -> Option<i32> {
+        let mut move_list = MoveList::new();
+        movegen::generate_captures(board, &mut move_list);
+
+        for &m in move_list.iter() {
+            if !see::see_ge(board, m, probcut_beta - static_eval) {
+                continue;
+            }
+
+            let undo = board.make_move(m);
+            let us = if board.side_to_move == Color::White {
+                Color::Black
+            } else {
+                Color::White
+            };
+            if board.is_square_attacked(board.king_sq[us as usize], board.side_to_move) {
+                board.unmake_move(m, undo);
+                continue;
+            }
+
+            let old_prev = self.prev_move;
+            self.prev_move = Some(m);
+            let old_pieces = self.prev_pieces;
+            let to = moves::to_sq(m);
+            self.prev_pieces = [board.piece_type_on(to).map(|pt| (pt, to)), old_pieces[0]];
+
+            // Quiescence rejects most candidates before the costlier search
+            let mut score =
+                -self.quiescence(board, ply + 1, 0, -probcut_beta, -probcut_beta + 1);
+            if score >= probcut_beta {
+                let (s, _) = self.negamax(
+                    board,
+                    depth - PROBCUT_REDUCTION,
+                    ply + 1,
+                    -probcut_beta,
+                    -probcut_beta + 1,
+                    true,
+                    None,
+                );
+                score = -s;
+            }
+
+            self.prev_move = old_prev;
+            self.prev_pieces = old_pieces;
+            board.unmake_move(m, undo);
+
+            if self.should_stop() {
+                return None;
+            }
+            if score >= probcut_beta {
+                return Some(score);
+            }
+        }
+        None
+    }
+
+    /// Value of a draw to the side to move: `-contempt` for the root side,
+    /// `contempt` for its opponent.
+    fn draw_score(&self, board: &Board) -> i32 {
+        if board.side_to_move == self.root_color {
+            -self.contempt
+        } else {
+            self.contempt
+        }
+    }
+
+    /// True if the position occurred twice before, counting both the moves
+    /// searched since the root and the game played before it.
+    fn is_repetition(&self, board: &Board) -> bool {
+        let mut count = 0;
+        for undo in board.history[self.root_history_len..].iter().rev() {
+            if undo.old_zobrist_hash == board.zobrist_hash {
+                count += 1;
+            }
+            if undo.old_halfmove_clock == 0 {
+                return count >= 2;
+            }
+        }
+        count += self.game_history.iter().filter(|&&key| key == board.zobrist_hash).count();
+        count >= 2
+    }
+
+    /// True if `static_eval` beats our static eval two plies back. In check
+    /// nothing is improving; when the earlier node was in check, the one
+    /// four plies back is used, and with no eval to compare at all we
+    /// assume improving so as not to prune more than before.
+    fn is_improving(&self, ply: i32, static_eval: i32) -> bool {
+        if static_eval == -INF {
+            return false;
+        }
+        let earlier = |back: i32| {
+            let ply = ply - back;
+            if ply < 0 {
+                return None;
+            }
+            self.eval_stack.get(ply as usize).copied().filter(|&e| e != -INF)
+        };
+        earlier(2).or_else(|| earlier(4)).is_none_or(|e| static_eval > e)
+    }
+
+    /// Searches captures, and quiet checks in the first `QS_CHECK_PLIES`
+    /// plies (`qs_ply` counts from 0), until the position is quiet. In
+    /// check there is no standing pat: every evasion is searched.
+    fn quiescence(
+        &mut self,
+        board: &mut Board,
+        ply: i32,
+        qs_ply: u8,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
         if self.nodes & 2047 == 0 && self.should_stop() {
             return 0;
         }
 
         self.increment_nodes();
+        self.seldepth = self.seldepth.max(ply);
 
-        let stand_pat = eval::evaluate(board);
-        if stand_pat >= beta {
-            return beta;
+        // Captures can leave too little material to mate
+        if board.is_insufficient_material() {
+            return self.draw_score(board);
         }
 
-        // Delta Pruning
-        let delta = 975;
-        if stand_pat + delta < alpha {
-            return alpha;
+        // Entries are depth 0 while quiet checks are searched and -1 after,
+        // and only one at least as deep answers this node. Negamax only cuts
+        // on entries of depth 1 or more, so the ones stored here never stand
+        // in for a main search. They carry no move either: a capture as the
+        // TT move would skip IID and lead the move ordering there.
+        let tt_depth = if qs_ply < QS_CHECK_PLIES { 0 } else { -1 };
+        let mut tt_move = None;
+        if let Some((mv, sc, d, flag)) = self.shared.tt.probe(board.zobrist_hash) {
+            let tt_score = Score(sc).from_tt(ply).0;
+            if d >= tt_depth {
+                match flag {
+                    TTFlag::Exact => return tt_score,
+                    TTFlag::Beta if tt_score >= beta => return tt_score,
+                    TTFlag::Alpha if tt_score <= alpha => return tt_score,
+                    _ => {}
+                }
+            }
+            if mv != 0 {
+                tt_move = Some(mv);
+            }
         }
 
-        if stand_pat > alpha {
-            alpha = stand_pat;
+        let in_check = board.in_check();
+        let alpha_orig = alpha;
+        if !in_check {
+            let stand_pat = eval::evaluate_lazy(board, alpha, beta);
+            if stand_pat >= beta {
+                let stored = Score(beta).to_tt(ply).0;
+                self.shared.tt.store(board.zobrist_hash, None, stored, tt_depth, TTFlag::Beta);
+                return beta;
+            }
+
+            // Delta Pruning
+            let delta = 975;
+            if stand_pat + delta < alpha {
+                return alpha;
+            }
+
+            if stand_pat > alpha {
+                alpha = stand_pat;
+            }
         }
 
         let mut move_list = MoveList::new();
-        movegen::generate_captures(board, &mut move_list);
+        if in_check {
+            board.generate_pseudo_legal_moves(&mut move_list);
+        } else {
+            movegen::generate_captures(board, &mut move_list);
+        }
+        if qs_ply < QS_CHECK_PLIES && !in_check {
+            let mut quiets = MoveList::new();
+            movegen::generate_quiets(board, &mut quiets);
+            for &m in quiets.iter() {
+                if board.gives_check(m) {
+                    move_list.push(m);
+                }
+            }
+        }
 
-        let mut move_scores = [0; 256];
+        let mut move_scores = [0; moves::MAX_MOVES];
         for i in 0..move_list.len() {
             let m = move_list.get(i);
-            move_scores[i] = 1000000 + self.get_mvv_lva(m, board);
+            move_scores[i] = if Some(m) == tt_move {
+                2000000000
+            } else if moves::is_capture(m) {
+                1000000 + movepick::mvv_lva(board, m)
+            } else {
+                0
+            };
         }
+        let mut legal_moves = 0;
 
         for i in 0..move_list.len() {
             let mut best_pick_score = i32::MIN;
@@ -809,7 +1434,7 @@ impl SearchThread {
 
             let m = move_list.get(i);
 
-            if see::see(board, m) < -50 {
+            if !in_check && !see::see_ge(board, m, -50) {
                 continue;
             }
             let undo = board.make_move(m);
@@ -825,16 +1450,31 @@ impl SearchThread {
                 continue;
             }
 
-            let score = -self.quiescence(board, -beta, -alpha);
+            legal_moves += 1;
+
+            let score = -self.quiescence(board, ply + 1, qs_ply + 1, -beta, -alpha);
             board.unmake_move(m, undo);
 
+            if self.should_stop() {
+                return 0;
+            }
+
             if score >= beta {
+                let stored = Score(beta).to_tt(ply).0;
+                self.shared.tt.store(board.zobrist_hash, None, stored, tt_depth, TTFlag::Beta);
                 return beta;
             }
             if score > alpha {
                 alpha = score;
             }
         }
+
+        if in_check && legal_moves == 0 {
+            return Score::mated_in_plies(ply).0;
+        }
+
+        let flag = if alpha > alpha_orig { TTFlag::Exact } else { TTFlag::Alpha };
+        self.shared.tt.store(board.zobrist_hash, None, Score(alpha).to_tt(ply).0, tt_depth, flag);
         alpha
     }
 
@@ -846,88 +1486,539 @@ impl SearchThread {
                 }
             }
         }
+        for table in &mut self.continuation_history {
+            for entry in table.iter_mut().flatten().flatten() {
+                *entry /= 2;
+            }
+        }
     }
 
-    fn get_mvv_lva(&self, m: Move, board: &Board) -> i32 {
-        let to = moves::to_sq(m);
-        let from = moves::from_sq(m);
-        let victim = board.piece_type_on(to).unwrap_or(PieceType::Pawn);
-        let attacker = board.piece_type_on(from).unwrap();
-        let vv = match victim {
-            PieceType::Pawn => 1,
-            PieceType::Knight => 2,
-            PieceType::Bishop => 3,
-            PieceType::Rook => 4,
-            PieceType::Queen => 5,
-            PieceType::King => 6,
-        };
-        let av = match attacker {
-            PieceType::Pawn => 1,
-            PieceType::Knight => 2,
-            PieceType::Bishop => 3,
-            PieceType::Rook => 4,
-            PieceType::Queen => 5,
-            PieceType::King => 6,
-        };
-        10 * vv - av + 10000
+    /// Continuation history of a quiet move after the last two moves.
+    fn continuation_score(&self, pt: PieceType, to: Square) -> i32 {
+        let mut score = 0;
+        for (table, prev) in self.continuation_history.iter().zip(self.prev_pieces) {
+            if let Some((prev_pt, prev_to)) = prev {
+                score += table[prev_pt as usize * 64 + prev_to as usize][pt as usize][to as usize];
+            }
+        }
+        score
     }
-}
 
-fn score_to_tt(score: i32, ply: i32) -> i32 {
-    if score > 30000 {
-        score + ply
-    } else if score < -30000 {
-        score - ply
-    } else {
+    fn update_continuation(&mut self, pt: PieceType, to: Square, bonus: i32) {
+        for (table, prev) in self.continuation_history.iter_mut().zip(self.prev_pieces) {
+            if let Some((prev_pt, prev_to)) = prev {
+                let entry = &mut table[prev_pt as usize * 64 + prev_to as usize][pt as usize][to as usize];
+                *entry = (*entry + bonus).clamp(-20000, 20000);
+            }
+        }
+    }
+
+    /// History score of a quiet move, with a little per-thread noise for
+    /// Lazy SMP diversity.
+    fn quiet_score(&self, board: &Board, m: Move) -> i32 {
+        let Some(pt) = board.piece_type_on(moves::from_sq(m)) else {
+            return 0;
+        };
+        let to = moves::to_sq(m);
+        let mut score = self.history[pt as usize][board.side_to_move as usize][to as usize]
+            + self.continuation_score(pt, to) * self.params.continuation_weight / 100;
+        if self.jitter {
+            score += ((self.thread_id as i32) * 7) % 13;
+        }
         score
     }
 }
 
-fn score_from_tt(score: i32, ply: i32) -> i32 {
-    if score > 30000 {
-        score - ply
-    } else if score < -30000 {
-        score + ply
-    } else {
-        score
+/// Converts a centipawn score into win/draw/loss permill for `info ... wdl`.
+/// Known wins (mate or tablebase, reported as >= 29000) are exact.
+pub fn wdl_from_score(score: i32) -> (u32, u32, u32) {
+    if score >= 29000 {
+        return (1000, 0, 0);
+    }
+    if score <= -29000 {
+        return (0, 0, 1000);
     }
+
+    let cp = score as f64;
+    let win = 1000.0 / (1.0 + ((WDL_MIDPOINT - cp) / WDL_SCALE).exp());
+    let loss = 1000.0 / (1.0 + ((WDL_MIDPOINT + cp) / WDL_SCALE).exp());
+    let w = win.round() as u32;
+    let l = loss.round() as u32;
+    (w, 1000 - w - l, l)
 }
 
+/// Single-threaded searcher with its own transposition table and book.
+/// It drives the same `SearchThread` used by the Lazy SMP pool.
 pub struct Searcher {
-    pub nodes: u64,
-    pub start_time: Instant,
+    pub thread: SearchThread,
+    pub book: OpeningBook,
     pub time_soft_limit: u128,
     pub time_hard_limit: u128,
-    pub stop: bool,
-    pub tt: crate::tt::TranspositionTable,
-    pub book: OpeningBook,
-    pub killers: [[Option<Move>; 2]; 64],
-    pub history: [[[i32; 64]; 2]; 6],
-    pub counter_moves: [[Option<Move>; 64]; 6],
-    pub prev_move: Option<Move>,
 }
 
 impl Searcher {
     pub fn new() -> Self {
-        let book = OpeningBook::new("Perfect2023.bin");
-        if book.file.is_some() {
+        let book = OpeningBook::new(DEFAULT_BOOK);
+        if book.is_loaded() {
             println!("info string Opening book loaded successfully");
         } else {
             println!("info string Warning: book.bin not found");
         }
 
+        let shared = Arc::new(SharedState::new(64));
+
         Self {
-            nodes: 0,
-            start_time: Instant::now(),
-            time_soft_limit: 0,
-            time_hard_limit: 0,
-            stop: false,
-            tt: crate::tt::TranspositionTable::new(64),
+            thread: SearchThread::new(0, shared, true),
             book,
-            killers: [[None; 2]; 64],
-            history: [[[0; 64]; 2]; 6],
-            counter_moves: [[None; 64]; 6],
-            prev_move: None,
+            time_soft_limit: u128::MAX,
+            time_hard_limit: u128::MAX,
         }
     }
+
+    pub fn search(&mut self, board: &mut Board, depth: u8) -> (i32, Option<Move>) {
+        let shared = &self.thread.shared;
+        shared.stop.store(false, Ordering::SeqCst);
+        shared.nodes.store(0, Ordering::Relaxed);
+        shared.tt.new_search();
+
+        self.thread.time_soft_limit = self.time_soft_limit;
+        self.thread.time_hard_limit = self.time_hard_limit;
+        self.thread.game_history = board.repetition_keys();
+        self.thread.search(board, depth)
+    }
+
+    pub fn nodes(&self) -> u64 {
+        self.thread.shared.nodes.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn searcher_finds_legal_move_at_startpos() {
+        movegen::init();
+        let mut board =
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let mut searcher = Searcher::new();
+        let (_, best_move) = searcher.search(&mut board, 4);
+
+        let best_move = best_move.expect("search should return a move");
+        let mut move_list = MoveList::new();
+        board.generate_pseudo_legal_moves(&mut move_list);
+        assert!(move_list.iter().any(|&m| m == best_move));
+        assert!(searcher.nodes() > 0);
+    }
+
+    #[test]
+    fn easy_move_on_free_queen_capture() {
+        movegen::init();
+        let shared = Arc::new(SharedState::new(16));
+        let mut thread = SearchThread::new(0, shared, true);
+        thread.silent = true;
+
+        let mut board = Board::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let (score, best_move) = thread.search(&mut board, 6);
+        let best_move = best_move.unwrap();
+        assert_eq!(moves::format(best_move), "d2d5");
+        assert!(thread.is_easy_move(&mut board, best_move, score, 3));
+
+        let mut board =
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let (score, best_move) = thread.search(&mut board, 6);
+        assert!(!thread.is_easy_move(&mut board, best_move.unwrap(), score, 3));
+    }
+
+    #[test]
+    fn multipv_ranks_distinct_root_moves() {
+        movegen::init();
+        let shared = Arc::new(SharedState::new(16));
+        let mut thread = SearchThread::new(0, shared, true);
+        thread.silent = true;
+        thread.multipv = 3;
+
+        let mut board = Board::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let (score, best_move) = thread.search(&mut board, 5);
+        let lines = thread.root_lines.clone();
+        assert_eq!(lines.len(), 3);
+        assert_eq!((score, best_move), (lines[0].0, Some(lines[0].1[0])));
+        assert_eq!(moves::format(lines[0].1[0]), "d2d5");
+        assert!(lines.windows(2).all(|w| w[0].0 >= w[1].0 && w[0].1[0] != w[1].1[0]));
+        assert_ne!(lines[0].1[0], lines[2].1[0]);
+        assert_eq!(thread.pv, lines[0].1);
+
+        // Capped at the number of legal moves
+        thread.multipv = 10;
+        let mut board = Board::from_fen("7k/8/8/8/8/8/r7/K7 w - - 0 1").unwrap();
+        thread.search(&mut board, 4);
+        assert_eq!(thread.root_lines.len(), 2);
+
+        thread.multipv = 1;
+        let mut board = Board::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        thread.search(&mut board, 5);
+        assert!(thread.root_lines.is_empty());
+    }
+
+    #[test]
+    fn seldepth_reaches_past_the_nominal_depth() {
+        movegen::init();
+        let shared = Arc::new(SharedState::new(16));
+        let mut thread = SearchThread::new(0, shared, true);
+        thread.silent = true;
+
+        let kiwi = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut board = Board::from_fen(kiwi).unwrap();
+        thread.search(&mut board, 5);
+        assert!(thread.seldepth > 5 + 2, "seldepth {}", thread.seldepth);
+
+        // Reset for each search
+        let mut board = Board::from_fen("7k/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        thread.search(&mut board, 2);
+        assert!(thread.seldepth <= 3, "seldepth {}", thread.seldepth);
+    }
+
+    #[test]
+    fn excluded_move_search_fails_low() {
+        movegen::init();
+        let shared = Arc::new(SharedState::new(16));
+        let mut thread = SearchThread::new(0, shared.clone(), true);
+        thread.silent = true;
+
+        // a2a3 is White's only legal move
+        let mut board = Board::from_fen("7k/8/8/8/p7/8/P4q2/7K w - - 0 1").unwrap();
+        let only = moves::new(8, 16, moves::QUIET_MOVE_FLAG);
+        let (score, best) = thread.negamax(&mut board, 2, 1, -100, 100, false, Some(only));
+        assert_eq!((score, best), (-100, None));
+        assert!(shared.tt.probe(board.zobrist_hash).is_none());
+
+        let (_, best) = thread.negamax(&mut board, 2, 1, -INF, INF, false, None);
+        assert_eq!(best, Some(only));
+    }
+
+    #[test]
+    fn continuation_history_follows_the_previous_moves() {
+        let shared = Arc::new(SharedState::new(16));
+        let mut thread = SearchThread::new(0, shared, true);
+
+        // After 1. e4 e5, reward Nf3
+        thread.prev_pieces = [Some((PieceType::Pawn, 36)), Some((PieceType::Pawn, 28))];
+        thread.update_continuation(PieceType::Knight, 21, 50);
+        assert_eq!(thread.continuation_score(PieceType::Knight, 21), 100);
+        assert_eq!(thread.continuation_score(PieceType::Knight, 18), 0);
+
+        // Only the entry keyed by the matching move counts
+        thread.prev_pieces = [Some((PieceType::Pawn, 35)), Some((PieceType::Pawn, 28))];
+        assert_eq!(thread.continuation_score(PieceType::Knight, 21), 50);
+        thread.prev_pieces = [None, None];
+        assert_eq!(thread.continuation_score(PieceType::Knight, 21), 0);
+
+        thread.age_history();
+        thread.prev_pieces = [Some((PieceType::Pawn, 36)), Some((PieceType::Pawn, 28))];
+        assert_eq!(thread.continuation_score(PieceType::Knight, 21), 50);
+    }
+
+    #[test]
+    fn searches_the_richest_known_position() {
+        movegen::init();
+        // 218 legal moves, filling most of every move and score list
+        let mut board = Board::from_fen("R6R/3Q4/1Q4Q1/4Q3/2Q4Q/Q4Q2/pp1Q4/kBNN1KB1 w - - 0 1").unwrap();
+        let shared = Arc::new(SharedState::new(16));
+        let mut thread = SearchThread::new(0, shared, true);
+        thread.silent = true;
+        let (score, best) = thread.search(&mut board, 3);
+        assert!(best.is_some());
+        assert!(score > MATE_BOUND, "{}", score);
+    }
+
+    #[test]
+    fn probcut_keeps_the_best_move() {
+        movegen::init();
+
+        let best = |fen: &str, probcut_margin: i32| {
+            let shared = Arc::new(SharedState::new(16));
+            let mut thread = SearchThread::new(0, shared, true);
+            thread.silent = true;
+            thread.params.probcut_margin = probcut_margin;
+            let mut board = Board::from_fen(fen).unwrap();
+            thread.search(&mut board, 7).1.map(moves::format)
+        };
+
+        // The SEE test positions, searched with ProbCut and with a margin
+        // too large for any capture to reach
+        for fen in [
+            "3rk3/8/8/3n4/8/8/3R4/3RK3 w - - 0 1",
+            "3rk3/8/8/3p4/8/8/3R4/3QK3 w - - 0 1",
+            "4k3/2p5/8/3pP3/8/8/8/4K3 w - d6 0 2",
+            "4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1",
+            "4k3/8/4p3/3p4/8/8/3Q4/4K3 w - - 0 1",
+            "4k3/8/8/4n3/8/8/4N3/4K3 w - - 0 1",
+        ] {
+            assert_eq!(best(fen, 200), best(fen, 100_000), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn improving_compares_with_two_plies_back() {
+        movegen::init();
+        let shared = Arc::new(SharedState::new(16));
+        let mut thread = SearchThread::new(0, shared, true);
+
+        thread.eval_stack[..3].copy_from_slice(&[20, -40, 30]);
+        assert!(thread.is_improving(2, 30));
+        assert!(!thread.is_improving(2, 10));
+        assert!(!thread.is_improving(2, -INF));
+
+        // Two plies back was in check: fall back to four plies back
+        thread.eval_stack[..5].copy_from_slice(&[20, -40, -INF, 0, 10]);
+        assert!(!thread.is_improving(4, 10));
+        assert!(thread.is_improving(4, 25));
+        assert!(thread.is_improving(1, -100));
+
+        // A new search starts from an empty stack
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        thread.silent = true;
+        thread.search(&mut board, 1);
+        assert!(thread.eval_stack[2..].iter().all(|&e| e == -INF));
+    }
+
+    #[test]
+    fn lmr_table_matches_the_formula() {
+        let table = lmr_table();
+        for depth in 3..64u8 {
+            for move_number in 2..64 {
+                let r = (1.0 + (depth as f64).ln() * (move_number as f64).ln() / 2.0) as u8;
+                assert_eq!(table[depth as usize][move_number], r.min(depth - 1));
+            }
+        }
+    }
+
+    #[test]
+    fn quiescence_stores_and_reuses_its_result() {
+        movegen::init();
+        let shared = Arc::new(SharedState::new(16));
+        let mut thread = SearchThread::new(0, Arc::clone(&shared), true);
+
+        // White can win a knight: the result is exact and stored at depth 0
+        let mut board = Board::from_fen("4k3/8/8/3n4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let score = thread.quiescence(&mut board, 0, 0, -INF, INF);
+        let (_, stored, depth, flag) = shared.tt.probe(board.zobrist_hash).unwrap();
+        assert_eq!((stored, depth), (score, 0));
+        assert!(flag == TTFlag::Exact);
+
+        let nodes = thread.nodes;
+        assert_eq!(thread.quiescence(&mut board, 0, 0, -INF, INF), score);
+        assert_eq!(thread.nodes, nodes + 1);
+    }
+
+    // Tests that load tablebases replace the global set, so they take turns
+    static TABLEBASES: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    #[test]
+    fn tablebase_wins_shrink_towards_the_fifty_move_limit() {
+        movegen::init();
+        let _tables = TABLEBASES.lock().unwrap_or_else(|e| e.into_inner());
+        syzygy::init_global_syzygy("syzygy");
+        assert!(
+            syzygy::get_global_syzygy().is_some_and(|tb| tb.max_pieces() >= 3),
+            "the KRvK tables in syzygy/ did not load"
+        );
+
+        // A null window just under the win bound: wins cut at their TB score
+        let score = |clock: u8| {
+            let shared = Arc::new(SharedState::new(16));
+            let mut thread = SearchThread::new(0, shared, true);
+            let fen = format!("8/8/8/4k3/8/8/8/R3K3 w - - {} 80", clock);
+            let mut board = Board::from_fen(&fen).unwrap();
+            thread.negamax(&mut board, 1, 1, 28999, 29000, false, None).0
+        };
+
+        assert_eq!(score(0), 29999);
+        // Still known wins, but ones that have to be converted soon
+        assert_eq!(score(60), 29999 - 60);
+        assert_eq!(score(99), 29999 - 99);
+    }
+
+    #[test]
+    fn tablebase_win_is_converted_before_the_fifty_move_limit() {
+        use crate::game::{GameResult, TimeControl, play_game};
+
+        movegen::init();
+        let _tables = TABLEBASES.lock().unwrap_or_else(|e| e.into_inner());
+        syzygy::init_global_syzygy("syzygy");
+        assert!(
+            syzygy::get_global_syzygy().is_some_and(|tb| tb.max_pieces() >= 3),
+            "the KRvK tables in syzygy/ did not load"
+        );
+
+        // Mate is 27 plies away and the fifty-move rule leaves 28: one wasted
+        // tempo by White draws. Black defends with the tables as well.
+        let board = Board::from_fen("8/8/8/4k3/8/8/8/R3K3 w - - 72 80").unwrap();
+        let tc = TimeControl::depth(6);
+        let (result, played) = play_game(board, tc, tc, 60);
+        let line: Vec<String> = played.iter().map(|&m| moves::format(m)).collect();
+        assert_eq!(result, GameResult::WhiteWins, "played {}", line.join(" "));
+    }
+
+    #[test]
+    #[ignore = "needs SYZYGY_PATH pointing at a 7-man set"]
+    fn seven_piece_positions_are_probed() {
+        movegen::init();
+        let _tables = TABLEBASES.lock().unwrap_or_else(|e| e.into_inner());
+        let path = std::env::var("SYZYGY_PATH").expect("SYZYGY_PATH is not set");
+        syzygy::init_global_syzygy(&path);
+        assert_eq!(syzygy::max_pieces(), 7, "no 7-man tables under {}", path);
+
+        let shared = Arc::new(SharedState::new(16));
+        let mut thread = SearchThread::new(0, shared.clone(), true);
+        thread.silent = true;
+        let mut board = Board::from_fen("4k1n1/8/8/8/8/8/PPP5/4K2R w - - 0 1").unwrap();
+        assert_eq!(board.occupancy[2].count_ones(), 7);
+        thread.search(&mut board, 2);
+        assert!(shared.tbhits.load(Ordering::Relaxed) > 0);
+    }
+
+    #[test]
+    fn quiescence_stores_mate_cutoffs_relative_to_the_node() {
+        movegen::init();
+        let shared = Arc::new(SharedState::new(16));
+        let mut thread = SearchThread::new(0, Arc::clone(&shared), true);
+
+        // Standing pat beats a beta of being mated
+        let mut board = Board::from_fen("r6k/6pp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let beta = -MATE_BOUND - 10;
+        let ply = 2;
+        assert_eq!(thread.quiescence(&mut board, ply, QS_CHECK_PLIES, beta - 1, beta), beta);
+        let (_, stored, _, flag) = shared.tt.probe(board.zobrist_hash).unwrap();
+        assert!(flag == TTFlag::Beta);
+        assert_eq!(Score(stored).from_tt(ply).0, beta);
+    }
+
+    #[test]
+    fn quiescence_finds_a_quiet_mate() {
+        movegen::init();
+        let quiescence = |qs_ply: u8| {
+            let shared = Arc::new(SharedState::new(16));
+            let mut thread = SearchThread::new(0, shared, true);
+            let mut board = Board::from_fen("7k/6pp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+            thread.quiescence(&mut board, 0, qs_ply, -INF, INF)
+        };
+
+        // Ra8# is not a capture
+        assert_eq!(Score(quiescence(0)).mate_in(), Some(1));
+        // Only the first quiescence ply looks at checks
+        assert!(!Score(quiescence(QS_CHECK_PLIES)).is_mate());
+    }
+
+    #[test]
+    fn quiescence_without_checks_does_not_answer_one_with_them() {
+        movegen::init();
+        let shared = Arc::new(SharedState::new(16));
+        let mut thread = SearchThread::new(0, Arc::clone(&shared), true);
+        let mut board = Board::from_fen("7k/6pp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+
+        // Stored at depth -1, blind to Ra8#
+        assert!(!Score(thread.quiescence(&mut board, 0, QS_CHECK_PLIES, -INF, INF)).is_mate());
+        assert_eq!(shared.tt.probe(board.zobrist_hash).unwrap().2, -1);
+
+        let score = thread.quiescence(&mut board, 0, 0, -INF, INF);
+        assert_eq!(Score(score).mate_in(), Some(1));
+        assert_eq!(shared.tt.probe(board.zobrist_hash).unwrap().2, 0);
+    }
+
+    #[test]
+    fn quiescence_sees_mate_after_a_capture() {
+        movegen::init();
+        let quiescence = |fen: &str| {
+            let shared = Arc::new(SharedState::new(16));
+            let mut thread = SearchThread::new(0, shared, true);
+            let mut board = Board::from_fen(fen).unwrap();
+            thread.quiescence(&mut board, 0, QS_CHECK_PLIES, -INF, INF)
+        };
+
+        // Rxa8 mates: Black may not stand pat on a rook's worth of material
+        assert_eq!(Score(quiescence("r6k/6pp/8/8/8/8/8/R5K1 w - - 0 1")).mate_in(), Some(1));
+        assert_eq!(quiescence("R6k/6pp/8/8/8/8/8/6K1 b - - 0 1"), Score::mated_in_plies(0).0);
+
+        // Every interposition is a quiet evasion met by a mating capture
+        let score = quiescence("6k1/5ppp/8/8/8/8/4Q1PP/q6K w - - 0 1");
+        assert_eq!(score, Score::mated_in_plies(2).0);
+    }
+
+    #[test]
+    fn pv_is_legal_and_repeatable() {
+        movegen::init();
+        let kiwi = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let run = || {
+            let shared = Arc::new(SharedState::new(16));
+            let mut thread = SearchThread::new(0, shared, true);
+            thread.silent = true;
+            let mut board = Board::from_fen(kiwi).unwrap();
+            let (_, best_move) = thread.search(&mut board, 7);
+            assert_eq!(thread.pv.first().copied(), best_move);
+            thread.pv
+        };
+
+        let pv = run();
+        assert!(pv.len() >= 4, "pv {:?}", pv);
+        let mut board = Board::from_fen(kiwi).unwrap();
+        for &m in &pv {
+            assert!(board.make_move_checked(m).is_ok(), "illegal {}", moves::format(m));
+        }
+        assert_eq!(run(), pv);
+    }
+
+    #[test]
+    fn search_params_change_nodes_not_correctness() {
+        movegen::init();
+        let kiwi = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+        let run = |params: SearchParams, fen: &str, depth: u8| {
+            let shared = Arc::new(SharedState::new(16));
+            let mut thread = SearchThread::new(0, shared, true);
+            thread.silent = true;
+            thread.params = params;
+            let mut board = Board::from_fen(fen).unwrap();
+            let (score, best_move) = thread.search(&mut board, depth);
+
+            let best_move = best_move.expect("search should return a move");
+            let mut move_list = MoveList::new();
+            board.generate_pseudo_legal_moves(&mut move_list);
+            assert!(move_list.iter().any(|&m| m == best_move));
+            (score, best_move, thread.nodes)
+        };
+
+        let mut tight = SearchParams::default();
+        assert!(tight.set("RFPMargin", 30));
+        assert!(tight.set("lmp1", 1));
+        assert!(!tight.set("NoSuchParam", 1));
+
+        let (_, _, default_nodes) = run(SearchParams::default(), kiwi, 6);
+        let (_, _, tight_nodes) = run(tight, kiwi, 6);
+        assert_ne!(default_nodes, tight_nodes);
+
+        let (score, best_move, _) = run(tight, "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 4);
+        assert_eq!(moves::format(best_move), "a1a8");
+        assert!(Score(score).is_mate());
+    }
+
+    #[test]
+    fn wdl_large_score_is_mostly_win() {
+        let (w, d, l) = wdl_from_score(800);
+        assert!(w > 950, "expected high win permill, got {}", w);
+        assert_eq!(w + d + l, 1000);
+    }
+
+    #[test]
+    fn wdl_zero_score_is_mostly_draw() {
+        let (w, d, l) = wdl_from_score(0);
+        assert!(d > 700, "expected mostly draw, got {} {} {}", w, d, l);
+        assert_eq!(w, l);
+    }
+
+    #[test]
+    fn wdl_known_win_is_exact() {
+        assert_eq!(wdl_from_score(29000), (1000, 0, 0));
+        assert_eq!(wdl_from_score(-MATE_SCORE + 5), (0, 0, 1000));
+    }
 }
@@ -1,7 +1,6 @@
 use crate::board::Board;
 use crate::moves::{self, Move};
-use crate::types::{Color, PieceType, Square};
-use crate::movegen;
+use crate::types::{Color, PieceType};
 
 pub fn see(board: &Board, m: Move) -> i32 {
     let from = moves::from_sq(m);
@@ -16,7 +15,14 @@ pub fn see(board: &Board, m: Move) -> i32 {
     let mut side = board.side_to_move;
     
     let att_pt = board.piece_type_on(from).unwrap();
-    let victim_pt = board.piece_type_on(to);
+    let victim_pt = if moves::flag(m) == moves::EN_PASSANT_CAPTURE_FLAG {
+        // The captured pawn is beside `from`, and may have screened a slider
+        let captured_sq = (from & !7) | (to & 7);
+        occ ^= 1u64 << captured_sq;
+        Some(PieceType::Pawn)
+    } else {
+        board.piece_type_on(to)
+    };
     
     gain[d] = if let Some(pt) = victim_pt {
         piece_value(pt)
@@ -38,8 +44,10 @@ pub fn see(board: &Board, m: Move) -> i32 {
         
         side = if side == Color::White { Color::Black } else { Color::White };
         
+        // Recomputed each step so sliders behind a captured piece join in
+        let attackers = board.attackers_to(to, occ);
         let mut next_pt = PieceType::Pawn;
-        from_set = get_least_valuable_attacker(board, to, occ, side, &mut next_pt);
+        from_set = get_least_valuable_attacker(board, attackers, side, &mut next_pt);
         
         if from_set == 0 {
             break;
@@ -56,6 +64,60 @@ pub fn see(board: &Board, m: Move) -> i32 {
     gain[0]
 }
 
+/// `see(board, m) >= threshold`, but stops as soon as the exchange is
+/// known to end above or below the threshold.
+pub fn see_ge(board: &Board, m: Move, threshold: i32) -> bool {
+    let from = moves::from_sq(m);
+    let to = moves::to_sq(m);
+    let mut occ = board.occupancy[2];
+
+    let victim_value = if moves::flag(m) == moves::EN_PASSANT_CAPTURE_FLAG {
+        occ ^= 1u64 << ((from & !7) | (to & 7));
+        piece_value(PieceType::Pawn)
+    } else {
+        board.piece_type_on(to).map_or(0, piece_value)
+    };
+
+    // `swap` is what the side that just captured stands to lose next
+    let mut swap = victim_value - threshold;
+    if swap < 0 {
+        return false;
+    }
+    swap = piece_value(board.piece_type_on(from).unwrap()) - swap;
+    if swap <= 0 {
+        return true;
+    }
+
+    occ ^= 1u64 << from;
+    let mut side = board.side_to_move;
+    let mut result = true;
+
+    loop {
+        side = if side == Color::White { Color::Black } else { Color::White };
+        let attackers = board.attackers_to(to, occ);
+        let mut pt = PieceType::Pawn;
+        let from_set = get_least_valuable_attacker(board, attackers, side, &mut pt);
+        if from_set == 0 {
+            break;
+        }
+        result = !result;
+
+        // The king may only take last
+        if pt == PieceType::King {
+            let defenders = attackers & !board.occupancy[side as usize];
+            return if defenders != 0 { !result } else { result };
+        }
+
+        swap = piece_value(pt) - swap;
+        if swap < result as i32 {
+            break;
+        }
+        occ ^= from_set;
+    }
+
+    result
+}
+
 fn piece_value(pt: PieceType) -> i32 {
     match pt {
         PieceType::Pawn => 100,
@@ -68,55 +130,17 @@ fn piece_value(pt: PieceType) -> i32 {
 }
 
 fn get_least_valuable_attacker(
-    board: &Board, 
-    sq: Square, 
-    occ: u64, 
+    board: &Board,
+    attackers: u64,
     side: Color,
     piece_type: &mut PieceType
 ) -> u64 {
-    let them = if side == Color::White { Color::Black } else { Color::White };
-    
-    let pawns = board.pieces[PieceType::Pawn as usize][side as usize] & occ;
-    let pawn_attack_mask = movegen::pawn_attacks(them, sq);
-    let attackers = pawns & pawn_attack_mask;
-    if attackers != 0 {
-        *piece_type = PieceType::Pawn;
-        return attackers & attackers.wrapping_neg(); // LSB
-    }
-
-    let knights = board.pieces[PieceType::Knight as usize][side as usize] & occ;
-    let attackers = knights & movegen::knight_attacks(sq);
-    if attackers != 0 {
-        *piece_type = PieceType::Knight;
-        return attackers & attackers.wrapping_neg();
-    }
-
-    let bishops = board.pieces[PieceType::Bishop as usize][side as usize] & occ;
-    let attackers = bishops & movegen::get_bishop_attacks(sq, occ);
-    if attackers != 0 {
-        *piece_type = PieceType::Bishop;
-        return attackers & attackers.wrapping_neg();
-    }
-
-    let rooks = board.pieces[PieceType::Rook as usize][side as usize] & occ;
-    let attackers = rooks & movegen::get_rook_attacks(sq, occ);
-    if attackers != 0 {
-        *piece_type = PieceType::Rook;
-        return attackers & attackers.wrapping_neg();
-    }
-
-    let queens = board.pieces[PieceType::Queen as usize][side as usize] & occ;
-    let attackers = queens & (movegen::get_bishop_attacks(sq, occ) | movegen::get_rook_attacks(sq, occ));
-    if attackers != 0 {
-        *piece_type = PieceType::Queen;
-        return attackers & attackers.wrapping_neg();
-    }
-
-    let kings = board.pieces[PieceType::King as usize][side as usize] & occ;
-    let attackers = kings & movegen::king_attacks(sq);
-    if attackers != 0 {
-        *piece_type = PieceType::King;
-        return attackers & attackers.wrapping_neg();
+    for pt in 0..6 {
+        let set = attackers & board.pieces[pt][side as usize];
+        if set != 0 {
+            *piece_type = PieceType::from_index(pt);
+            return set & set.wrapping_neg(); // LSB
+        }
     }
 
     0
@@ -129,6 +153,69 @@ mod tests {
     use crate::moves;
     use crate::movegen;
 
+    #[test]
+    fn test_see_xray_recapture() {
+        movegen::init();
+
+        // Rd2xd5 Rxd5 is answered by the rook behind on d1
+        let board = Board::from_fen("3rk3/8/8/3n4/8/8/3R4/3RK3 w - - 0 1").unwrap();
+        let m = moves::new(11, 35, moves::CAPTURE_FLAG); // Rd2xd5
+        assert_eq!(see(&board, m), 320);
+    }
+
+    #[test]
+    fn test_see_xray_queen_battery() {
+        movegen::init();
+
+        // Rd2xd5 Rxd5 Qxd5: without the queen behind the rook this loses the exchange
+        let board = Board::from_fen("3rk3/8/8/3p4/8/8/3R4/3QK3 w - - 0 1").unwrap();
+        let m = moves::new(11, 35, moves::CAPTURE_FLAG); // Rd2xd5
+        assert_eq!(see(&board, m), 100);
+
+        let without_queen = Board::from_fen("3rk3/8/8/3p4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        assert_eq!(see(&without_queen, m), -400);
+    }
+
+    #[test]
+    fn test_see_en_passant() {
+        movegen::init();
+
+        // exd6 e.p. wins a pawn for a pawn when d6 is defended
+        let board = Board::from_fen("4k3/2p5/8/3pP3/8/8/8/4K3 w - d6 0 2").unwrap();
+        let m = moves::new(36, 43, moves::EN_PASSANT_CAPTURE_FLAG);
+        assert_eq!(see(&board, m), 0);
+
+        // Lifting the d5 pawn lets the rook on d1 back up the capture
+        let board = Board::from_fen("3rk3/8/8/3pP3/8/8/8/3RK3 w - d6 0 2").unwrap();
+        assert_eq!(see(&board, m), 100);
+    }
+
+    #[test]
+    fn test_see_ge_agrees_with_see() {
+        movegen::init();
+
+        let cases = [
+            ("3rk3/8/8/3n4/8/8/3R4/3RK3 w - - 0 1", moves::new(11, 35, moves::CAPTURE_FLAG)),
+            ("3rk3/8/8/3p4/8/8/3R4/3QK3 w - - 0 1", moves::new(11, 35, moves::CAPTURE_FLAG)),
+            ("3rk3/8/8/3p4/8/8/3R4/4K3 w - - 0 1", moves::new(11, 35, moves::CAPTURE_FLAG)),
+            ("4k3/2p5/8/3pP3/8/8/8/4K3 w - d6 0 2", moves::new(36, 43, moves::EN_PASSANT_CAPTURE_FLAG)),
+            ("3rk3/8/8/3pP3/8/8/8/3RK3 w - d6 0 2", moves::new(36, 43, moves::EN_PASSANT_CAPTURE_FLAG)),
+            ("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1", moves::new(28, 35, moves::CAPTURE_FLAG)),
+            ("4k3/8/4p3/3p4/8/8/3Q4/4K3 w - - 0 1", moves::new(11, 35, moves::CAPTURE_FLAG)),
+            ("4k3/8/3p4/4n3/8/8/4R3/4K3 w - - 0 1", moves::new(12, 36, moves::CAPTURE_FLAG)),
+            ("4k3/8/8/4n3/8/8/4N3/4K3 w - - 0 1", moves::new(12, 36, moves::CAPTURE_FLAG)),
+            // Quiet move onto a square only a pawn guards
+            ("4k3/8/8/3p4/8/8/8/2B1K3 w - - 0 1", moves::new(2, 20, moves::QUIET_MOVE_FLAG)),
+        ];
+        for (fen, m) in cases {
+            let board = Board::from_fen(fen).unwrap();
+            let value = see(&board, m);
+            for threshold in (-1000..=1000).step_by(10) {
+                assert_eq!(see_ge(&board, m, threshold), value >= threshold, "{} at {}", fen, threshold);
+            }
+        }
+    }
+
     #[test]
     fn test_see_basic() {
         movegen::init();
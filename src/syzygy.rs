@@ -1,9 +1,10 @@
 use crate::board::Board;
 use crate::movegen;
 use crate::types::{Color, PieceType, Square};
-use pyrrhic_rs::{EngineAdapter, TableBases, WdlProbeResult, DtzProbeValue};
+use pyrrhic_rs::{DtzProbeValue, DtzResult, EngineAdapter, TableBases, WdlProbeResult};
 use std::path::Path;
 use std::sync::RwLock;
+use std::sync::atomic::{AtomicU32, Ordering};
 
 #[derive(Clone)]
 pub struct SyzygyAdapter;
@@ -44,12 +45,17 @@ pub type SyzygyTB = TableBases<SyzygyAdapter>;
 // Global storage for TableBases
 pub static SYZYGY_TB: RwLock<Option<SyzygyTB>> = RwLock::new(None);
 
+// Piece count of the largest loaded table, 0 with none, so the search can
+// rule out a probe without taking the lock
+static MAX_PIECES: AtomicU32 = AtomicU32::new(0);
+
 pub fn init_global_syzygy(path: &str) {
     match TableBases::<SyzygyAdapter>::new(path) {
         Ok(tb) => {
             println!("info string Syzygy tablebases found at: {}", path);
             println!("info string Syzygy max pieces: {}", tb.max_pieces());
             let mut lock = SYZYGY_TB.write().unwrap();
+            MAX_PIECES.store(tb.max_pieces(), Ordering::Relaxed);
             *lock = Some(tb);
         }
         Err(e) => {
@@ -71,6 +77,11 @@ pub fn auto_load() {
     }
 }
 
+/// Most pieces a position may have to be probed: 0 until tables load.
+pub fn max_pieces() -> u32 {
+    MAX_PIECES.load(Ordering::Relaxed)
+}
+
 pub fn get_global_syzygy() -> Option<SyzygyTB> {
     let lock = SYZYGY_TB.read().unwrap();
     lock.clone()
@@ -111,6 +122,29 @@ pub fn probe_wdl(board: &Board, tb: &SyzygyTB) -> Option<WdlProbeResult> {
 
 /// Probe DTZ at root and return the best move info (from_sq, to_sq, promo, wdl_score)
 pub fn probe_root(board: &Board, tb: &SyzygyTB) -> Option<(u8, u8, u8, i32)> {
+    let dtz_result = probe_dtz(board, tb)?;
+    let from = dtz_result.from_square;
+    let to = dtz_result.to_square;
+    let promo = match dtz_result.promotion {
+        pyrrhic_rs::Piece::Queen => 4,
+        pyrrhic_rs::Piece::Rook => 3,
+        pyrrhic_rs::Piece::Bishop => 2,
+        pyrrhic_rs::Piece::Knight => 1,
+        _ => 0,
+    };
+    let wdl_score = match dtz_result.wdl {
+        WdlProbeResult::Win => 1,
+        WdlProbeResult::CursedWin => 1,
+        WdlProbeResult::Loss => -1,
+        WdlProbeResult::BlessedLoss => -1,
+        WdlProbeResult::Draw => 0,
+    };
+    Some((from, to, promo, wdl_score))
+}
+
+/// DTZ probe of every move, giving the best one. `None` when there is no
+/// move (mate or stalemate) or the probe fails.
+fn probe_dtz(board: &Board, tb: &SyzygyTB) -> Option<DtzResult> {
     let white = board.occupancy[Color::White as usize];
     let black = board.occupancy[Color::Black as usize];
 
@@ -126,33 +160,10 @@ pub fn probe_root(board: &Board, tb: &SyzygyTB) -> Option<(u8, u8, u8, i32)> {
     let rule50 = board.halfmove_clock as u32;
 
     match tb.probe_root(white, black, kings, queens, rooks, bishops, knights, pawns, rule50, ep, turn) {
-        Ok(result) => {
-            // Extract from root field
-            match result.root {
-                DtzProbeValue::DtzResult(dtz_result) => {
-                    let from = dtz_result.from_square;
-                    let to = dtz_result.to_square;
-                    let promo = match dtz_result.promotion {
-                        pyrrhic_rs::Piece::Queen => 4,
-                        pyrrhic_rs::Piece::Rook => 3,
-                        pyrrhic_rs::Piece::Bishop => 2,
-                        pyrrhic_rs::Piece::Knight => 1,
-                        _ => 0,
-                    };
-                    let wdl_score = match dtz_result.wdl {
-                        WdlProbeResult::Win => 1,
-                        WdlProbeResult::CursedWin => 1,
-                        WdlProbeResult::Loss => -1,
-                        WdlProbeResult::BlessedLoss => -1,
-                        WdlProbeResult::Draw => 0,
-                    };
-                    Some((from, to, promo, wdl_score))
-                }
-                DtzProbeValue::Checkmate => None, 
-                DtzProbeValue::Stalemate => None, 
-                DtzProbeValue::Failed => None,
-            }
-        }
+        Ok(result) => match result.root {
+            DtzProbeValue::DtzResult(dtz_result) => Some(dtz_result),
+            DtzProbeValue::Checkmate | DtzProbeValue::Stalemate | DtzProbeValue::Failed => None,
+        },
         Err(_) => None,
     }
-}
\ No newline at end of file
+}
@@ -1,10 +1,24 @@
 use crate::board::Board;
 use crate::moves::Move;
-use crate::search::SearchThread;
+use crate::search::{SearchParams, SearchThread};
 use crate::tt::TranspositionTable;
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::Arc;
+use crate::zobrist::ZHash;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
+
+/// Upper bound on search threads, matching the UCI `Threads` option.
+pub const MAX_THREADS: usize = 256;
+
+/// Time limits the main thread switches to on `ponderhit`, measured from
+/// the moment it arrived.
+#[derive(Debug, Clone, Copy)]
+pub struct ClockUpdate {
+    pub start: Instant,
+    pub soft_limit: u128,
+    pub hard_limit: u128,
+}
 
 #[repr(C, align(64))]
 pub struct SharedState {
@@ -12,6 +26,15 @@ pub struct SharedState {
     pub stop: AtomicBool,
     pub _padding: [u8; 56],
     pub nodes: AtomicU64,
+    /// Successful Syzygy probes in the current search.
+    pub tbhits: AtomicU64,
+    /// Set while searching the expected reply; cleared on `ponderhit`.
+    pub ponder: AtomicBool,
+    clock_pending: AtomicBool,
+    clock_update: Mutex<Option<ClockUpdate>>,
+    /// Nodes and completed depth of each thread's last search, by thread id.
+    pub thread_nodes: [AtomicU64; MAX_THREADS],
+    pub thread_depth: [AtomicU8; MAX_THREADS],
 }
 
 impl SharedState {
@@ -21,47 +44,170 @@ impl SharedState {
             stop: AtomicBool::new(false),
             _padding: [0; 56],
             nodes: AtomicU64::new(0),
+            tbhits: AtomicU64::new(0),
+            ponder: AtomicBool::new(false),
+            clock_pending: AtomicBool::new(false),
+            clock_update: Mutex::new(None),
+            thread_nodes: std::array::from_fn(|_| AtomicU64::new(0)),
+            thread_depth: std::array::from_fn(|_| AtomicU8::new(0)),
+        }
+    }
+
+    /// Ends pondering: the running search keeps its tree and switches to
+    /// the given limits, with the clock starting now.
+    pub fn ponderhit(&self, soft_limit: u128, hard_limit: u128) {
+        *self.clock_update.lock().unwrap() = Some(ClockUpdate {
+            start: Instant::now(),
+            soft_limit,
+            hard_limit,
+        });
+        self.clock_pending.store(true, Ordering::Release);
+        self.ponder.store(false, Ordering::Release);
+    }
+
+    /// Takes the clock published by `ponderhit`, if any. Cheap when there
+    /// is none, so it can be polled from the search.
+    pub fn take_clock_update(&self) -> Option<ClockUpdate> {
+        if !self.clock_pending.swap(false, Ordering::Acquire) {
+            return None;
         }
+        self.clock_update.lock().unwrap().take()
+    }
+
+    fn record_thread(&self, search_thread: &SearchThread) {
+        let id = search_thread.thread_id;
+        self.thread_nodes[id].store(search_thread.nodes, Ordering::Relaxed);
+        self.thread_depth[id].store(search_thread.completed_depth, Ordering::Relaxed);
     }
 }
 
+#[derive(Clone)]
 pub struct ThreadPool {
     pub shared: Arc<SharedState>,
     pub num_threads: usize,
+    pub show_wdl: bool,
+    pub silent: bool,
+    /// Per-thread move ordering noise; ignored with a single thread.
+    pub jitter: bool,
+    pub analyse_mode: bool,
+    /// Draw score penalty in centipawns for the side to move (`Contempt`).
+    pub contempt: i32,
+    /// Print per-thread node counts and depths after each search.
+    pub debug: bool,
+    pub params: SearchParams,
+    /// Root lines the main thread reports (`MultiPV`); helpers search one.
+    pub multipv: usize,
+    /// If not empty, the only root moves searched (`go searchmoves`).
+    pub root_moves: Vec<Move>,
+    /// Mate length in moves that ends the search once found (`go mate`).
+    pub mate_limit: Option<i32>,
+    /// Write castling as king takes rook (`UCI_Chess960`).
+    pub chess960: bool,
+    /// Positions played before the root (`Board::repetition_keys`). The
+    /// boards searched are usually `clone_for_search` copies without them.
+    pub game_history: Vec<ZHash>,
 }
 
 impl ThreadPool {
     pub fn new(num_threads: usize, tt_size_mb: usize) -> Self {
         Self {
             shared: Arc::new(SharedState::new(tt_size_mb)),
-            num_threads,
+            num_threads: num_threads.clamp(1, MAX_THREADS),
+            show_wdl: false,
+            silent: false,
+            jitter: true,
+            analyse_mode: false,
+            contempt: 0,
+            debug: false,
+            params: SearchParams::default(),
+            multipv: 1,
+            root_moves: Vec::new(),
+            mate_limit: None,
+            chess960: false,
+            game_history: Vec::new(),
         }
     }
 
     pub fn search(
         &self,
-        board: &mut Board, 
+        board: &mut Board,
         depth: u8,
         time_soft_limit: u128,
         time_hard_limit: u128,
     ) -> (i32, Option<Move>) {
-        // Reset shared state
+        let (score, best_move, _) =
+            self.search_limited(board, depth, time_soft_limit, time_hard_limit, u64::MAX);
+        (score, best_move)
+    }
+
+    /// Like `search`, but also stops once `node_limit` nodes have been
+    /// searched. Whichever of depth, nodes or time is hit first ends it.
+    /// Also returns the ponder move, as `run` does.
+    pub fn search_limited(
+        &self,
+        board: &mut Board,
+        depth: u8,
+        time_soft_limit: u128,
+        time_hard_limit: u128,
+        node_limit: u64,
+    ) -> (i32, Option<Move>, Option<Move>) {
+        self.prepare(false);
+        self.run(board, depth, time_soft_limit, time_hard_limit, node_limit)
+    }
+
+    /// Resets the shared state for a new search. Callers that run the
+    /// search on another thread do this first, so that a `stop` or
+    /// `ponderhit` sent right after `go` is not lost.
+    pub fn prepare(&self, ponder: bool) {
         self.shared.stop.store(false, Ordering::SeqCst);
         self.shared.nodes.store(0, Ordering::Relaxed);
+        self.shared.tbhits.store(0, Ordering::Relaxed);
         self.shared.tt.new_search();
+        self.shared.take_clock_update();
+        self.shared.ponder.store(ponder, Ordering::Release);
+        for id in 0..self.num_threads {
+            self.shared.thread_nodes[id].store(0, Ordering::Relaxed);
+            self.shared.thread_depth[id].store(0, Ordering::Relaxed);
+        }
+    }
 
+    /// Runs a search set up by `prepare`. A pondering search holds its
+    /// result until `ponderhit` or `stop`, even if it finishes early.
+    /// Returns the score, the best move and the expected reply to it: the
+    /// second move of the main thread's PV, if that PV starts with the best
+    /// move.
+    pub fn run(
+        &self,
+        board: &mut Board,
+        depth: u8,
+        time_soft_limit: u128,
+        time_hard_limit: u128,
+        node_limit: u64,
+    ) -> (i32, Option<Move>, Option<Move>) {
         let mut handles = Vec::with_capacity(self.num_threads);
+        let jitter = self.jitter && self.num_threads > 1;
 
         // Spawn helper threads first (they will search until stopped)
         for thread_id in 1..self.num_threads {
             let shared = Arc::clone(&self.shared);
             let mut board_clone = board.clone_for_search();
+            let params = self.params;
+            let contempt = self.contempt;
+            let root_moves = self.root_moves.clone();
+            let game_history = self.game_history.clone();
 
             let handle = thread::spawn(move || {
                 let mut search_thread = SearchThread::new(thread_id, shared, false);
+                search_thread.jitter = jitter;
+                search_thread.params = params;
+                search_thread.contempt = contempt;
+                search_thread.root_moves = root_moves;
+                search_thread.game_history = game_history;
                 search_thread.time_soft_limit = u128::MAX;
                 search_thread.time_hard_limit = u128::MAX;
-                search_thread.search(&mut board_clone, depth)
+                let result = search_thread.search(&mut board_clone, depth);
+                search_thread.shared.record_thread(&search_thread);
+                result
             });
 
             handles.push(handle);
@@ -71,8 +217,31 @@ impl ThreadPool {
         let mut main_search = SearchThread::new(0, Arc::clone(&self.shared), true);
         main_search.time_soft_limit = time_soft_limit;
         main_search.time_hard_limit = time_hard_limit;
-        
-        let result = main_search.search(board, depth);  // No clone!
+        main_search.node_limit = node_limit;
+        main_search.show_wdl = self.show_wdl;
+        main_search.silent = self.silent;
+        main_search.jitter = jitter;
+        main_search.analyse_mode = self.analyse_mode;
+        main_search.contempt = self.contempt;
+        main_search.params = self.params;
+        main_search.multipv = self.multipv;
+        main_search.root_moves = self.root_moves.clone();
+        main_search.mate_limit = self.mate_limit;
+        main_search.chess960 = self.chess960;
+        main_search.game_history = self.game_history.clone();
+
+        let (score, best_move) = main_search.search(board, depth);  // No clone!
+        self.shared.record_thread(&main_search);
+        let ponder_move = match main_search.pv[..] {
+            [first, reply, ..] if Some(first) == best_move => Some(reply),
+            _ => None,
+        };
+
+        while self.shared.ponder.load(Ordering::Acquire)
+            && !self.shared.stop.load(Ordering::SeqCst)
+        {
+            thread::sleep(Duration::from_millis(1));
+        }
 
         // Main thread finished - stop all helper threads
         self.shared.stop.store(true, Ordering::SeqCst);
@@ -82,7 +251,13 @@ impl ThreadPool {
             let _ = handle.join();
         }
 
-        result
+        if self.debug && !self.silent {
+            for (id, (nodes, depth)) in self.thread_stats().into_iter().enumerate() {
+                println!("info string thread {} nodes {} depth {}", id, nodes, depth);
+            }
+        }
+
+        (score, best_move, ponder_move)
     }
 
     pub fn stop(&self) {
@@ -96,4 +271,80 @@ impl ThreadPool {
     pub fn total_nodes(&self) -> u64 {
         self.shared.nodes.load(Ordering::Relaxed)
     }
-}
\ No newline at end of file
+
+    /// `(nodes, completed depth)` of each thread in the last search.
+    pub fn thread_stats(&self) -> Vec<(u64, u8)> {
+        (0..self.num_threads)
+            .map(|id| {
+                (
+                    self.shared.thread_nodes[id].load(Ordering::Relaxed),
+                    self.shared.thread_depth[id].load(Ordering::Relaxed),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen;
+
+    #[test]
+    fn single_thread_search_is_deterministic() {
+        movegen::init();
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+        let run = || {
+            let mut pool = ThreadPool::new(1, 16);
+            pool.silent = true;
+            let mut board = Board::from_fen(fen).unwrap();
+            let (score, best_move) = pool.search(&mut board, 6, u128::MAX, u128::MAX);
+            (score, best_move, pool.total_nodes())
+        };
+
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn ponder_move_is_the_second_move_of_the_main_pv() {
+        movegen::init();
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+        let mut pool = ThreadPool::new(1, 16);
+        pool.silent = true;
+        let mut board = Board::from_fen(fen).unwrap();
+        let (_, best_move, ponder_move) =
+            pool.search_limited(&mut board, 6, u128::MAX, u128::MAX, u64::MAX);
+
+        // The same search on a fresh table, keeping the thread to read its PV
+        let pool = ThreadPool::new(1, 16);
+        pool.prepare(false);
+        let mut main_search = SearchThread::new(0, Arc::clone(&pool.shared), true);
+        main_search.silent = true;
+        main_search.jitter = false;
+        let mut board = Board::from_fen(fen).unwrap();
+        main_search.search(&mut board, 6);
+
+        assert_eq!(main_search.pv.len() >= 2, ponder_move.is_some());
+        assert_eq!(best_move, main_search.pv.first().copied());
+        assert_eq!(ponder_move, main_search.pv.get(1).copied());
+    }
+
+    #[test]
+    fn helper_threads_record_their_work() {
+        movegen::init();
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut pool = ThreadPool::new(2, 16);
+        pool.silent = true;
+        let mut board = Board::from_fen(fen).unwrap();
+        pool.search(&mut board, 7, u128::MAX, u128::MAX);
+
+        let stats = pool.thread_stats();
+        assert_eq!(stats.len(), 2);
+        for (id, (nodes, depth)) in stats.into_iter().enumerate() {
+            assert!(nodes > 0, "thread {} searched no nodes", id);
+            assert!(depth > 0, "thread {} completed no iteration", id);
+        }
+    }
+}
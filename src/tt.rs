@@ -6,6 +6,9 @@ use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
 #[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::{_MM_HINT_T0, _mm_prefetch};
 
+/// Added to a depth before it is packed into its byte.
+const DEPTH_OFFSET: i32 = 1;
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum TTFlag {
     Exact,
@@ -16,6 +19,8 @@ pub enum TTFlag {
 /// Atomic TT Entry using two AtomicU64s
 /// Data1: key (64 bits)
 /// Data2: move(16) | score(16) | depth(8) | generation(8) | flag(8) | padding(8)
+/// The depth is stored plus `DEPTH_OFFSET`, so quiescence can use -1.
+/// Depths past 254 are stored as 254.
 #[repr(C, align(16))]
 pub struct AtomicTTEntry {
     key: AtomicU64,
@@ -118,7 +123,21 @@ impl TranspositionTable {
         self.generation.fetch_add(1, Ordering::Relaxed);
     }
 
-    pub fn probe(&self, key: ZHash) -> Option<(Move, i32, u8, TTFlag)> {
+    /// Hints the CPU to pull the cluster for `key` into cache ahead of a probe.
+    /// No-op on targets without a stable prefetch intrinsic.
+    #[inline]
+    pub fn prefetch(&self, key: ZHash) {
+        #[cfg(target_arch = "x86_64")]
+        unsafe {
+            let index = (key as usize) & (self.size - 1);
+            let cluster = self.table.as_ptr().add(index);
+            _mm_prefetch(cluster as *const i8, _MM_HINT_T0);
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        let _ = key;
+    }
+
+    pub fn probe(&self, key: ZHash) -> Option<(Move, i32, i32, TTFlag)> {
         let index = (key as usize) & (self.size - 1);
         let cluster = &self.table[index];
 
@@ -135,7 +154,7 @@ impl TranspositionTable {
                         1 => TTFlag::Alpha,
                         _ => TTFlag::Beta,
                     };
-                    return Some((mv, score as i32, depth, flag));
+                    return Some((mv, score as i32, depth as i32 - DEPTH_OFFSET, flag));
                 }
             }
         }
@@ -143,7 +162,7 @@ impl TranspositionTable {
         None
     }
 
-    pub fn store(&self, key: ZHash, move_best: Option<Move>, score: i32, depth: u8, flag: TTFlag) {
+    pub fn store(&self, key: ZHash, move_best: Option<Move>, score: i32, depth: i32, flag: TTFlag) {
         let index = (key as usize) & (self.size - 1);
         let cluster = &self.table[index];
         let generation = self.generation.load(Ordering::Relaxed);
@@ -160,7 +179,12 @@ impl TranspositionTable {
             if let Some((stored_key, _stored_mv, _, stored_depth, stored_gen, _)) =
                 cluster.entries[i].read()
             {
+                let stored_depth = stored_depth as i32 - DEPTH_OFFSET;
                 if stored_key == key {
+                    // A quiescence result never replaces a deeper one
+                    if depth <= 0 && stored_depth > depth {
+                        return;
+                    }
                     replace_idx = i;
                     found = true;
                     break;
@@ -171,7 +195,7 @@ impl TranspositionTable {
                 if stored_gen != generation {
                     entry_score += 1000;
                 }
-                entry_score += 256 - stored_depth as i32;
+                entry_score += 256 - stored_depth;
 
                 if entry_score > worst_score {
                     worst_score = entry_score;
@@ -195,7 +219,21 @@ impl TranspositionTable {
             move_u16
         };
 
-        cluster.entries[replace_idx].write(key, final_move, score_i16, depth, generation, flag_u8);
+        let depth_u8 = (depth + DEPTH_OFFSET).clamp(0, u8::MAX as i32) as u8;
+        cluster.entries[replace_idx].write(key, final_move, score_i16, depth_u8, generation, flag_u8);
+    }
+
+    /// Permille of entries written during the current search, estimated
+    /// from the first 1000 clusters so it stays cheap for large tables.
+    pub fn hashfull(&self) -> u16 {
+        let sample = self.size.min(1000);
+        let generation = self.generation.load(Ordering::Relaxed);
+        let used = self.table[..sample]
+            .iter()
+            .flat_map(|cluster| &cluster.entries)
+            .filter(|entry| matches!(entry.read(), Some((_, _, _, _, g, _)) if g == generation))
+            .count();
+        (used * 1000 / (sample * 4)) as u16
     }
 
     pub fn clear(&self) {
@@ -208,3 +246,98 @@ impl TranspositionTable {
         self.generation.store(0, Ordering::Relaxed);
     }
 }
+
+/// Table for `Board::perft_hashed`: one always-replaced entry per slot.
+/// Entry data is `nodes << 8 | depth`, and the key is stored XORed with it
+/// so a torn write from another thread reads back as a miss, never as a
+/// wrong count.
+pub struct PerftTable {
+    entries: Vec<AtomicTTEntry>,
+    size: usize,
+}
+
+impl PerftTable {
+    pub fn new(mb_size: usize) -> Self {
+        let entry_size = std::mem::size_of::<AtomicTTEntry>();
+        let size = ((mb_size * 1024 * 1024) / entry_size).next_power_of_two();
+
+        let mut entries = Vec::with_capacity(size);
+        for _ in 0..size {
+            entries.push(AtomicTTEntry::new());
+        }
+
+        Self { entries, size }
+    }
+
+    /// Node count stored for `key` at exactly `depth`, if any.
+    pub fn probe(&self, key: ZHash, depth: u8) -> Option<u64> {
+        let entry = &self.entries[(key as usize) & (self.size - 1)];
+        let data = entry.data.load(Ordering::Relaxed);
+        let stored_key = entry.key.load(Ordering::Relaxed) ^ data;
+
+        if data != 0 && stored_key == key && data as u8 == depth {
+            Some(data >> 8)
+        } else {
+            None
+        }
+    }
+
+    pub fn store(&self, key: ZHash, depth: u8, nodes: u64) {
+        debug_assert!(nodes < 1 << 56);
+        let entry = &self.entries[(key as usize) & (self.size - 1)];
+        let data = (nodes << 8) | depth as u64;
+        entry.data.store(data, Ordering::Relaxed);
+        entry.key.store(key ^ data, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashfull_counts_the_current_generation() {
+        let tt = TranspositionTable::new(1);
+        tt.new_search();
+        assert_eq!(tt.hashfull(), 0);
+
+        // Fill every slot of the first 500 sampled clusters
+        for index in 0..500 {
+            for slot in 1..=4 {
+                let key = (slot * tt.size + index) as ZHash;
+                tt.store(key, None, 0, 1, TTFlag::Exact);
+            }
+        }
+        assert_eq!(tt.hashfull(), 500);
+
+        tt.new_search();
+        assert_eq!(tt.hashfull(), 0);
+    }
+
+    #[test]
+    fn quiescence_entries_keep_main_search_entries() {
+        let tt = TranspositionTable::new(1);
+        tt.store(42, None, 10, 0, TTFlag::Alpha);
+        tt.store(42, Some(77), 25, 3, TTFlag::Exact);
+        tt.store(42, None, -50, 0, TTFlag::Beta);
+        let (mv, score, depth, flag) = tt.probe(42).unwrap();
+        assert_eq!((mv, score, depth), (77, 25, 3));
+        assert!(flag == TTFlag::Exact);
+
+        // Nor does one without quiet checks replace one with them
+        tt.store(43, None, 10, 0, TTFlag::Alpha);
+        tt.store(43, None, -50, -1, TTFlag::Beta);
+        assert_eq!(tt.probe(43).unwrap().2, 0);
+        tt.store(44, None, 5, -1, TTFlag::Exact);
+        assert_eq!(tt.probe(44).unwrap().2, -1);
+    }
+
+    #[test]
+    fn maximum_depth_does_not_wrap() {
+        let tt = TranspositionTable::new(1);
+        tt.store(42, Some(77), 25, u8::MAX as i32, TTFlag::Exact);
+        assert_eq!(tt.probe(42).unwrap().2, u8::MAX as i32 - DEPTH_OFFSET);
+        tt.store(42, None, -50, -1, TTFlag::Beta);
+        assert_eq!(tt.probe(42).unwrap().0, 77);
+    }
+}
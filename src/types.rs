@@ -2,6 +2,7 @@ pub type Bitboard = u64;
 pub type Square = u8;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum Color {
   White,
@@ -37,19 +38,48 @@ pub enum PieceType {
   King,
 }
 
-impl From<usize> for PieceType {
-  fn from(val: usize) -> Self {
+impl TryFrom<usize> for PieceType {
+  type Error = &'static str;
+
+  fn try_from(val: usize) -> Result<Self, Self::Error> {
     match val {
-        0 => PieceType::Pawn,
-        1 => PieceType::Knight,
-        2 => PieceType::Bishop,
-        3 => PieceType::Rook,
-        4 => PieceType::Queen,
-        5 => PieceType::King,
-        _=> unreachable!(),
+        0 => Ok(PieceType::Pawn),
+        1 => Ok(PieceType::Knight),
+        2 => Ok(PieceType::Bishop),
+        3 => Ok(PieceType::Rook),
+        4 => Ok(PieceType::Queen),
+        5 => Ok(PieceType::King),
+        _ => Err("piece type index out of range"),
     }
   }
 }
 
+impl PieceType {
+  /// Infallible conversion for internal `0..6` indices. Out-of-range values
+  /// trip a debug assertion and saturate to `King` in release builds; use
+  /// `TryFrom` for anything decoded from outside data.
+  pub fn from_index(val: usize) -> Self {
+    debug_assert!(val < 6, "piece type index {} out of range", val);
+    PieceType::try_from(val).unwrap_or(PieceType::King)
+  }
+}
+
 pub type Move = u16;
 
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn piece_type_try_from_valid_indices() {
+    for i in 0..6 {
+      assert_eq!(PieceType::try_from(i).unwrap() as usize, i);
+    }
+  }
+
+  #[test]
+  fn piece_type_try_from_rejects_out_of_range() {
+    assert!(PieceType::try_from(6).is_err());
+    assert!(PieceType::try_from(usize::MAX).is_err());
+  }
+}
@@ -1,28 +1,27 @@
-use crate::board::Board;
-use crate::book::OpeningBook;
-use crate::moves::{Move, MoveList, format};
+use crate::book::{BookSelection, DEFAULT_BOOK};
+use crate::engine::{Engine, PositionError, SearchLimits, SearchResult};
+use crate::eval::evaluate_white;
+use crate::moves::format_uci;
 use crate::syzygy::auto_load;
-use crate::thread::ThreadPool;
-use crate::types::Color;
-use std::io::{self, BufRead};
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
 
-pub fn main_loop() {
-    let stdin = io::stdin();
-    let mut board = Board::default();
-
-    // Default to number of CPUs, capped at reasonable limit
-    let num_threads = std::thread::available_parallelism()
-        .map(|n| (n.get() / 2).max(1))
-        .unwrap_or(1);
-    // let num_threads = 1;
-
-    let mut thread_pool = ThreadPool::new(num_threads, 128); // 128MB TT
+/// Input to the command loop: lines from the GUI, and results from
+/// searches running in the background.
+enum Event {
+    Command(String),
+    BestMove(SearchResult),
+    Eof,
+}
 
-    let mut book = OpeningBook::new("Perfect2023.bin");
+pub fn main_loop() {
+    let mut stdout = io::stdout();
 
-    let mut move_overhead: u64 = 0;
+    let mut engine = Engine::new(default_threads(), 128); // 128MB TT
+    engine.pool.silent = false;
 
-    if book.file.is_some() {
+    if engine.book.is_loaded() {
         println!("info string Opening book loaded successfully");
     } else {
         println!("info string Warning: book.bin not found");
@@ -30,258 +29,630 @@ pub fn main_loop() {
 
     auto_load();
 
-    for line in stdin.lock().lines() {
-        let line = line.unwrap();
-        let cmd = line.trim();
-
-        if cmd == "uci" {
-            println!("id name AdityaChess");
-            println!("id author Aditya");
-            println!(
-                "option name Threads type spin default {} min 1 max 256",
-                num_threads
-            );
-            println!("option name Hash type spin default 128 min 1 max 16384");
-            println!("option name SyzygyPath type string default <empty>");
-            println!("option name Move Overhead type spin default 0 min 0 max 5000");
-            println!("uciok");
-        } else if cmd == "isready" {
-            println!("readyok");
-        } else if cmd.starts_with("setoption") {
-            let cmd_lower = cmd.to_lowercase();
-
-            if cmd_lower.contains("name move overhead") {
-                if let Some(val_part) = cmd.split("value").nth(1) {
-                    if let Ok(val) = val_part.trim().parse::<u64>() {
-                        move_overhead = val;
-                    }
+    command_loop(io::BufReader::new(io::stdin()), &mut engine, &mut stdout);
+}
+
+/// Reads commands until `quit`, EOF, or an unreadable line (closed pipe,
+/// invalid UTF-8), then makes sure no search is left running. Input is
+/// read on its own thread so `stop` and `ponderhit` reach a running
+/// search. At EOF a bounded search is allowed to finish and report.
+fn command_loop(input: impl BufRead + Send + 'static, engine: &mut Engine, out: &mut impl Write) {
+    let (events, rx) = mpsc::channel();
+    let reader = events.clone();
+    thread::spawn(move || {
+        for line in input.lines() {
+            let Ok(line) = line else { break };
+            if reader.send(Event::Command(line)).is_err() {
+                return;
+            }
+        }
+        let _ = reader.send(Event::Eof);
+    });
+
+    let mut eof = false;
+    for event in rx.iter() {
+        match event {
+            Event::Command(line) => match handle_command(line.trim(), engine, out, &events) {
+                Ok(true) => {}
+                _ => break,
+            },
+            Event::BestMove(result) => {
+                engine.wait();
+                if write_bestmove(out, engine, &result).is_err() || eof {
+                    break;
                 }
-            } else if cmd_lower.contains("name threads") {
-                if let Some(val_part) = cmd.split("value").nth(1) {
-                    if let Ok(n) = val_part.trim().parse::<usize>() {
-                        let n = n.max(1).min(256);
-                        thread_pool = ThreadPool::new(n, 128);
-                    }
+            }
+            Event::Eof => {
+                if engine.is_unbounded() {
+                    engine.stop();
                 }
-            } else if cmd_lower.contains("name hash") {
-                if let Some(val_part) = cmd.split("value").nth(1) {
-                    if let Ok(mb) = val_part.trim().parse::<usize>() {
-                        let mb = mb.max(1).min(16384);
-                        let threads = thread_pool.num_threads;
-                        thread_pool = ThreadPool::new(threads, mb);
-                    }
+                eof = true;
+                if !engine.is_searching() {
+                    break;
+                }
+            }
+        }
+    }
+    engine.stop();
+    engine.wait();
+
+    // Book moves are reported straight away and may be queued behind EOF
+    if eof {
+        for event in rx.try_iter() {
+            if let Event::BestMove(result) = event {
+                let _ = write_bestmove(out, engine, &result);
+            }
+        }
+    }
+}
+
+fn write_bestmove(out: &mut impl Write, engine: &Engine, result: &SearchResult) -> io::Result<()> {
+    let format = |m| format_uci(m, &engine.board, engine.pool.chess960);
+    match (result.best_move, result.ponder_move) {
+        (Some(m), Some(reply)) => writeln!(out, "bestmove {} ponder {}", format(m), format(reply))?,
+        (Some(m), None) => writeln!(out, "bestmove {}", format(m))?,
+        (None, _) => writeln!(out, "bestmove 0000")?,
+    }
+    out.flush()
+}
+
+/// Default to number of CPUs, capped at reasonable limit
+fn default_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| (n.get() / 2).max(1))
+        .unwrap_or(1)
+}
+
+/// Runs one UCI command, writing replies to `out`. Returns `Ok(false)` on
+/// `quit`. Commands other than `go` run to completion before the next one
+/// is read, so `readyok` is only sent after earlier `setoption`s, including
+/// a Hash reallocation, have been fully applied. `go` starts a background
+/// search whose result is posted to `events`.
+fn handle_command(
+    cmd: &str,
+    engine: &mut Engine,
+    out: &mut impl Write,
+    events: &Sender<Event>,
+) -> io::Result<bool> {
+    if cmd == "uci" {
+        writeln!(out, "id name AdityaChess")?;
+        writeln!(out, "id author Aditya")?;
+        writeln!(
+            out,
+            "option name Threads type spin default {} min 1 max 256",
+            default_threads()
+        )?;
+        writeln!(out, "option name Hash type spin default 128 min 1 max 16384")?;
+        writeln!(out, "option name SyzygyPath type string default <empty>")?;
+        writeln!(out, "option name Move Overhead type spin default 0 min 0 max 5000")?;
+        writeln!(out, "option name UCI_ShowWDL type check default false")?;
+        writeln!(out, "option name UCI_AnalyseMode type check default false")?;
+        writeln!(out, "option name UCI_Chess960 type check default false")?;
+        writeln!(out, "option name Ponder type check default false")?;
+        writeln!(out, "option name MultiPV type spin default 1 min 1 max 64")?;
+        writeln!(out, "option name Contempt type spin default 0 min -100 max 100")?;
+        writeln!(out, "option name BookFile type string default {}", DEFAULT_BOOK)?;
+        writeln!(out, "option name BookDepth type spin default 1000 min 0 max 1000")?;
+        writeln!(out, "option name Book Best Move type check default false")?;
+        writeln!(out, "uciok")?;
+    } else if cmd == "isready" {
+        writeln!(out, "readyok")?;
+    } else if cmd.starts_with("setoption") {
+        let cmd_lower = cmd.to_lowercase();
+
+        if cmd_lower.contains("name move overhead") {
+            if let Some(val_part) = cmd.split("value").nth(1) {
+                if let Ok(val) = val_part.trim().parse::<u64>() {
+                    engine.move_overhead = val;
+                }
+            }
+        } else if cmd_lower.contains("name threads") {
+            if let Some(val_part) = cmd.split("value").nth(1) {
+                if let Ok(n) = val_part.trim().parse::<usize>() {
+                    engine.set_threads(n.max(1).min(256));
                 }
-            } else if cmd_lower.contains("name syzygypath") {
-                if let Some(val_part) = cmd.split("value").nth(1) {
-                    crate::syzygy::init_global_syzygy(val_part.trim());
+            }
+        } else if cmd_lower.contains("name hash") {
+            if let Some(val_part) = cmd.split("value").nth(1) {
+                if let Ok(mb) = val_part.trim().parse::<usize>() {
+                    engine.set_hash(mb.max(1).min(16384));
                 }
             }
-        } else if cmd == "ucinewgame" {
-            thread_pool.clear();
-        } else if cmd.starts_with("position") {
-            parse_position(cmd, &mut board);
-        } else if cmd.starts_with("go") {
-            parse_go(cmd, &thread_pool, &mut board, &mut book, move_overhead);
-        } else if cmd == "stop" {
-            thread_pool.stop();
-        } else if cmd == "quit" {
-            thread_pool.stop();
-            break;
+        } else if cmd_lower.contains("name multipv") {
+            if let Some(val_part) = cmd.split("value").nth(1)
+                && let Ok(n) = val_part.trim().parse::<usize>()
+            {
+                engine.pool.multipv = n.clamp(1, 64);
+            }
+        } else if cmd_lower.contains("name contempt") {
+            if let Some(val_part) = cmd.split("value").nth(1)
+                && let Ok(cp) = val_part.trim().parse::<i32>()
+            {
+                engine.pool.contempt = cp.clamp(-100, 100);
+            }
+        } else if cmd_lower.contains("name bookfile") {
+            if let Some(val_part) = cmd.split_once("value").map(|(_, v)| v) {
+                engine.book.load(val_part.trim());
+            }
+        } else if cmd_lower.contains("name bookdepth") {
+            if let Some(val_part) = cmd.split("value").nth(1)
+                && let Ok(plies) = val_part.trim().parse::<u32>()
+            {
+                engine.book.max_ply = plies.min(1000);
+            }
+        } else if cmd_lower.contains("name book best move") {
+            if let Some(val_part) = cmd_lower.split("value").nth(1) {
+                engine.book.selection = if val_part.trim() == "true" {
+                    BookSelection::Best
+                } else {
+                    BookSelection::WeightedRandom
+                };
+            }
+        } else if cmd_lower.contains("name uci_showwdl") {
+            if let Some(val_part) = cmd_lower.split("value").nth(1) {
+                engine.pool.show_wdl = val_part.trim() == "true";
+            }
+        } else if cmd_lower.contains("name uci_chess960") {
+            if let Some(val_part) = cmd_lower.split("value").nth(1) {
+                engine.pool.chess960 = val_part.trim() == "true";
+            }
+        } else if cmd_lower.contains("name uci_analysemode") {
+            if let Some(val_part) = cmd_lower.split("value").nth(1) {
+                engine.pool.analyse_mode = val_part.trim() == "true";
+            }
+        } else if cmd_lower.contains("name smpjitter") {
+            // Hidden: disable for reproducible multi-threaded node counts
+            if let Some(val_part) = cmd_lower.split("value").nth(1) {
+                engine.pool.jitter = val_part.trim() != "false";
+            }
+        } else if cmd_lower.contains("name syzygypath") {
+            if let Some(val_part) = cmd.split("value").nth(1) {
+                crate::syzygy::init_global_syzygy(val_part.trim());
+            }
+        } else if let Some((name, val_part)) = cmd_lower
+            .split_once("name")
+            .and_then(|(_, rest)| rest.split_once("value"))
+        {
+            // Hidden tuning options, e.g. `setoption name RFPMargin value 90`
+            if let Ok(val) = val_part.trim().parse::<i32>() {
+                engine.pool.params.set(&name.replace(' ', ""), val);
+            }
         }
+    } else if cmd == "ucinewgame" {
+        engine.new_game();
+    } else if cmd.starts_with("position") {
+        parse_position(cmd, engine, out)?;
+    } else if cmd.starts_with("go") {
+        let limits = parse_go(cmd);
+        let events = events.clone();
+        engine.start(&limits, move |result| {
+            let _ = events.send(Event::BestMove(result));
+        });
+    } else if let Some(mode) = cmd.strip_prefix("debug ") {
+        engine.pool.debug = mode.trim() == "on";
+    } else if cmd == "ponderhit" {
+        engine.ponderhit();
+    } else if cmd == "d" {
+        writeln!(out, "{}", engine.board)?;
+        writeln!(out, "Fen: {}", engine.board.to_fen())?;
+        writeln!(out, "Key: {:016X}", engine.board.zobrist_hash)?;
+        writeln!(out, "Eval: {} cp (white)", evaluate_white(&engine.board))?;
+    } else if cmd == "eval" {
+        writeln!(out, "Final evaluation: {} cp (white side)", evaluate_white(&engine.board))?;
+    } else if cmd == "stop" {
+        engine.stop();
+    } else if cmd == "quit" {
+        engine.stop();
+        return Ok(false);
     }
+
+    out.flush()?;
+    Ok(true)
 }
 
-fn parse_position(cmd: &str, board: &mut Board) {
+fn parse_position(cmd: &str, engine: &mut Engine, out: &mut impl Write) -> io::Result<()> {
     let parts: Vec<&str> = cmd.split_whitespace().collect();
-    let mut moves_idx = 0;
+    let mut moves_idx = parts.len();
+    let mut fen = String::new();
 
     if parts.len() > 1 {
         if parts[1] == "startpos" {
-            *board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
-                .unwrap();
+            fen.push_str("startpos");
             moves_idx = 2;
         } else if parts[1] == "fen" {
-            let mut fen = String::new();
             let mut i = 2;
             while i < parts.len() && parts[i] != "moves" {
                 fen.push_str(parts[i]);
                 fen.push(' ');
                 i += 1;
             }
-            if let Ok(b) = Board::from_fen(fen.trim()) {
-                *board = b;
-            }
             moves_idx = i;
         }
     }
 
-    if moves_idx < parts.len() && parts[moves_idx] == "moves" {
-        for i in (moves_idx + 1)..parts.len() {
-            let move_str = parts[i];
-            let m = parse_move(board, move_str);
-            if m != 0 {
-                board.make_move(m);
-            }
-        }
-    }
-}
+    let moves = if moves_idx < parts.len() && parts[moves_idx] == "moves" {
+        &parts[moves_idx + 1..]
+    } else {
+        &[]
+    };
 
-fn parse_move(board: &Board, move_str: &str) -> Move {
-    let mut move_list = MoveList::new();
-    board.generate_pseudo_legal_moves(&mut move_list);
-    for &m in move_list.iter() {
-        if format(m) == move_str {
-            return m;
-        }
+    match engine.set_position(fen.trim(), moves) {
+        Ok(()) => Ok(()),
+        Err(PositionError::BadFen(e)) => writeln!(out, "info string bad fen: {}", e),
+        Err(PositionError::UnknownMove(m)) => writeln!(out, "info string unknown move {}", m),
+        Err(PositionError::IllegalMove(m)) => writeln!(out, "info string illegal move {}", m),
     }
-    0
 }
 
-fn parse_go(
-    cmd: &str,
-    thread_pool: &ThreadPool,
-    board: &mut Board,
-    book: &mut OpeningBook,
-    move_overhead: u64,
-) {
+/// Tokens that start a new `go` parameter, ending a `searchmoves` list.
+const GO_KEYWORDS: [&str; 12] = [
+    "searchmoves", "ponder", "wtime", "btime", "winc", "binc", "movestogo", "depth", "nodes",
+    "mate", "movetime", "infinite",
+];
+
+fn parse_go(cmd: &str) -> SearchLimits {
     let parts: Vec<&str> = cmd.split_whitespace().collect();
-    let mut depth = 64u8;
-    let mut wtime: u64 = 0;
-    let mut btime: u64 = 0;
-    let mut winc: u64 = 0;
-    let mut binc: u64 = 0;
-    let mut movetime: u64 = 0;
-    let mut movestogo = None;
+    let mut limits = SearchLimits::default();
     let mut i = 1;
 
     while i < parts.len() {
         match parts[i] {
             "depth" => {
                 if i + 1 < parts.len() {
-                    depth = parts[i + 1].parse().unwrap_or(64);
+                    limits.depth = parts[i + 1].parse().unwrap_or(64);
                     i += 1;
                 }
             }
             "wtime" => {
                 if i + 1 < parts.len() {
-                    wtime = parts[i + 1].parse().unwrap_or(0);
+                    limits.wtime = parts[i + 1].parse().unwrap_or(0);
                     i += 1;
                 }
             }
             "btime" => {
                 if i + 1 < parts.len() {
-                    btime = parts[i + 1].parse().unwrap_or(0);
+                    limits.btime = parts[i + 1].parse().unwrap_or(0);
                     i += 1;
                 }
             }
             "winc" => {
                 if i + 1 < parts.len() {
-                    winc = parts[i + 1].parse().unwrap_or(0);
+                    limits.winc = parts[i + 1].parse().unwrap_or(0);
                     i += 1;
                 }
             }
             "binc" => {
                 if i + 1 < parts.len() {
-                    binc = parts[i + 1].parse().unwrap_or(0);
+                    limits.binc = parts[i + 1].parse().unwrap_or(0);
                     i += 1;
                 }
             }
             "movetime" => {
                 if i + 1 < parts.len() {
-                    movetime = parts[i + 1].parse().unwrap_or(0);
+                    limits.movetime = parts[i + 1].parse().unwrap_or(0);
                     i += 1;
                 }
             }
             "movestogo" => {
                 if i + 1 < parts.len() {
-                    movestogo = Some(parts[i + 1].parse().unwrap_or(25));
+                    limits.movestogo = Some(parts[i + 1].parse().unwrap_or(25));
                     i += 1;
                 }
             }
+            "nodes" => {
+                if i + 1 < parts.len() {
+                    limits.nodes = parts[i + 1].parse().unwrap_or(0);
+                    i += 1;
+                }
+            }
+            "mate" => {
+                limits.mate = parts.get(i + 1).and_then(|n| n.parse().ok()).filter(|&n| n > 0);
+                i += 1;
+            }
             "infinite" => {
-                depth = 64;
+                limits.infinite = true;
+            }
+            "ponder" => {
+                limits.ponder = true;
+            }
+            "searchmoves" => {
+                // Runs to the next keyword; the moves are checked at search time
+                while let Some(&token) = parts.get(i + 1) {
+                    if GO_KEYWORDS.contains(&token) {
+                        break;
+                    }
+                    limits.searchmoves.push(token.to_string());
+                    i += 1;
+                }
             }
             _ => {}
         }
         i += 1;
     }
 
-    // Check book first
-    if let Some(book_move) = book.get_move(board.zobrist_hash) {
-        let mut move_list = MoveList::new();
-        board.generate_pseudo_legal_moves(&mut move_list);
-        for &m in move_list.iter() {
-            if crate::moves::from_sq(m) == crate::moves::from_sq(book_move)
-                && crate::moves::to_sq(m) == crate::moves::to_sq(book_move)
-            {
-                println!("bestmove {}", format(m));
-                return;
-            }
+    limits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{book::book_bytes, engine::find_move, movegen, moves::format};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn position_fen_with_four_fields() {
+        movegen::init();
+        let mut engine = Engine::new(1, 16);
+        let mut out = Vec::new();
+        parse_position(
+            "position fen r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - moves f1b5",
+            &mut engine,
+            &mut out,
+        )
+        .unwrap();
+        assert!(out.is_empty());
+        assert_eq!(
+            engine.board.to_fen(),
+            "r1bqkbnr/pppp1ppp/2n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 1 1"
+        );
+
+        let result = engine.go(&parse_go("go depth 1"));
+        let best_move = format(result.best_move.expect("expected a move"));
+        assert!(find_move(&engine.board, &best_move).is_some());
+    }
+
+    #[test]
+    fn readyok_follows_hash_resize() {
+        let mut engine = Engine::new(1, 16);
+        let mut out = Vec::new();
+        let (events, _rx) = mpsc::channel();
+        for cmd in ["setoption name Hash value 256", "isready"] {
+            assert!(handle_command(cmd, &mut engine, &mut out, &events).unwrap());
+        }
+
+        assert_eq!(engine.hash_mb, 256);
+        assert_eq!(String::from_utf8(out).unwrap(), "readyok\n");
+    }
+
+    #[test]
+    fn contempt_option_is_clamped() {
+        let mut engine = Engine::new(1, 16);
+        let mut out = Vec::new();
+        let (events, _rx) = mpsc::channel();
+        for (cmd, expected) in [
+            ("setoption name Contempt value 25", 25),
+            ("setoption name Contempt value -300", -100),
+            ("setoption name Contempt value 101", 100),
+        ] {
+            assert!(handle_command(cmd, &mut engine, &mut out, &events).unwrap());
+            assert_eq!(engine.pool.contempt, expected);
         }
+
+        engine.set_threads(2);
+        assert_eq!(engine.pool.contempt, 100);
     }
 
-    let safety_margin = 50_u64;
-    let time_limit: u64;
-    let hard_limit: u64;
-
-    if movetime > 0 {
-        let spendable = movetime
-            .saturating_sub(move_overhead)
-            .saturating_sub(safety_margin);
-        time_limit = spendable.max(5).min(movetime.saturating_sub(1).max(1));
-        hard_limit = movetime
-            .saturating_sub(5)
-            .max(time_limit + 10)
-            .min(movetime);
-    } else if wtime > 0 || btime > 0 {
-        let (time_left, inc) = if board.side_to_move == Color::White {
-            (wtime, winc)
-        } else {
-            (btime, binc)
+    #[test]
+    fn book_file_and_depth_options() {
+        movegen::init();
+        // Polyglot keys for the start position (e2e4) and after 1. e4 (e7e5)
+        let bytes = book_bytes(&[
+            (0x463b96181691fc9c, (12 << 6) | 28, 1),
+            (0x823c9b50fd114196, (52 << 6) | 36, 1),
+        ]);
+        let path = std::env::temp_dir().join(format!("uci_book_{}.bin", std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+
+        let mut engine = Engine::new(1, 16);
+        let mut out = Vec::new();
+        let (events, _rx) = mpsc::channel();
+        let mut book_move = |engine: &mut Engine, cmd: &str, moves: &[&str]| {
+            assert!(handle_command(cmd, engine, &mut out, &events).unwrap());
+            engine.set_position("startpos", moves).unwrap();
+            let result = engine.go(&parse_go("go depth 1"));
+            result.from_book.then(|| format(result.best_move.unwrap()))
         };
 
-        let real_time_left = time_left.saturating_sub(move_overhead);
-        let usable = real_time_left.saturating_sub(safety_margin);
-
-        if usable == 0 {
-            if inc == 0 {
-                time_limit = 500;
-                hard_limit = 800;
-            } else {
-                let inc_budget = inc.saturating_sub(safety_margin / 2).max(50);
-                time_limit = inc_budget.min(inc);
-                hard_limit = (inc_budget + safety_margin).max(time_limit + 50).min(inc);
-            }
-        } else {
-            let mtg = movestogo.unwrap_or(40).max(1) as u64;
-            let base = usable / mtg;
-            let inc_bonus = inc.saturating_mul(3) / 4;
-            let mut tl = base.saturating_add(inc_bonus).max(50);
-
-            if movestogo.is_none() {
-                let greedy = usable / 5 + inc / 2;
-                tl = tl.min(greedy);
-            }
+        let set_file = format!("setoption name BookFile value {}", path.display());
+        assert_eq!(book_move(&mut engine, &set_file, &[]).as_deref(), Some("e2e4"));
+        assert_eq!(book_move(&mut engine, "isready", &["e2e4"]).as_deref(), Some("e7e5"));
+        assert_eq!(book_move(&mut engine, "setoption name BookDepth value 1", &["e2e4"]), None);
+        assert_eq!(book_move(&mut engine, "isready", &[]).as_deref(), Some("e2e4"));
+        assert_eq!(book_move(&mut engine, "setoption name BookFile value missing.bin", &[]), None);
+        let _ = std::fs::remove_file(path);
+    }
 
-            time_limit = tl.min(usable);
-            hard_limit = (tl * 3 / 2 + safety_margin).min(
-                real_time_left
-                    .saturating_sub(safety_margin / 2)
-                    .max(tl + 50),
-            );
+    #[test]
+    fn book_best_move_option_sets_the_selection() {
+        let mut engine = Engine::new(1, 16);
+        let mut out = Vec::new();
+        let (events, _rx) = mpsc::channel();
+        for (cmd, expected) in [
+            ("setoption name Book Best Move value true", BookSelection::Best),
+            ("setoption name Book Best Move value false", BookSelection::WeightedRandom),
+        ] {
+            assert!(handle_command(cmd, &mut engine, &mut out, &events).unwrap());
+            assert_eq!(engine.book.selection, expected);
         }
-    } else {
-        // Infinite search or depth-only
-        time_limit = u64::MAX;
-        hard_limit = u64::MAX;
     }
 
-    let (_score, best_move) =
-        thread_pool.search(board, depth, time_limit as u128, hard_limit as u128);
+    #[test]
+    fn multipv_option_reaches_the_pool() {
+        movegen::init();
+        let mut engine = Engine::new(1, 16);
+        let mut out = Vec::new();
+        let (events, _rx) = mpsc::channel();
+        for (cmd, expected) in [
+            ("setoption name MultiPV value 3", 3),
+            ("setoption name MultiPV value 500", 64),
+            ("setoption name MultiPV value 0", 1),
+            ("setoption name MultiPV value 4", 4),
+        ] {
+            assert!(handle_command(cmd, &mut engine, &mut out, &events).unwrap());
+            assert_eq!(engine.pool.multipv, expected);
+        }
+
+        engine.set_threads(2);
+        assert_eq!(engine.pool.multipv, 4);
 
-    if let Some(m) = best_move {
-        println!("bestmove {}", format(m));
-    } else {
-        println!("bestmove 0000");
+        engine.set_position("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", &[]).unwrap();
+        let result = engine.go(&parse_go("go depth 5"));
+        assert_eq!(format(result.best_move.unwrap()), "d2d5");
+    }
+
+    #[test]
+    fn input_without_quit_terminates() {
+        let mut engine = Engine::new(1, 16);
+        let mut out = Vec::new();
+        command_loop(&b"isready\nposition startpos\n"[..], &mut engine, &mut out);
+        assert_eq!(String::from_utf8(out).unwrap(), "readyok\n");
+    }
+
+    #[test]
+    fn invalid_utf8_ends_the_loop() {
+        let mut engine = Engine::new(1, 16);
+        let mut out = Vec::new();
+        command_loop(&b"isready\n\xff\xfe\nisready\n"[..], &mut engine, &mut out);
+        assert_eq!(String::from_utf8(out).unwrap(), "readyok\n");
+    }
+
+    #[test]
+    fn ponderhit_switches_to_the_clock() {
+        movegen::init();
+        let mut engine = Engine::new(1, 16);
+        let mut out = Vec::new();
+        let (events, rx) = mpsc::channel();
+        for cmd in ["position startpos moves e2e4 e7e5", "go ponder wtime 1000 btime 1000"] {
+            assert!(handle_command(cmd, &mut engine, &mut out, &events).unwrap());
+        }
+
+        // Pondering never reports on its own, however long it runs
+        thread::sleep(Duration::from_millis(300));
+        assert!(rx.try_recv().is_err());
+
+        let start = Instant::now();
+        assert!(handle_command("ponderhit", &mut engine, &mut out, &events).unwrap());
+        let event = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(matches!(event, Event::BestMove(SearchResult { best_move: Some(_), .. })));
+        assert!(start.elapsed().as_millis() < 1000, "took {:?}", start.elapsed());
+        engine.wait();
+    }
+
+    #[test]
+    fn ponder_stop_answers_for_the_pondered_position() {
+        movegen::init();
+        let mut engine = Engine::new(1, 16);
+        let mut out = Vec::new();
+        let (events, rx) = mpsc::channel();
+        // e7e5 is the expected reply we are pondering on, so black's move is already made
+        for cmd in ["position startpos moves e2e4 e7e5", "go ponder wtime 1000 btime 1000"] {
+            assert!(handle_command(cmd, &mut engine, &mut out, &events).unwrap());
+        }
+        thread::sleep(Duration::from_millis(100));
+        assert!(rx.try_recv().is_err());
+
+        assert!(handle_command("stop", &mut engine, &mut out, &events).unwrap());
+        let Event::BestMove(SearchResult { best_move: Some(m), .. }) =
+            rx.recv_timeout(Duration::from_secs(5)).unwrap()
+        else {
+            panic!("expected a best move after stop");
+        };
+        engine.wait();
+        assert!(find_move(&engine.board, &format(m)).is_some());
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn position_errors_are_reported() {
+        movegen::init();
+        let mut engine = Engine::new(1, 16);
+        let mut report = |cmd: &str| {
+            let mut out = Vec::new();
+            parse_position(cmd, &mut engine, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(report("position startpos moves e2e5"), "info string unknown move e2e5\n");
+        let pinned = "position fen 4k3/4r3/8/8/8/8/4B3/4K3 w - - 0 1 moves e2d3";
+        assert_eq!(report(pinned), "info string illegal move e2d3\n");
+        assert!(report("position fen 8/8/8 w - -").starts_with("info string bad fen: "));
+    }
+
+    #[test]
+    fn chess960_castling_is_king_takes_rook() {
+        movegen::init();
+        let mut engine = Engine::new(1, 16);
+        let mut out = Vec::new();
+        let (events, _rx) = mpsc::channel();
+        // The king already stands on its castled square
+        let fen = "position fen 4k3/8/8/8/8/8/8/6KR w H - 0 1";
+        assert!(handle_command(fen, &mut engine, &mut out, &events).unwrap());
+        let castle = find_move(&engine.board, "g1g1").unwrap();
+        assert_eq!(format_uci(castle, &engine.board, true), "g1h1");
+
+        let option = "setoption name UCI_Chess960 value true";
+        assert!(handle_command(option, &mut engine, &mut out, &events).unwrap());
+        let result = SearchResult {
+            best_move: Some(castle),
+            ponder_move: None,
+            score: 0,
+            nodes: 0,
+            from_book: false,
+        };
+        write_bestmove(&mut out, &engine, &result).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "bestmove g1h1\n");
+
+        let mut out = Vec::new();
+        let played = format!("{} moves g1h1", fen);
+        assert!(handle_command(&played, &mut engine, &mut out, &events).unwrap());
+        assert!(out.is_empty());
+        assert_eq!(engine.board.to_fen(), "4k3/8/8/8/8/8/8/5RK1 b - - 1 1");
+    }
+
+    #[test]
+    fn bounded_search_reports_after_eof() {
+        movegen::init();
+        let mut engine = Engine::new(1, 16);
+        let mut out = Vec::new();
+        command_loop(&b"position startpos moves e2e4\ngo depth 3\n"[..], &mut engine, &mut out);
+        assert!(String::from_utf8(out).unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn bestmove_names_the_expected_reply() {
+        movegen::init();
+        let kiwi = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut engine = Engine::new(1, 16);
+        let mut out = Vec::new();
+        let input = format!("position fen {}\ngo depth 6\n", kiwi);
+        command_loop(std::io::Cursor::new(input), &mut engine, &mut out);
+
+        let out = String::from_utf8(out).unwrap();
+        let parts: Vec<&str> = out.split_whitespace().collect();
+        assert!(matches!(parts[..], ["bestmove", _, "ponder", _]), "got {:?}", out);
+        engine.set_position(kiwi, &[parts[1], parts[3]]).unwrap();
+    }
+
+    #[test]
+    fn parse_go_keeps_all_limits() {
+        let limits = parse_go("go depth 20 movetime 5000 nodes 100000");
+        assert_eq!(limits.depth, 20);
+        assert_eq!(limits.movetime, 5000);
+        assert_eq!(limits.nodes, 100000);
+
+        let limits = parse_go("go infinite depth 7");
+        assert!(limits.infinite);
+        assert_eq!(limits.depth, 7);
+
+        let limits = parse_go("go ponder wtime 1000 btime 1000");
+        assert!(limits.ponder);
+        assert_eq!(limits.wtime, 1000);
+
+        let limits = parse_go("go searchmoves e2e4 d2d4 depth 5");
+        assert_eq!(limits.searchmoves, ["e2e4", "d2d4"]);
+        assert_eq!(limits.depth, 5);
+
+        let limits = parse_go("go mate 3 movetime 1000");
+        assert_eq!(limits.mate, Some(3));
+        assert_eq!(limits.movetime, 1000);
     }
 }
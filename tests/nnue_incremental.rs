@@ -0,0 +1,87 @@
+//! Incremental NNUE updates checked against full refreshes. These live in
+//! their own test binary because loading the network enables NNUE for the
+//! whole process, which would change the classical eval the unit tests see.
+
+use chess_engine::{board::Board, engine, movegen, nnue};
+
+fn load_network() {
+    movegen::init();
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/nn-62ef826d1a6d.nnue");
+    if nnue::NETWORK.get().is_none() {
+        let net = nnue::Network::load(path).expect("network file");
+        nnue::NETWORK.set(net).ok();
+    }
+    nnue::init_cpu_features();
+    assert!(nnue::is_enabled());
+}
+
+fn assert_matches_refresh(board: &Board, context: &str) {
+    let fresh = nnue::refresh_accumulator(board);
+    for (side, (ours, fresh)) in board.accumulator.iter().zip(&fresh).enumerate() {
+        assert_eq!(ours.values, fresh.values, "{} (side {})", context, side);
+    }
+}
+
+#[test]
+fn king_moves_match_a_full_refresh() {
+    load_network();
+    // Quiet king steps, a king capture and castling on both wings
+    let lines = [
+        ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", &["e1g1", "e8c8", "g1h1", "c8b8"][..]),
+        ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", &["e1c1", "e8g8", "c1b1", "g8h8"][..]),
+        ("4k3/8/8/8/8/8/4p3/4K3 w - - 0 1", &["e1e2", "e8d7", "e2d3", "d7c6"][..]),
+    ];
+
+    for (fen, line) in lines {
+        let mut board = Board::from_fen(fen).unwrap();
+        let mut played = Vec::new();
+        for uci in line {
+            let m = engine::find_move(&board, uci).unwrap();
+            let undo = board.make_move(m);
+            assert_matches_refresh(&board, uci);
+            assert_eq!(nnue::evaluate(&board), nnue::evaluate(&Board::from_fen(&board.to_fen()).unwrap()));
+            played.push((m, undo));
+        }
+        while let Some((m, undo)) = played.pop() {
+            board.unmake_move(m, undo);
+            assert_matches_refresh(&board, "unmake");
+        }
+        assert_eq!(board.to_fen(), fen);
+    }
+}
+
+#[test]
+fn deep_make_unmake_restores_the_accumulator() {
+    use chess_engine::moves::MoveList;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    load_network();
+    let mut rng = StdRng::seed_from_u64(0x2297);
+    let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    for _ in 0..10 {
+        let mut board = Board::from_fen(fen).unwrap();
+        let start = board.accumulator;
+        let mut played = Vec::new();
+        for _ in 0..60 {
+            let mut legal = MoveList::new();
+            movegen::generate_legal_moves(&mut board, &mut legal);
+            if legal.len() == 0 {
+                break;
+            }
+            let m = legal.get(rng.random_range(0..legal.len()));
+            played.push((m, board.make_move(m)));
+            assert_matches_refresh(&board, &board.to_fen());
+        }
+        assert_eq!(board.accumulator_stack.len(), board.history.len());
+
+        while let Some((m, undo)) = played.pop() {
+            board.unmake_move(m, undo);
+            assert_matches_refresh(&board, &board.to_fen());
+        }
+        assert!(board.accumulator_stack.is_empty());
+        for (restored, start) in board.accumulator.iter().zip(&start) {
+            assert_eq!(restored.values, start.values);
+        }
+    }
+}